@@ -133,4 +133,23 @@ impl Config {
             .append(config.as_object_mut().unwrap());
         Ok(())
     }
+
+    /// Adds the fields of a component-specific configuration that aren't in the configuration
+    /// file yet, keeping the values of the fields that are.  Returns the names of the fields that
+    /// were added.
+    pub fn add_missing<C>(&mut self, config: C) -> Result<Vec<String>>
+    where
+        C: Serialize,
+    {
+        let config = serde_json::to_value(&config)?;
+        let existing = self.config.as_object_mut().unwrap();
+        let mut added = Vec::new();
+        for (key, value) in config.as_object().unwrap() {
+            if !existing.contains_key(key) {
+                existing.insert(key.clone(), value.clone());
+                added.push(key.clone());
+            }
+        }
+        Ok(added)
+    }
 }
@@ -1,8 +1,9 @@
 #![doc = include_str!("../README.md")]
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -23,6 +24,13 @@ use tree_sitter_tags::{Error as TagsError, TagsConfiguration};
 
 pub const EMSCRIPTEN_TAG: &str = concat!("docker.io/emscripten/emsdk:", env!("EMSCRIPTEN_VERSION"));
 
+// The name of the files that map the paths in a directory to language scopes.
+const SCOPES_FILE_NAME: &str = ".tree-sitter-scopes";
+
+// The number of bytes read from the start of a file when falling back to
+// content regexes to select its language.
+const CONTENT_REGEX_PREFIX_LENGTH: u64 = 16 * 1024;
+
 #[derive(Default, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
@@ -101,6 +109,7 @@ pub struct LanguageConfiguration<'a> {
 
 pub struct Loader {
     pub parser_lib_path: PathBuf,
+    other_parser_lib_paths: Vec<PathBuf>,
     languages_by_id: Vec<(PathBuf, OnceCell<Language>, Option<Vec<PathBuf>>)>,
     language_configurations: Vec<LanguageConfiguration<'static>>,
     language_configuration_ids_by_file_type: HashMap<String, Vec<usize>>,
@@ -109,6 +118,9 @@ pub struct Loader {
     highlight_names: Box<Mutex<Vec<String>>>,
     use_all_highlight_names: bool,
     debug_build: bool,
+    log_language_selection: bool,
+    scope_mappings_by_dir: HashMap<PathBuf, Option<Vec<(glob::Pattern, String)>>>,
+    scope_overrides: Vec<(glob::Pattern, String)>,
 
     #[cfg(feature = "wasm")]
     wasm_store: Mutex<Option<tree_sitter::WasmStore>>,
@@ -148,21 +160,34 @@ unsafe impl Send for Loader {}
 unsafe impl Sync for Loader {}
 
 impl Loader {
+    /// Create a loader that stores compiled parsers in `TREE_SITTER_LIBDIR`, or in the user's
+    /// cache directory. `TREE_SITTER_LIBDIR` can be a list of directories, separated like the
+    /// entries in `PATH`. See [`Loader::add_parser_lib_path`] for how they are used.
     pub fn new() -> Result<Self> {
-        let parser_lib_path = match env::var("TREE_SITTER_LIBDIR") {
-            Ok(path) => PathBuf::from(path),
-            _ => dirs::cache_dir()
-                .ok_or_else(|| anyhow!("Cannot determine cache directory"))?
-                .join("tree-sitter")
-                .join("lib"),
+        let mut parser_lib_paths = env::var_os("TREE_SITTER_LIBDIR")
+            .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter();
+        let mut loader = match parser_lib_paths.next() {
+            Some(path) => Self::with_parser_lib_path(path),
+            None => Self::with_parser_lib_path(
+                dirs::cache_dir()
+                    .ok_or_else(|| anyhow!("Cannot determine cache directory"))?
+                    .join("tree-sitter")
+                    .join("lib"),
+            ),
         };
-        Ok(Self::with_parser_lib_path(parser_lib_path))
+        for path in parser_lib_paths {
+            loader.add_parser_lib_path(path);
+        }
+        Ok(loader)
     }
 
     #[must_use]
     pub fn with_parser_lib_path(parser_lib_path: PathBuf) -> Self {
         Self {
             parser_lib_path,
+            other_parser_lib_paths: Vec::new(),
             languages_by_id: Vec::new(),
             language_configurations: Vec::new(),
             language_configuration_ids_by_file_type: HashMap::new(),
@@ -171,12 +196,23 @@ impl Loader {
             highlight_names: Box::new(Mutex::new(Vec::new())),
             use_all_highlight_names: true,
             debug_build: false,
+            log_language_selection: false,
+            scope_mappings_by_dir: HashMap::new(),
+            scope_overrides: Vec::new(),
 
             #[cfg(feature = "wasm")]
             wasm_store: Mutex::default(),
         }
     }
 
+    /// Add another directory to look for compiled parsers in. When loading a parser, the
+    /// library in the first directory that has one is used, starting with `parser_lib_path`,
+    /// and it is recompiled there if it is out of date. Parsers that aren't in any of the
+    /// directories yet are compiled into `parser_lib_path`.
+    pub fn add_parser_lib_path(&mut self, path: PathBuf) {
+        self.other_parser_lib_paths.push(path);
+    }
+
     pub fn configure_highlights(&mut self, names: &[String]) {
         self.use_all_highlight_names = false;
         let mut highlights = self.highlight_names.lock().unwrap();
@@ -240,6 +276,32 @@ impl Loader {
             .collect()
     }
 
+    /// Load the language of a configuration, compiling it if needed.
+    pub fn language_for_configuration(
+        &self,
+        configuration: &LanguageConfiguration,
+    ) -> Result<Language> {
+        self.language_for_id(configuration.language_id)
+    }
+
+    /// Like `language_for_configuration`, but the language is only loaded if its
+    /// library was already compiled. The grammar is never compiled.
+    pub fn compiled_language_for_configuration(
+        &self,
+        configuration: &LanguageConfiguration,
+    ) -> Result<Option<Language>> {
+        let (_, language, _) = &self.languages_by_id[configuration.language_id];
+        if let Some(language) = language.get() {
+            return Ok(Some(language.clone()));
+        }
+        let library_path = self.library_path(&configuration.language_name);
+        if !library_path.exists() {
+            return Ok(None);
+        }
+        self.load_compiled_language(&configuration.language_name, &library_path)
+            .map(Some)
+    }
+
     pub fn language_configuration_for_scope(
         &self,
         scope: &str,
@@ -257,30 +319,51 @@ impl Loader {
         &self,
         path: &Path,
     ) -> Result<Option<(Language, &LanguageConfiguration)>> {
-        self.language_configuration_ids_by_first_line_regex
-            .iter()
-            .try_fold(None, |_, (regex, ids)| {
-                if let Some(regex) = Self::regex(Some(regex)) {
-                    let file = fs::File::open(path)?;
-                    let reader = BufReader::new(file);
-                    let first_line = reader.lines().next().transpose()?;
-                    if let Some(first_line) = first_line {
-                        if regex.is_match(&first_line) && !ids.is_empty() {
-                            let configuration = &self.language_configurations[ids[0]];
-                            let language = self.language_for_id(configuration.language_id)?;
-                            return Ok(Some((language, configuration)));
-                        }
-                    }
-                }
+        if self
+            .language_configuration_ids_by_first_line_regex
+            .is_empty()
+        {
+            return Ok(None);
+        }
+        let file = fs::File::open(path)?;
+        let reader = BufReader::new(file);
+        match reader.lines().next().transpose()? {
+            Some(first_line) => self.language_configuration_for_first_line(&first_line),
+            None => Ok(None),
+        }
+    }
 
-                Ok(None)
-            })
+    fn language_configuration_for_first_line(
+        &self,
+        first_line: &str,
+    ) -> Result<Option<(Language, &LanguageConfiguration<'_>)>> {
+        for (regex, ids) in &self.language_configuration_ids_by_first_line_regex {
+            if let Some(regex) = Self::regex(Some(regex)) {
+                if regex.is_match(first_line) && !ids.is_empty() {
+                    let configuration = &self.language_configurations[ids[0]];
+                    let language = self.language_for_id(configuration.language_id)?;
+                    return Ok(Some((language, configuration)));
+                }
+            }
+        }
+        Ok(None)
     }
 
     pub fn language_configuration_for_file_name(
         &self,
         path: &Path,
     ) -> Result<Option<(Language, &LanguageConfiguration)>> {
+        self.language_configuration_for_file_name_with_contents(path, None)
+    }
+
+    /// Like `language_configuration_for_file_name`, but when given, the `contents`
+    /// take the place of the file at `path` for choosing between the
+    /// configurations that match its name.
+    pub fn language_configuration_for_file_name_with_contents(
+        &self,
+        path: &Path,
+        contents: Option<&[u8]>,
+    ) -> Result<Option<(Language, &LanguageConfiguration<'_>)>> {
         // Find all the language configurations that match this file name
         // or a suffix of the file name.
         let configuration_ids = path
@@ -303,37 +386,16 @@ impl Loader {
                 // If multiple language configurations match, then determine which
                 // one to use by applying the configurations' content regexes.
                 else {
-                    let file_contents =
-                        fs::read(path).with_context(|| format!("Failed to read path {path:?}"))?;
+                    let file_contents = match contents {
+                        Some(contents) => Cow::Borrowed(contents),
+                        None => Cow::Owned(
+                            fs::read(path)
+                                .with_context(|| format!("Failed to read path {path:?}"))?,
+                        ),
+                    };
                     let file_contents = String::from_utf8_lossy(&file_contents);
-                    let mut best_score = -2isize;
-                    let mut best_configuration_id = None;
-                    for configuration_id in configuration_ids {
-                        let config = &self.language_configurations[*configuration_id];
-
-                        // If the language configuration has a content regex, assign
-                        // a score based on the length of the first match.
-                        let score;
-                        if let Some(content_regex) = &config.content_regex {
-                            if let Some(mat) = content_regex.find(&file_contents) {
-                                score = (mat.end() - mat.start()) as isize;
-                            }
-                            // If the content regex does not match, then *penalize* this
-                            // language configuration, so that language configurations
-                            // without content regexes are preferred over those with
-                            // non-matching content regexes.
-                            else {
-                                score = -1;
-                            }
-                        } else {
-                            score = 0;
-                        }
-                        if score > best_score {
-                            best_configuration_id = Some(*configuration_id);
-                            best_score = score;
-                        }
-                    }
-
+                    let (best_configuration_id, _) =
+                        self.best_configuration_for_content(configuration_ids, &file_contents);
                     &self.language_configurations[best_configuration_id.unwrap()]
                 };
 
@@ -345,6 +407,83 @@ impl Loader {
         Ok(None)
     }
 
+    /// Select a language by matching every configuration's content regex against
+    /// the beginning of the file, for files whose name doesn't identify a language.
+    pub fn language_configuration_for_content_regex(
+        &self,
+        path: &Path,
+    ) -> Result<Option<(Language, &LanguageConfiguration<'_>)>> {
+        let mut file_contents = Vec::new();
+        fs::File::open(path)
+            .and_then(|file| {
+                file.take(CONTENT_REGEX_PREFIX_LENGTH)
+                    .read_to_end(&mut file_contents)
+            })
+            .with_context(|| format!("Failed to read path {path:?}"))?;
+        self.language_configuration_for_content(&file_contents)
+    }
+
+    fn language_configuration_for_content(
+        &self,
+        file_contents: &[u8],
+    ) -> Result<Option<(Language, &LanguageConfiguration<'_>)>> {
+        let length = file_contents
+            .len()
+            .min(CONTENT_REGEX_PREFIX_LENGTH as usize);
+        let file_contents = String::from_utf8_lossy(&file_contents[..length]);
+
+        let configuration_ids = self
+            .language_configurations
+            .iter()
+            .enumerate()
+            .filter(|(_, configuration)| configuration.content_regex.is_some())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        match self.best_configuration_for_content(&configuration_ids, &file_contents) {
+            (Some(id), score) if score >= 0 => {
+                let configuration = &self.language_configurations[id];
+                let language = self.language_for_id(configuration.language_id)?;
+                Ok(Some((language, configuration)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn best_configuration_for_content(
+        &self,
+        configuration_ids: &[usize],
+        file_contents: &str,
+    ) -> (Option<usize>, isize) {
+        let mut best_score = -2isize;
+        let mut best_configuration_id = None;
+        for configuration_id in configuration_ids {
+            let config = &self.language_configurations[*configuration_id];
+
+            // If the language configuration has a content regex, assign
+            // a score based on the length of the first match.
+            let score;
+            if let Some(content_regex) = &config.content_regex {
+                if let Some(mat) = content_regex.find(file_contents) {
+                    score = (mat.end() - mat.start()) as isize;
+                }
+                // If the content regex does not match, then *penalize* this
+                // language configuration, so that language configurations
+                // without content regexes are preferred over those with
+                // non-matching content regexes.
+                else {
+                    score = -1;
+                }
+            } else {
+                score = 0;
+            }
+            if score > best_score {
+                best_configuration_id = Some(*configuration_id);
+                best_score = score;
+            }
+        }
+        (best_configuration_id, best_score)
+    }
+
     pub fn language_configuration_for_injection_string(
         &self,
         string: &str,
@@ -415,31 +554,41 @@ impl Loader {
         self.load_language_at_path_with_name(config)
     }
 
-    pub fn load_language_at_path_with_name(&self, mut config: CompileConfig) -> Result<Language> {
-        let mut lib_name = config.name.to_string();
-        let language_fn_name = format!(
-            "tree_sitter_{}",
-            replace_dashes_with_underscores(&config.name)
-        );
+    /// The path of the compiled library for the grammar with the given name. This is
+    /// the first existing library in the parser library directories, or the path
+    /// in `parser_lib_path` that it would be compiled to.
+    #[must_use]
+    pub fn library_path(&self, name: &str) -> PathBuf {
+        let mut lib_name = name.to_string();
         if self.debug_build {
             lib_name.push_str(".debug._");
         }
+        let library_path = |dir: &Path| {
+            let mut path = dir.join(&lib_name);
+            path.set_extension(env::consts::DLL_EXTENSION);
+            #[cfg(feature = "wasm")]
+            if self.wasm_store.lock().unwrap().is_some() {
+                path.set_extension("wasm");
+            }
+            path
+        };
+        std::iter::once(&self.parser_lib_path)
+            .chain(&self.other_parser_lib_paths)
+            .map(|dir| library_path(dir))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| library_path(&self.parser_lib_path))
+    }
 
+    pub fn load_language_at_path_with_name(&self, mut config: CompileConfig) -> Result<Language> {
         if config.output_path.is_none() {
             fs::create_dir_all(&self.parser_lib_path)?;
         }
 
         let mut recompile = config.output_path.is_some(); // if specified, always recompile
 
-        let output_path = config.output_path.unwrap_or_else(|| {
-            let mut path = self.parser_lib_path.join(lib_name);
-            path.set_extension(env::consts::DLL_EXTENSION);
-            #[cfg(feature = "wasm")]
-            if self.wasm_store.lock().unwrap().is_some() {
-                path.set_extension("wasm");
-            }
-            path
-        });
+        let output_path = config
+            .output_path
+            .unwrap_or_else(|| self.library_path(&config.name));
         config.output_path = Some(output_path.clone());
 
         let parser_path = config.src_path.join("parser.c");
@@ -465,7 +614,7 @@ impl Loader {
         }
 
         #[cfg(feature = "wasm")]
-        if let Some(wasm_store) = self.wasm_store.lock().unwrap().as_mut() {
+        if self.wasm_store.lock().unwrap().is_some() {
             if recompile {
                 self.compile_parser_to_wasm(
                     &config.name,
@@ -479,8 +628,7 @@ impl Loader {
                 )?;
             }
 
-            let wasm_bytes = fs::read(&output_path)?;
-            return Ok(wasm_store.load_language(&config.name, &wasm_bytes)?);
+            return self.load_compiled_language(&config.name, &output_path);
         }
 
         let lock_path = if env::var("CROSS_RUNNER").is_ok() {
@@ -535,8 +683,19 @@ impl Loader {
             }
         }
 
-        let library = unsafe { Library::new(&output_path) }
-            .with_context(|| format!("Error opening dynamic library {output_path:?}"))?;
+        self.load_compiled_language(&config.name, &output_path)
+    }
+
+    fn load_compiled_language(&self, name: &str, library_path: &Path) -> Result<Language> {
+        #[cfg(feature = "wasm")]
+        if let Some(wasm_store) = self.wasm_store.lock().unwrap().as_mut() {
+            let wasm_bytes = fs::read(library_path)?;
+            return Ok(wasm_store.load_language(name, &wasm_bytes)?);
+        }
+
+        let language_fn_name = format!("tree_sitter_{}", replace_dashes_with_underscores(name));
+        let library = unsafe { Library::new(library_path) }
+            .with_context(|| format!("Error opening dynamic library {library_path:?}"))?;
         let language = unsafe {
             let language_fn = library
                 .get::<Symbol<unsafe extern "C" fn() -> Language>>(language_fn_name.as_bytes())
@@ -1095,23 +1254,67 @@ impl Loader {
         pattern.and_then(|r| RegexBuilder::new(r).multi_line(true).build().ok())
     }
 
+    /// Select the language for a file. An explicit `scope` takes precedence,
+    /// then the scope overrides, then a `.tree-sitter-scopes` file in the
+    /// file's directory or one of its ancestors, then the file's name, the
+    /// grammar in `current_dir`, and finally the file's first line and content.
     pub fn select_language(
         &mut self,
         path: &Path,
         current_dir: &Path,
         scope: Option<&str>,
+    ) -> Result<Language> {
+        self.select_language_with_contents(path, current_dir, scope, None)
+    }
+
+    /// Like `select_language`, but when given, the `contents` take the place of
+    /// the file at `path` for matching the first line and content regexes, as
+    /// for a decompressed file whose path is its name without the `.gz` suffix.
+    pub fn select_language_with_contents(
+        &mut self,
+        path: &Path,
+        current_dir: &Path,
+        scope: Option<&str>,
+        contents: Option<&[u8]>,
     ) -> Result<Language> {
         if let Some(scope) = scope {
             if let Some(config) = self
                 .language_configuration_for_scope(scope)
                 .with_context(|| format!("Failed to load language for scope '{scope}'"))?
             {
+                self.log_selection(path, "the given scope");
                 Ok(config.0)
             } else {
                 Err(anyhow!("Unknown scope '{scope}'"))
             }
+        } else if let Some(scope) = find_mapped_scope(
+            &self.scope_overrides,
+            path,
+            path.strip_prefix(".").unwrap_or(path),
+        ) {
+            if let Some(config) = self
+                .language_configuration_for_scope(&scope)
+                .with_context(|| format!("Failed to load language for scope '{scope}'"))?
+            {
+                self.log_selection(path, &format!("the scope override for '{scope}'"));
+                Ok(config.0)
+            } else {
+                Err(anyhow!("Unknown scope '{scope}', given for {path:?}"))
+            }
+        } else if let Some((scopes_path, scope)) = self.scope_for_path(&current_dir.join(path))? {
+            if let Some(config) = self
+                .language_configuration_for_scope(&scope)
+                .with_context(|| format!("Failed to load language for scope '{scope}'"))?
+            {
+                self.log_selection(path, &format!("the scope mapping in {scopes_path:?}"));
+                Ok(config.0)
+            } else {
+                Err(anyhow!(
+                    "Unknown scope '{scope}', mapped in {scopes_path:?}"
+                ))
+            }
         } else if let Some((lang, _)) = self
-            .language_configuration_for_file_name(path)
+            .language_configuration_for_file_name_with_contents(path, contents)
             .with_context(|| {
                 format!(
                     "Failed to load language for file name {}",
@@ -1119,8 +1322,10 @@ impl Loader {
                 )
             })?
         {
+            self.log_selection(path, "its file name");
             Ok(lang)
         } else if let Some(id) = self.language_configuration_in_current_path {
+            self.log_selection(path, "the grammar in the current directory");
             Ok(self.language_for_id(self.language_configurations[id].language_id)?)
         } else if let Some(lang) = self
             .languages_at_path(current_dir)
@@ -1128,14 +1333,88 @@ impl Loader {
             .first()
             .cloned()
         {
+            self.log_selection(path, "the grammar in the current directory");
             Ok(lang.0)
-        } else if let Some(lang) = self.language_configuration_for_first_line_regex(path)? {
+        } else if let Some(lang) = match contents {
+            Some(contents) => {
+                let first_line = contents.split(|b| *b == b'\n').next().unwrap_or_default();
+                let first_line = String::from_utf8_lossy(first_line);
+                self.language_configuration_for_first_line(first_line.trim_end_matches('\r'))?
+            }
+            None => self.language_configuration_for_first_line_regex(path)?,
+        } {
+            self.log_selection(path, "its first line");
+            Ok(lang.0)
+        } else if let Some(lang) = match contents {
+            Some(contents) => self.language_configuration_for_content(contents)?,
+            None => self.language_configuration_for_content_regex(path)?,
+        } {
+            self.log_selection(path, "its content");
             Ok(lang.0)
         } else {
             Err(anyhow!("No language found"))
         }
     }
 
+    /// Find the scope that the nearest `.tree-sitter-scopes` file maps the given
+    /// path to, walking up from the path's directory. Each line of such a file is
+    /// `glob = scope`, where the glob is relative to the file's directory, and
+    /// matches only the file name if it has no `/`. Within a file, the last
+    /// matching line wins. Blank lines and lines starting with `#` are ignored.
+    fn scope_for_path(&mut self, path: &Path) -> Result<Option<(PathBuf, String)>> {
+        for dir in path.ancestors().skip(1) {
+            let Some(mappings) = self.scope_mappings_in_dir(dir)? else {
+                continue;
+            };
+            let Ok(relative_path) = path.strip_prefix(dir) else {
+                continue;
+            };
+            if let Some(scope) = find_mapped_scope(mappings, path, relative_path) {
+                return Ok(Some((dir.join(SCOPES_FILE_NAME), scope)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn scope_mappings_in_dir(&mut self, dir: &Path) -> Result<Option<&[(glob::Pattern, String)]>> {
+        if !self.scope_mappings_by_dir.contains_key(dir) {
+            let scopes_path = dir.join(SCOPES_FILE_NAME);
+            let mappings = if scopes_path.is_file() {
+                let contents = fs::read_to_string(&scopes_path)
+                    .with_context(|| format!("Failed to read {scopes_path:?}"))?;
+                Some(
+                    parse_scope_mappings(&contents)
+                        .with_context(|| format!("Invalid scope mapping in {scopes_path:?}"))?,
+                )
+            } else {
+                None
+            };
+            self.scope_mappings_by_dir
+                .insert(dir.to_path_buf(), mappings);
+        }
+        Ok(self.scope_mappings_by_dir[dir].as_deref())
+    }
+
+    /// Select the language of the files that match a glob by its scope, with a
+    /// mapping in the same `glob = scope` form as the lines of `.tree-sitter-scopes`
+    /// files. These overrides take precedence over those files, and when several
+    /// match a path, the last one that was added wins. Globs are matched against
+    /// paths as they are given to `select_language`.
+    pub fn add_scope_override(&mut self, mapping: &str) -> Result<()> {
+        self.scope_overrides.push(parse_scope_mapping(mapping)?);
+        Ok(())
+    }
+
+    fn log_selection(&self, path: &Path, heuristic: &str) {
+        if self.log_language_selection {
+            eprintln!("Selected the language for {path:?} by {heuristic}");
+        }
+    }
+
+    pub fn log_language_selection(&mut self, flag: bool) {
+        self.log_language_selection = flag;
+    }
+
     pub fn use_debug_build(&mut self, flag: bool) {
         self.debug_build = flag;
     }
@@ -1396,3 +1675,183 @@ fn replace_dashes_with_underscores(name: &str) -> String {
     }
     result
 }
+
+fn parse_scope_mappings(contents: &str) -> Result<Vec<(glob::Pattern, String)>> {
+    let mut mappings = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        mappings.push(parse_scope_mapping(line).with_context(|| format!("Line {}", i + 1))?);
+    }
+    Ok(mappings)
+}
+
+fn parse_scope_mapping(mapping: &str) -> Result<(glob::Pattern, String)> {
+    let (glob, scope) = mapping
+        .split_once('=')
+        .map(|(glob, scope)| (glob.trim(), scope.trim()))
+        .filter(|(glob, scope)| !glob.is_empty() && !scope.is_empty())
+        .ok_or_else(|| anyhow!("Expected `glob = scope`, not {mapping:?}"))?;
+    let pattern = glob::Pattern::new(glob.trim_start_matches("./").trim_start_matches('/'))
+        .with_context(|| format!("Invalid glob {glob:?}"))?;
+    Ok((pattern, scope.to_string()))
+}
+
+/// Find the scope of the last mapping whose glob matches the path. A glob with no
+/// `/` matches only the file name, and other globs match the relative path.
+fn find_mapped_scope(
+    mappings: &[(glob::Pattern, String)],
+    path: &Path,
+    relative_path: &Path,
+) -> Option<String> {
+    let match_options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    mappings.iter().rev().find_map(|(pattern, scope)| {
+        let is_match = if pattern.as_str().contains('/') {
+            pattern.matches_path_with(relative_path, match_options)
+        } else {
+            path.file_name()
+                .is_some_and(|name| pattern.matches_path_with(Path::new(name), match_options))
+        };
+        is_match.then(|| scope.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_overrides() {
+        let mut loader = Loader::with_parser_lib_path(PathBuf::new());
+        loader.add_scope_override("*.h = source.c").unwrap();
+        loader
+            .add_scope_override("include/*.h = source.cpp")
+            .unwrap();
+        loader
+            .add_scope_override("./vendor/** = source.vendored")
+            .unwrap();
+        assert!(loader.add_scope_override("*.h").is_err());
+        assert!(loader.add_scope_override(" = source.c").is_err());
+        assert!(loader.add_scope_override("[ = source.c").is_err());
+
+        // A glob without a `/` matches the file name in any directory.
+        assert_eq!(scope(&loader, "a.h").as_deref(), Some("source.c"));
+        assert_eq!(scope(&loader, "src/a.h").as_deref(), Some("source.c"));
+        // Other globs match the whole path, and the last matching override wins.
+        assert_eq!(scope(&loader, "include/a.h").as_deref(), Some("source.cpp"));
+        assert_eq!(
+            scope(&loader, "./include/a.h").as_deref(),
+            Some("source.cpp")
+        );
+        assert_eq!(
+            scope(&loader, "src/include/a.h").as_deref(),
+            Some("source.c")
+        );
+        assert_eq!(
+            scope(&loader, "vendor/include/a.h").as_deref(),
+            Some("source.vendored")
+        );
+        assert_eq!(scope(&loader, "a.c"), None);
+
+        // Adding an override with a broader glob later takes precedence.
+        loader.add_scope_override("**/*.h = source.objc").unwrap();
+        assert_eq!(
+            scope(&loader, "include/a.h").as_deref(),
+            Some("source.objc")
+        );
+        assert_eq!(scope(&loader, "a.h").as_deref(), Some("source.objc"));
+    }
+
+    #[test]
+    fn test_parse_scope_mappings() {
+        let mappings = parse_scope_mappings(
+            "# Headers\n\n*.h = source.c\n  ./include/*.h=source.cpp  \n/src/**/*.x = source.x\n",
+        )
+        .unwrap();
+        assert_eq!(
+            mappings
+                .iter()
+                .map(|(pattern, scope)| (pattern.as_str(), scope.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("*.h", "source.c"),
+                ("include/*.h", "source.cpp"),
+                ("src/**/*.x", "source.x"),
+            ]
+        );
+
+        for (contents, message) in [
+            ("*.h = source.c\n*.h\n", "Line 2"),
+            ("# comment\n= source.c\n", "Line 2"),
+            ("*.h =\n", "Line 1"),
+            ("*.h = source.c\n\n[ = source.c\n", "Line 3"),
+        ] {
+            let error = parse_scope_mappings(contents).unwrap_err();
+            assert_eq!(error.to_string(), message, "{contents:?}");
+        }
+    }
+
+    #[test]
+    fn test_scope_for_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub/include")).unwrap();
+        fs::write(
+            root.join(SCOPES_FILE_NAME),
+            "*.h = source.c\n/include/*.h = source.cpp\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("sub").join(SCOPES_FILE_NAME),
+            "*.h = source.objc\n",
+        )
+        .unwrap();
+
+        let mut loader = Loader::with_parser_lib_path(PathBuf::new());
+        let mut scope_for_path = |path: &str| {
+            loader
+                .scope_for_path(&root.join(path))
+                .unwrap()
+                .map(|(scopes_path, scope)| {
+                    let scopes_dir = scopes_path.parent().unwrap().strip_prefix(root).unwrap();
+                    (scopes_dir.to_path_buf(), scope)
+                })
+        };
+        let mapped = |dir: &str, scope: &str| Some((PathBuf::from(dir), scope.to_string()));
+
+        assert_eq!(scope_for_path("a.h"), mapped("", "source.c"));
+        // A glob with a `/` is relative to the directory of its file.
+        assert_eq!(scope_for_path("include/a.h"), mapped("", "source.cpp"));
+        assert_eq!(scope_for_path("other/include/a.h"), mapped("", "source.c"));
+        // The nearest file with a matching line takes precedence over its ancestors.
+        assert_eq!(scope_for_path("sub/a.h"), mapped("sub", "source.objc"));
+        assert_eq!(
+            scope_for_path("sub/include/a.h"),
+            mapped("sub", "source.objc")
+        );
+        assert_eq!(scope_for_path("sub/a.c"), None);
+
+        fs::write(root.join("sub").join(SCOPES_FILE_NAME), "*.h\n").unwrap();
+        let error = Loader::with_parser_lib_path(PathBuf::new())
+            .scope_for_path(&root.join("sub/a.h"))
+            .unwrap_err();
+        assert!(
+            error.to_string().starts_with("Invalid scope mapping in "),
+            "{error}"
+        );
+    }
+
+    fn scope(loader: &Loader, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        find_mapped_scope(
+            &loader.scope_overrides,
+            path,
+            path.strip_prefix(".").unwrap_or(path),
+        )
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A file that is produced from the files before it in a grammar's build.
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Other files that it is produced from, besides the artifact before it.
+    pub extra_sources: Vec<PathBuf>,
+    /// The command that brings the artifact up to date.
+    pub fix: &'static str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Freshness {
+    UpToDate,
+    /// The artifact is older than this source.
+    Stale(PathBuf),
+    Missing,
+}
+
+pub struct ArtifactStatus {
+    pub path: PathBuf,
+    pub freshness: Freshness,
+    pub fix: &'static str,
+}
+
+impl fmt::Display for ArtifactStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.freshness {
+            Freshness::UpToDate => write!(f, "{}: up to date", self.path.display()),
+            Freshness::Stale(source) => write!(
+                f,
+                "{}: stale, it is older than {}, {}",
+                self.path.display(),
+                source.display(),
+                self.fix
+            ),
+            Freshness::Missing => write!(f, "{}: missing, skipped", self.path.display()),
+        }
+    }
+}
+
+/// The files that are produced from a grammar, in the order they are produced:
+/// its `grammar.json`, its `parser.c`, and the compiled library at `library_path`.
+#[must_use]
+pub fn grammar_artifacts(repo_path: &Path, library_path: Option<PathBuf>) -> Vec<Artifact> {
+    let src_path = repo_path.join("src");
+    let mut artifacts = vec![
+        Artifact {
+            path: src_path.join("grammar.json"),
+            extra_sources: Vec::new(),
+            fix: "run `tree-sitter generate`",
+        },
+        Artifact {
+            path: src_path.join("parser.c"),
+            extra_sources: Vec::new(),
+            fix: "run `tree-sitter generate`",
+        },
+    ];
+    if let Some(library_path) = library_path {
+        artifacts.push(Artifact {
+            path: library_path,
+            extra_sources: vec![src_path.join("scanner.c"), src_path.join("scanner.cc")],
+            fix: "it will be recompiled the next time the grammar is loaded, or run `tree-sitter build`",
+        });
+    }
+    artifacts
+}
+
+/// Check that each artifact is newer than everything it is produced from, starting
+/// with `source`. Missing artifacts are skipped, so the ones after them are compared
+/// with the last file that exists. Once an artifact is stale, the ones after it are
+/// stale as well.
+pub fn check_freshness(
+    source: &Path,
+    artifacts: Vec<Artifact>,
+    mtime: impl Fn(&Path) -> Option<SystemTime>,
+) -> Vec<ArtifactStatus> {
+    let mut newest = mtime(source).map(|time| (source.to_owned(), time));
+    let mut result = Vec::new();
+    for artifact in artifacts {
+        for source in &artifact.extra_sources {
+            if let Some(time) = mtime(source) {
+                if newest.as_ref().map_or(true, |(_, newest)| time > *newest) {
+                    newest = Some((source.clone(), time));
+                }
+            }
+        }
+
+        let freshness = match (mtime(&artifact.path), &newest) {
+            (None, _) => Freshness::Missing,
+            (Some(time), Some((source, newest))) if time < *newest => {
+                Freshness::Stale(source.clone())
+            }
+            (Some(time), _) => {
+                newest = Some((artifact.path.clone(), time));
+                Freshness::UpToDate
+            }
+        };
+        result.push(ArtifactStatus {
+            path: artifact.path,
+            freshness,
+            fix: artifact.fix,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_check_freshness() {
+        let check = |mtimes: &[(&str, u64)]| {
+            let mtimes = mtimes
+                .iter()
+                .map(|(path, secs)| {
+                    (
+                        PathBuf::from(path),
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(*secs),
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            let artifacts = grammar_artifacts(Path::new("g"), Some(PathBuf::from("lib/g.so")));
+            check_freshness(Path::new("g/grammar.js"), artifacts, |path| {
+                mtimes.get(path).copied()
+            })
+            .into_iter()
+            .map(|status| status.freshness)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            check(&[
+                ("g/grammar.js", 1),
+                ("g/src/grammar.json", 2),
+                ("g/src/parser.c", 3),
+                ("lib/g.so", 4),
+            ]),
+            [
+                Freshness::UpToDate,
+                Freshness::UpToDate,
+                Freshness::UpToDate
+            ]
+        );
+
+        // The grammar was edited after generating, so everything after it is stale.
+        assert_eq!(
+            check(&[
+                ("g/grammar.js", 5),
+                ("g/src/grammar.json", 2),
+                ("g/src/parser.c", 3),
+                ("lib/g.so", 4),
+            ]),
+            [
+                Freshness::Stale(PathBuf::from("g/grammar.js")),
+                Freshness::Stale(PathBuf::from("g/grammar.js")),
+                Freshness::Stale(PathBuf::from("g/grammar.js")),
+            ]
+        );
+
+        // A missing grammar.json is skipped, and the scanner is a source of the library.
+        assert_eq!(
+            check(&[
+                ("g/grammar.js", 1),
+                ("g/src/parser.c", 3),
+                ("g/src/scanner.c", 5),
+                ("lib/g.so", 4),
+            ]),
+            [
+                Freshness::Missing,
+                Freshness::UpToDate,
+                Freshness::Stale(PathBuf::from("g/src/scanner.c")),
+            ]
+        );
+    }
+}
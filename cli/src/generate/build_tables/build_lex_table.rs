@@ -5,6 +5,7 @@ use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar};
 use crate::generate::nfa::NfaCursor;
 use crate::generate::rules::{Symbol, TokenSet};
 use crate::generate::tables::{AdvanceAction, LexState, LexTable, ParseStateId, ParseTable};
+use anyhow::Result;
 use log::info;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, VecDeque};
@@ -17,10 +18,10 @@ pub fn build_lex_table(
     keywords: &TokenSet,
     coincident_token_index: &CoincidentTokenIndex,
     token_conflict_map: &TokenConflictMap,
-) -> (LexTable, LexTable) {
+) -> Result<(LexTable, LexTable)> {
     let keyword_lex_table = if syntax_grammar.word_token.is_some() {
         let mut builder = LexTableBuilder::new(lexical_grammar);
-        builder.add_state_for_tokens(keywords);
+        builder.add_state_for_tokens(keywords)?;
         builder.table
     } else {
         LexTable::default()
@@ -68,7 +69,7 @@ pub fn build_lex_table(
 
     let mut builder = LexTableBuilder::new(lexical_grammar);
     for (tokens, parse_state_ids) in parse_state_ids_by_token_set {
-        let lex_state_id = builder.add_state_for_tokens(&tokens);
+        let lex_state_id = builder.add_state_for_tokens(&tokens)?;
         for id in parse_state_ids {
             parse_table.states[id].lex_state_id = lex_state_id;
         }
@@ -77,7 +78,7 @@ pub fn build_lex_table(
     let mut table = builder.table;
     minimize_lex_table(&mut table, parse_table);
     sort_states(&mut table, parse_table);
-    (table, keyword_lex_table)
+    Ok((table, keyword_lex_table))
 }
 
 struct QueueEntry {
@@ -105,7 +106,7 @@ impl<'a> LexTableBuilder<'a> {
         }
     }
 
-    fn add_state_for_tokens(&mut self, tokens: &TokenSet) -> usize {
+    fn add_state_for_tokens(&mut self, tokens: &TokenSet) -> Result<usize> {
         let mut eof_valid = false;
         let nfa_states = tokens
             .iter()
@@ -137,9 +138,9 @@ impl<'a> LexTableBuilder<'a> {
             eof_valid,
         }) = self.state_queue.pop_front()
         {
-            self.populate_state(state_id, nfa_states, eof_valid);
+            self.populate_state(state_id, nfa_states, eof_valid)?;
         }
-        state_id
+        Ok(state_id)
     }
 
     fn add_state(&mut self, nfa_states: Vec<u32>, eof_valid: bool) -> (usize, bool) {
@@ -163,7 +164,12 @@ impl<'a> LexTableBuilder<'a> {
         }
     }
 
-    fn populate_state(&mut self, state_id: usize, nfa_states: Vec<u32>, eof_valid: bool) {
+    fn populate_state(
+        &mut self,
+        state_id: usize,
+        nfa_states: Vec<u32>,
+        eof_valid: bool,
+    ) -> Result<()> {
         self.cursor.force_reset(nfa_states);
 
         // The EOF state is represented as an empty list of NFA states.
@@ -202,7 +208,7 @@ impl<'a> LexTableBuilder<'a> {
                     completed_id,
                     completed_precedence,
                     has_sep,
-                ) {
+                )? {
                     continue;
                 }
             }
@@ -223,6 +229,7 @@ impl<'a> LexTableBuilder<'a> {
         } else if self.cursor.state_ids.is_empty() {
             self.table.states[state_id].accept_action = Some(Symbol::end());
         }
+        Ok(())
     }
 }
 
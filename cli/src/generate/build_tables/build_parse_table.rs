@@ -17,7 +17,7 @@ use std::fmt::Write;
 use std::hash::BuildHasherDefault;
 use std::u32;
 
-use indexmap::{map::Entry, IndexMap};
+use indexmap::{map::Entry, IndexMap, IndexSet};
 use rustc_hash::FxHasher;
 
 // For conflict reporting, each parse state is associated with an example
@@ -27,6 +27,15 @@ type SymbolSequence = Vec<Symbol>;
 type AuxiliarySymbolSequence = Vec<AuxiliarySymbolInfo>;
 pub type ParseStateInfo<'a> = (SymbolSequence, ParseItemSet<'a>);
 
+// The parse table, the tokens that can follow each token, the info for each parse
+// state, and the conflicts that were resolved by precedence, if they were reported.
+type BuildParseTableResult<'a> = (
+    ParseTable,
+    Vec<TokenSet>,
+    Vec<ParseStateInfo<'a>>,
+    Vec<String>,
+);
+
 #[derive(Clone)]
 struct AuxiliarySymbolInfo {
     auxiliary_symbol: Symbol,
@@ -58,11 +67,12 @@ struct ParseTableBuilder<'a> {
     parse_state_queue: VecDeque<ParseStateQueueEntry>,
     non_terminal_extra_states: Vec<(Symbol, usize)>,
     actual_conflicts: HashSet<Vec<Symbol>>,
+    precedence_report: Option<IndexSet<String>>,
     parse_table: ParseTable,
 }
 
 impl<'a> ParseTableBuilder<'a> {
-    fn build(mut self) -> Result<(ParseTable, Vec<ParseStateInfo<'a>>)> {
+    fn build(mut self) -> Result<(ParseTable, Vec<ParseStateInfo<'a>>, Vec<String>)> {
         // Ensure that the empty alias sequence has index 0.
         self.parse_table
             .production_infos
@@ -142,7 +152,15 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
-        Ok((self.parse_table, self.parse_state_info_by_id))
+        let precedence_report = self
+            .precedence_report
+            .map(|report| report.into_iter().collect())
+            .unwrap_or_default();
+        Ok((
+            self.parse_table,
+            self.parse_state_info_by_id,
+            precedence_report,
+        ))
     }
 
     fn add_parse_state(
@@ -196,6 +214,7 @@ impl<'a> ParseTableBuilder<'a> {
         let mut non_terminal_successors = BTreeMap::new();
         let mut lookaheads_with_conflicts = TokenSet::new();
         let mut reduction_infos = HashMap::<Symbol, ReductionInfo>::new();
+        let mut reduce_reduce_resolutions = Vec::new();
 
         // Each item in the item set contributes to either or a Shift action or a Reduce
         // action in this state.
@@ -272,13 +291,25 @@ impl<'a> ParseTableBuilder<'a> {
                     if table_entry.actions.is_empty() {
                         table_entry.actions.push(action);
                     } else {
-                        match Self::compare_precedence(
+                        let ordering = Self::compare_precedence(
                             self.syntax_grammar,
                             precedence,
                             &[symbol],
                             &reduction_info.precedence,
                             &reduction_info.symbols,
-                        ) {
+                        );
+                        if self.precedence_report.is_some() && ordering != Ordering::Equal {
+                            reduce_reduce_resolutions.push((
+                                lookahead,
+                                ordering,
+                                (symbol, precedence.clone()),
+                                (
+                                    reduction_info.symbols.clone(),
+                                    reduction_info.precedence.clone(),
+                                ),
+                            ));
+                        }
+                        match ordering {
                             Ordering::Greater => {
                                 table_entry.actions.clear();
                                 table_entry.actions.push(action);
@@ -306,6 +337,34 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
+        for (lookahead, ordering, (symbol, precedence), (other_symbols, other_precedence)) in
+            reduce_reduce_resolutions
+        {
+            let other_names = other_symbols
+                .iter()
+                .map(|symbol| format!("`{}`", self.symbol_name(symbol)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let (winner, winner_precedence, loser, loser_precedence) = match ordering {
+                Ordering::Greater => (
+                    format!("`{}`", self.symbol_name(&symbol)),
+                    precedence,
+                    other_names,
+                    other_precedence,
+                ),
+                _ => (
+                    other_names,
+                    other_precedence,
+                    format!("`{}`", self.symbol_name(&symbol)),
+                    precedence,
+                ),
+            };
+            self.record_precedence_resolution(format!(
+                "Conflict on {} resolved by precedence, in favor of reducing to {winner} (precedence: {winner_precedence}) over {loser} (precedence: {loser_precedence})\n",
+                self.symbol_name(&lookahead),
+            ));
+        }
+
         // Having computed the successor item sets for each symbol, add a new
         // parse state for each of these item sets, and add a corresponding Shift
         // action to this state.
@@ -443,11 +502,6 @@ impl<'a> ParseTableBuilder<'a> {
         conflicting_lookahead: Symbol,
         reduction_info: &ReductionInfo,
     ) -> Result<()> {
-        let entry = self.parse_table.states[state_id]
-            .terminal_entries
-            .get_mut(&conflicting_lookahead)
-            .unwrap();
-
         // Determine which items in the set conflict with each other, and the
         // precedences associated with SHIFT vs REDUCE actions. There won't
         // be multiple REDUCE actions with different precedences; that is
@@ -483,6 +537,16 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
+        let conflict_description = self
+            .precedence_report
+            .is_some()
+            .then(|| self.describe_conflict(&conflicting_items, conflicting_lookahead));
+        let mut resolution = None;
+
+        let entry = self.parse_table.states[state_id]
+            .terminal_entries
+            .get_mut(&conflicting_lookahead)
+            .unwrap();
         if let ParseAction::Shift { is_repetition, .. } = entry.actions.last_mut().unwrap() {
             // If all of the items in the conflict have the same parent symbol,
             // and that parent symbols is auxiliary, then this is just the intentional
@@ -519,11 +583,13 @@ impl<'a> ParseTableBuilder<'a> {
 
             if shift_is_more && !shift_is_less {
                 entry.actions.drain(0..entry.actions.len() - 1);
+                resolution = Some("by precedence, in favor of shifting");
             }
             // If the REDUCE actions have higher precedence, remove the SHIFT action.
             else if shift_is_less && !shift_is_more {
                 entry.actions.pop();
                 conflicting_items.retain(|item| item.is_done());
+                resolution = Some("by precedence, in favor of reducing");
             }
             // If the SHIFT and REDUCE actions have the same predence, consider
             // the REDUCE actions' associativity.
@@ -540,15 +606,24 @@ impl<'a> ParseTableBuilder<'a> {
                     (true, false, false) => {
                         entry.actions.pop();
                         conflicting_items.retain(|item| item.is_done());
+                        resolution = Some("by left associativity, in favor of reducing");
                     }
                     (false, false, true) => {
                         entry.actions.drain(0..entry.actions.len() - 1);
+                        resolution = Some("by right associativity, in favor of shifting");
                     }
                     _ => {}
                 }
             }
         }
 
+        if let (Some(description), Some(resolution)) = (conflict_description, resolution) {
+            self.record_precedence_resolution(format!(
+                "Conflict on {} resolved {resolution}:\n{description}",
+                self.symbol_name(&conflicting_lookahead)
+            ));
+        }
+
         // If all of the actions but one have been eliminated, then there's no problem.
         let entry = self.parse_table.states[state_id]
             .terminal_entries
@@ -617,45 +692,8 @@ impl<'a> ParseTableBuilder<'a> {
                 {
                     write!(&mut line, "  {}", self.symbol_name(preceding_symbol)).unwrap();
                 }
-
-                write!(
-                    &mut line,
-                    "  ({}",
-                    &self.syntax_grammar.variables[item.variable_index as usize].name
-                )
-                .unwrap();
-
-                for (j, step) in item.production.steps.iter().enumerate() {
-                    if j as u32 == item.step_index {
-                        write!(&mut line, "  •").unwrap();
-                    }
-                    write!(&mut line, "  {}", self.symbol_name(&step.symbol)).unwrap();
-                }
-
-                write!(&mut line, ")").unwrap();
-
-                if item.is_done() {
-                    write!(
-                        &mut line,
-                        "  •  {}  …",
-                        self.symbol_name(&conflicting_lookahead)
-                    )
-                    .unwrap();
-                }
-
-                let precedence = item.precedence();
-                let associativity = item.associativity();
-
-                let prec_line = if let Some(associativity) = associativity {
-                    Some(format!(
-                        "(precedence: {precedence}, associativity: {associativity:?})",
-                    ))
-                } else if !precedence.is_none() {
-                    Some(format!("(precedence: {precedence})"))
-                } else {
-                    None
-                };
-
+                let (description, prec_line) = self.describe_item(item, conflicting_lookahead);
+                write!(&mut line, "  {description}").unwrap();
                 (line, prec_line)
             })
             .collect::<Vec<_>>();
@@ -765,6 +803,78 @@ impl<'a> ParseTableBuilder<'a> {
         Err(anyhow!(msg))
     }
 
+    /// Describe an item as its rule with a dot at the item's position, along with
+    /// the precedence and associativity of the item, if it has any.
+    fn describe_item(&self, item: &ParseItem, lookahead: Symbol) -> (String, Option<String>) {
+        let mut line = format!(
+            "({}",
+            &self.syntax_grammar.variables[item.variable_index as usize].name
+        );
+
+        for (j, step) in item.production.steps.iter().enumerate() {
+            if j as u32 == item.step_index {
+                write!(&mut line, "  •").unwrap();
+            }
+            write!(&mut line, "  {}", self.symbol_name(&step.symbol)).unwrap();
+        }
+
+        write!(&mut line, ")").unwrap();
+
+        if item.is_done() {
+            write!(&mut line, "  •  {}  …", self.symbol_name(&lookahead)).unwrap();
+        }
+
+        let precedence = item.precedence();
+        let associativity = item.associativity();
+
+        let prec_line = if let Some(associativity) = associativity {
+            Some(format!(
+                "(precedence: {precedence}, associativity: {associativity:?})",
+            ))
+        } else if !precedence.is_none() {
+            Some(format!("(precedence: {precedence})"))
+        } else {
+            None
+        };
+
+        (line, prec_line)
+    }
+
+    /// Describe the items of a SHIFT/REDUCE conflict for the precedence report, one
+    /// per line.
+    fn describe_conflict(&self, items: &HashSet<&ParseItem>, lookahead: Symbol) -> String {
+        let mut lines = items
+            .iter()
+            .map(|item| {
+                let action = if item.is_done() { "reduce:" } else { "shift:" };
+                let (description, prec_line) = self.describe_item(item, lookahead);
+                (format!("  {action:<9}{description}"), prec_line)
+            })
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+
+        let max_line_length = lines.iter().map(|l| l.0.chars().count()).max();
+        let mut result = String::new();
+        for (line, prec_line) in lines {
+            result += &line;
+            if let Some(prec_line) = prec_line {
+                for _ in line.chars().count()..max_line_length.unwrap_or(0) {
+                    result.push(' ');
+                }
+                result += "  ";
+                result += &prec_line;
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    fn record_precedence_resolution(&mut self, resolution: String) {
+        if let Some(report) = &mut self.precedence_report {
+            report.insert(resolution);
+        }
+    }
+
     fn compare_precedence(
         grammar: &SyntaxGrammar,
         left: &Precedence,
@@ -966,7 +1076,8 @@ pub fn build_parse_table<'a>(
     lexical_grammar: &'a LexicalGrammar,
     inlines: &'a InlinedProductionMap,
     variable_info: &'a [VariableInfo],
-) -> Result<(ParseTable, Vec<TokenSet>, Vec<ParseStateInfo<'a>>)> {
+    report_precedence: bool,
+) -> Result<BuildParseTableResult<'a>> {
     let actual_conflicts = syntax_grammar.expected_conflicts.iter().cloned().collect();
     let item_set_builder = ParseItemSetBuilder::new(syntax_grammar, lexical_grammar, inlines);
     let mut following_tokens = vec![TokenSet::new(); lexical_grammar.variables.len()];
@@ -977,13 +1088,14 @@ pub fn build_parse_table<'a>(
         &item_set_builder,
     );
 
-    let (table, item_sets) = ParseTableBuilder {
+    let (table, item_sets, precedence_report) = ParseTableBuilder {
         syntax_grammar,
         lexical_grammar,
         item_set_builder,
         variable_info,
         non_terminal_extra_states: Vec::new(),
         actual_conflicts,
+        precedence_report: report_precedence.then(IndexSet::new),
         state_ids_by_item_set: IndexMap::default(),
         core_ids_by_core: HashMap::new(),
         parse_state_info_by_id: Vec::new(),
@@ -998,5 +1110,103 @@ pub fn build_parse_table<'a>(
     }
     .build()?;
 
-    Ok((table, following_tokens, item_sets))
+    Ok((table, following_tokens, item_sets, precedence_report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::parse_grammar::parse_grammar;
+    use crate::generate::prepare_grammar::prepare_grammar;
+
+    #[test]
+    fn test_precedence_report() {
+        let input_grammar = parse_grammar(
+            r#"
+            {
+                "name": "test_grammar_for_precedence_report",
+                "rules": {
+                    "expression": {
+                        "type": "CHOICE",
+                        "members": [
+                            {
+                                "type": "PREC_LEFT",
+                                "value": 1,
+                                "content": {
+                                    "type": "SEQ",
+                                    "members": [
+                                        {"type": "SYMBOL", "name": "expression"},
+                                        {"type": "STRING", "value": "+"},
+                                        {"type": "SYMBOL", "name": "expression"}
+                                    ]
+                                }
+                            },
+                            {
+                                "type": "PREC_LEFT",
+                                "value": 2,
+                                "content": {
+                                    "type": "SEQ",
+                                    "members": [
+                                        {"type": "SYMBOL", "name": "expression"},
+                                        {"type": "STRING", "value": "*"},
+                                        {"type": "SYMBOL", "name": "expression"}
+                                    ]
+                                }
+                            },
+                            {"type": "PATTERN", "value": "\\d+"}
+                        ]
+                    }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
+            prepare_grammar(&input_grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases).unwrap();
+
+        let build = |report_precedence| {
+            build_parse_table(
+                &syntax_grammar,
+                &lexical_grammar,
+                &inlines,
+                &variable_info,
+                report_precedence,
+            )
+            .unwrap()
+            .3
+        };
+        assert!(build(false).is_empty());
+
+        // Each conflict is reported once, with the rules on both sides of it.
+        let report = build(true);
+        assert_eq!(report.len(), 4);
+        assert!(report.contains(
+            &[
+                "Conflict on '*' resolved by precedence, in favor of shifting:",
+                "  reduce:  (expression  expression  '+'  expression)  •  '*'  …  (precedence: 1, associativity: Left)",
+                "  shift:   (expression  expression  •  '*'  expression)          (precedence: 2, associativity: Left)",
+                "",
+            ]
+            .join("\n")
+        ));
+        assert!(report.contains(
+            &[
+                "Conflict on '+' resolved by precedence, in favor of reducing:",
+                "  reduce:  (expression  expression  '*'  expression)  •  '+'  …  (precedence: 2, associativity: Left)",
+                "  shift:   (expression  expression  •  '+'  expression)          (precedence: 1, associativity: Left)",
+                "",
+            ]
+            .join("\n")
+        ));
+        for operator in ["'+'", "'*'"] {
+            assert!(report
+                .iter()
+                .any(|resolution| resolution.starts_with(&format!(
+                    "Conflict on {operator} resolved by left associativity, in favor of reducing:"
+                ))));
+        }
+    }
 }
@@ -11,14 +11,17 @@ use self::build_parse_table::{build_parse_table, ParseStateInfo};
 use self::coincident_tokens::CoincidentTokenIndex;
 use self::minimize_parse_table::minimize_parse_table;
 use self::token_conflicts::TokenConflictMap;
-use crate::generate::grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
+use crate::generate::grammars::{
+    InlinedProductionMap, LexicalGrammar, SyntaxGrammar, VariableType,
+};
 use crate::generate::nfa::NfaCursor;
 use crate::generate::node_types::VariableInfo;
 use crate::generate::rules::{AliasMap, Symbol, SymbolType, TokenSet};
-use crate::generate::tables::{LexTable, ParseAction, ParseTable, ParseTableEntry};
-use anyhow::Result;
+use crate::generate::tables::{GotoAction, LexTable, ParseAction, ParseTable, ParseTableEntry};
+use anyhow::{anyhow, Result};
 use log::info;
 use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
 
 pub fn build_tables(
     syntax_grammar: &SyntaxGrammar,
@@ -27,10 +30,20 @@ pub fn build_tables(
     variable_info: &[VariableInfo],
     inlines: &InlinedProductionMap,
     report_symbol_name: Option<&str>,
+    report_precedence: bool,
 ) -> Result<(ParseTable, LexTable, LexTable, Option<Symbol>)> {
-    let (mut parse_table, following_tokens, parse_state_info) =
-        build_parse_table(syntax_grammar, lexical_grammar, inlines, variable_info)?;
-    let token_conflict_map = TokenConflictMap::new(lexical_grammar, following_tokens);
+    let (mut parse_table, following_tokens, parse_state_info, precedence_report) =
+        build_parse_table(
+            syntax_grammar,
+            lexical_grammar,
+            inlines,
+            variable_info,
+            report_precedence,
+        )?;
+    for resolution in precedence_report {
+        eprintln!("{resolution}");
+    }
+    let token_conflict_map = TokenConflictMap::new(lexical_grammar, following_tokens)?;
     let coincident_token_index = CoincidentTokenIndex::new(&parse_table, lexical_grammar);
     let keywords = identify_keywords(
         lexical_grammar,
@@ -63,7 +76,7 @@ pub fn build_tables(
         &keywords,
         &coincident_token_index,
         &token_conflict_map,
-    );
+    )?;
     populate_external_lex_states(&mut parse_table, syntax_grammar);
     mark_fragile_tokens(&mut parse_table, lexical_grammar, &token_conflict_map);
 
@@ -466,6 +479,85 @@ fn report_state_info<'a>(
     }
 }
 
+/// Render the actions of every parse state, or of just one state, keyed by the
+/// names of the symbols that trigger them.
+pub fn render_parse_table(
+    parse_table: &ParseTable,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    state_id: Option<usize>,
+) -> Result<String> {
+    let state_ids = match state_id {
+        Some(id) if id >= parse_table.states.len() => {
+            return Err(anyhow!(
+                "There is no parse state {id}, the table has {} states",
+                parse_table.states.len()
+            ));
+        }
+        Some(id) => id..id + 1,
+        None => 0..parse_table.states.len(),
+    };
+
+    let symbol_name = |symbol: &Symbol| match symbol.kind {
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => "end".to_string(),
+        SymbolType::External => syntax_grammar.external_tokens[symbol.index].name.clone(),
+        SymbolType::NonTerminal => syntax_grammar.variables[symbol.index].name.clone(),
+        SymbolType::Terminal => {
+            let variable = &lexical_grammar.variables[symbol.index];
+            if variable.kind == VariableType::Named {
+                variable.name.clone()
+            } else {
+                format!("'{}'", variable.name)
+            }
+        }
+    };
+
+    let mut result = String::new();
+    for id in state_ids {
+        let state = &parse_table.states[id];
+        writeln!(&mut result, "state {id}:").unwrap();
+        for (symbol, entry) in &state.terminal_entries {
+            let actions = entry
+                .actions
+                .iter()
+                .map(|action| match action {
+                    ParseAction::Accept => "accept".to_string(),
+                    ParseAction::Shift {
+                        state,
+                        is_repetition: false,
+                    } => format!("shift {state}"),
+                    ParseAction::Shift {
+                        state,
+                        is_repetition: true,
+                    } => format!("shift {state} (repetition)"),
+                    ParseAction::ShiftExtra => "shift extra".to_string(),
+                    ParseAction::Recover => "recover".to_string(),
+                    ParseAction::Reduce {
+                        symbol,
+                        child_count,
+                        ..
+                    } => format!("reduce {} ({child_count})", symbol_name(symbol)),
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                &mut result,
+                "  {} -> {}",
+                symbol_name(symbol),
+                actions.join(", ")
+            )
+            .unwrap();
+        }
+        for (symbol, action) in &state.nonterminal_entries {
+            let action = match action {
+                GotoAction::Goto(state) => format!("goto {state}"),
+                GotoAction::ShiftExtra => "shift extra".to_string(),
+            };
+            writeln!(&mut result, "  {} -> {action}", symbol_name(symbol)).unwrap();
+        }
+    }
+    Ok(result)
+}
+
 fn all_chars_are_alphabetical(cursor: &NfaCursor) -> bool {
     cursor.transition_chars().all(|(chars, is_sep)| {
         if is_sep {
@@ -475,3 +567,82 @@ fn all_chars_are_alphabetical(cursor: &NfaCursor) -> bool {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::parse_grammar::parse_grammar;
+    use crate::generate::prepare_grammar::prepare_grammar;
+
+    #[test]
+    fn test_render_parse_table() {
+        let input_grammar = parse_grammar(
+            r#"
+            {
+                "name": "test_grammar_for_render_parse_table",
+                "rules": {
+                    "program": {
+                        "type": "REPEAT",
+                        "content": {"type": "SYMBOL", "name": "pair"}
+                    },
+                    "pair": {
+                        "type": "SEQ",
+                        "members": [
+                            {"type": "SYMBOL", "name": "identifier"},
+                            {"type": "STRING", "value": ";"}
+                        ]
+                    },
+                    "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
+            prepare_grammar(&input_grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases).unwrap();
+        let (parse_table, ..) = build_tables(
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            &variable_info,
+            &inlines,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let render = |state_id| {
+            render_parse_table(&parse_table, &syntax_grammar, &lexical_grammar, state_id)
+        };
+        assert_eq!(render(Some(5)).unwrap(), "state 5:\n  ';' -> shift 4\n");
+        assert_eq!(
+            render(Some(3)).unwrap(),
+            concat!(
+                "state 3:\n",
+                "  identifier -> reduce program_repeat1 (2), shift 5 (repetition)\n",
+                "  end -> reduce program_repeat1 (2)\n",
+                "  pair -> goto 3\n",
+                "  program_repeat1 -> goto 3\n",
+            )
+        );
+
+        let table = render(None).unwrap();
+        assert!(table.starts_with("state 0:\n"));
+        assert!(table.ends_with("state 6:\n  end -> accept\n"));
+        assert_eq!(
+            table
+                .lines()
+                .filter(|line| line.starts_with("state "))
+                .count(),
+            parse_table.states.len()
+        );
+
+        assert_eq!(
+            render(Some(7)).unwrap_err().to_string(),
+            "There is no parse state 7, the table has 7 states"
+        );
+    }
+}
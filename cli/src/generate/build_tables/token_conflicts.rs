@@ -2,6 +2,7 @@ use crate::generate::build_tables::item::TokenSetDisplay;
 use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar};
 use crate::generate::nfa::{CharacterSet, NfaCursor, NfaTransition};
 use crate::generate::rules::TokenSet;
+use anyhow::Result;
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt;
@@ -32,7 +33,7 @@ impl<'a> TokenConflictMap<'a> {
     ///
     /// This analyzes the possible kinds of overlap between each pair of tokens and stores
     /// them in a matrix.
-    pub fn new(grammar: &'a LexicalGrammar, following_tokens: Vec<TokenSet>) -> Self {
+    pub fn new(grammar: &'a LexicalGrammar, following_tokens: Vec<TokenSet>) -> Result<Self> {
         let mut cursor = NfaCursor::new(&grammar.nfa, Vec::new());
         let starting_chars = get_starting_chars(&mut cursor, grammar);
         let following_chars = get_following_chars(&starting_chars, &following_tokens);
@@ -41,20 +42,20 @@ impl<'a> TokenConflictMap<'a> {
         let mut status_matrix = vec![TokenConflictStatus::default(); n * n];
         for i in 0..grammar.variables.len() {
             for j in 0..i {
-                let status = compute_conflict_status(&mut cursor, grammar, &following_chars, i, j);
+                let status = compute_conflict_status(&mut cursor, grammar, &following_chars, i, j)?;
                 status_matrix[matrix_index(n, i, j)] = status.0;
                 status_matrix[matrix_index(n, j, i)] = status.1;
             }
         }
 
-        TokenConflictMap {
+        Ok(TokenConflictMap {
             n,
             status_matrix,
             following_tokens,
             starting_chars_by_index: starting_chars,
             following_chars_by_index: following_chars,
             grammar,
-        }
+        })
     }
 
     /// Does token `i` match any strings that token `j` also matches, such that token `i`
@@ -124,23 +125,23 @@ impl<'a> TokenConflictMap<'a> {
         completed_id: usize,
         completed_precedence: i32,
         has_separator_transitions: bool,
-    ) -> bool {
+    ) -> Result<bool> {
         if t.precedence < completed_precedence {
-            return false;
+            return Ok(false);
         }
         if t.precedence == completed_precedence {
             if t.is_separator {
-                return false;
+                return Ok(false);
             }
             if has_separator_transitions
                 && !grammar
-                    .variable_indices_for_nfa_states(&t.states)
-                    .any(|i| i == completed_id)
+                    .variable_indices_for_nfa_states(&t.states)?
+                    .contains(&completed_id)
             {
-                return false;
+                return Ok(false);
             }
         }
-        true
+        Ok(true)
     }
 }
 
@@ -241,7 +242,7 @@ fn compute_conflict_status(
     following_chars: &[CharacterSet],
     i: usize,
     j: usize,
-) -> (TokenConflictStatus, TokenConflictStatus) {
+) -> Result<(TokenConflictStatus, TokenConflictStatus)> {
     let mut visited_state_sets = HashSet::new();
     let mut state_set_queue = vec![vec![
         grammar.variables[i].start_state,
@@ -253,13 +254,13 @@ fn compute_conflict_status(
     );
 
     while let Some(state_set) = state_set_queue.pop() {
-        let mut live_variable_indices = grammar.variable_indices_for_nfa_states(&state_set);
+        let live_variable_indices = grammar.variable_indices_for_nfa_states(&state_set)?;
 
         // If only one of the two tokens could possibly match from this state, then
         // there is no reason to analyze any of its successors. Just record the fact
         // that the token matches a string that the other token does not match.
-        let first_live_variable_index = live_variable_indices.next().unwrap();
-        if live_variable_indices.count() == 0 {
+        let first_live_variable_index = live_variable_indices[0];
+        if live_variable_indices.len() == 1 {
             if first_live_variable_index == i {
                 result.0.matches_different_string = true;
             } else {
@@ -323,7 +324,7 @@ fn compute_conflict_status(
             if let Some((completed_id, completed_precedence)) = completion {
                 let mut advanced_id = None;
                 let mut successor_contains_completed_id = false;
-                for variable_id in grammar.variable_indices_for_nfa_states(&transition.states) {
+                for variable_id in grammar.variable_indices_for_nfa_states(&transition.states)? {
                     if variable_id == completed_id {
                         successor_contains_completed_id = true;
                         break;
@@ -340,7 +341,7 @@ fn compute_conflict_status(
                         completed_id,
                         completed_precedence,
                         within_separator,
-                    ) {
+                    )? {
                         can_advance = true;
                         if advanced_id == i {
                             result.0.does_match_continuation = true;
@@ -366,7 +367,7 @@ fn compute_conflict_status(
             }
         }
     }
-    result
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -395,7 +396,7 @@ mod tests {
         })
         .unwrap();
 
-        let token_map = TokenConflictMap::new(&grammar, Vec::new());
+        let token_map = TokenConflictMap::new(&grammar, Vec::new()).unwrap();
 
         assert_eq!(
             token_map.starting_chars_by_index[0],
@@ -446,7 +447,8 @@ mod tests {
                     .copied()
                     .collect(),
             ],
-        );
+        )
+        .unwrap();
 
         // Given the string "in", the `in` token is preferred over the `identifier` token
         assert!(token_map.does_match_same_string(var("in"), var("identifier")));
@@ -483,7 +485,7 @@ mod tests {
 
         let var = |name| index_of_var(&grammar, name);
 
-        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]).unwrap();
 
         assert!(token_map.does_conflict(var("newline"), var("x")));
         assert!(!token_map.does_conflict(var("x"), var("newline")));
@@ -510,7 +512,7 @@ mod tests {
 
         let var = |name| index_of_var(&grammar, name);
 
-        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]).unwrap();
 
         assert!(token_map.does_match_shorter_or_longer(var("anything"), var("x")));
         assert!(!token_map.does_match_shorter_or_longer(var("x"), var("anything")));
@@ -446,4 +446,9 @@ global.grammar = grammar;
 global.field = field;
 
 const result = require(process.env.TREE_SITTER_GRAMMAR_PATH);
-process.stdout.write(JSON.stringify(result.grammar, null, null));
+if (process.env.TREE_SITTER_LIST_MODULES) {
+  // For `tree-sitter deps`, print the files that were required while loading the grammar.
+  process.stdout.write(JSON.stringify(Object.keys(require.cache), null, null));
+} else {
+  process.stdout.write(JSON.stringify(result.grammar, null, null));
+}
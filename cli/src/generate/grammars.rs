@@ -1,5 +1,6 @@
 use super::nfa::Nfa;
 use super::rules::{Alias, Associativity, Precedence, Rule, Symbol};
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::fmt;
 
@@ -197,27 +198,24 @@ impl VariableType {
 }
 
 impl LexicalGrammar {
-    pub fn variable_indices_for_nfa_states<'a>(
-        &'a self,
-        state_ids: &'a [u32],
-    ) -> impl Iterator<Item = usize> + 'a {
-        let mut prev = None;
-        state_ids.iter().filter_map(move |state_id| {
-            let variable_id = self.variable_index_for_nfa_state(*state_id);
-            if prev == Some(variable_id) {
-                None
-            } else {
-                prev = Some(variable_id);
-                prev
+    /// The indices of the variables that own the given NFA states, with consecutive
+    /// duplicates removed.
+    pub fn variable_indices_for_nfa_states(&self, state_ids: &[u32]) -> Result<Vec<usize>> {
+        let mut result = Vec::new();
+        for state_id in state_ids {
+            let variable_id = self.variable_index_for_nfa_state(*state_id)?;
+            if result.last() != Some(&variable_id) {
+                result.push(variable_id);
             }
-        })
+        }
+        Ok(result)
     }
 
-    pub fn variable_index_for_nfa_state(&self, state_id: u32) -> usize {
+    pub fn variable_index_for_nfa_state(&self, state_id: u32) -> Result<usize> {
         self.variables
             .iter()
             .position(|v| v.start_state >= state_id)
-            .unwrap()
+            .ok_or_else(|| anyhow!("NFA state {state_id} has no owning lexical variable"))
     }
 }
 
@@ -256,3 +254,40 @@ impl fmt::Display for PrecedenceEntry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variable_index_for_nfa_state() {
+        let variable = |name: &str, start_state| LexicalVariable {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            implicit_precedence: 0,
+            start_state,
+        };
+        let grammar = LexicalGrammar {
+            nfa: Nfa::new(),
+            variables: vec![variable("a", 2), variable("b", 5)],
+        };
+
+        assert_eq!(grammar.variable_index_for_nfa_state(0).unwrap(), 0);
+        assert_eq!(grammar.variable_index_for_nfa_state(2).unwrap(), 0);
+        assert_eq!(grammar.variable_index_for_nfa_state(3).unwrap(), 1);
+        assert_eq!(
+            grammar
+                .variable_indices_for_nfa_states(&[0, 1, 4, 2])
+                .unwrap(),
+            [0, 1, 0]
+        );
+        assert_eq!(
+            grammar
+                .variable_index_for_nfa_state(6)
+                .unwrap_err()
+                .to_string(),
+            "NFA state 6 has no owning lexical variable"
+        );
+        assert!(grammar.variable_indices_for_nfa_states(&[1, 6]).is_err());
+    }
+}
@@ -7,10 +7,11 @@ use anyhow::{anyhow, Context, Result};
 use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
 use semver::Version;
+use serde::{Deserialize, Serialize};
 
-use build_tables::build_tables;
+use build_tables::{build_tables, render_parse_table};
 use grammar_files::path_in_ignore;
-use grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
+use grammars::{InlinedProductionMap, InputGrammar, LexicalGrammar, SyntaxGrammar};
 use parse_grammar::parse_grammar;
 use prepare_grammar::prepare_grammar;
 use render::render_c_code;
@@ -38,24 +39,170 @@ lazy_static! {
 
 struct GeneratedParser {
     c_code: String,
+    symbols_header: Option<String>,
     node_types_json: String,
+    stats: GenerateStats,
+    parse_table_dump: Option<String>,
+}
+
+/// Measurements of the generated tables, for tracking their growth over time.
+#[derive(Debug, Default, Serialize)]
+pub struct GenerateStats {
+    pub symbol_count: usize,
+    pub parse_state_count: usize,
+    pub parse_table_entry_count: usize,
+    pub lex_state_count: usize,
+    pub keyword_lex_state_count: usize,
+    pub parser_c_bytes: usize,
+}
+
+/// The step of generating a parser that failed, so that tools that run `generate`
+/// can tell the kinds of errors apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerateErrorKind {
+    LoadGrammar,
+    ParseGrammar,
+    PrepareGrammar,
+    BuildTables,
+    /// Any other failure, such as an error writing the generated files.
+    Other,
+}
+
+/// An error from `generate_parser_in_directory`, tagged with the step that failed.
+/// It displays just like the underlying error.
+#[derive(Debug)]
+pub struct GenerateError {
+    pub kind: GenerateErrorKind,
+    pub error: anyhow::Error,
+}
+
+impl From<anyhow::Error> for GenerateError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<Self>() {
+            Ok(error) => error,
+            Err(error) => Self {
+                kind: GenerateErrorKind::Other,
+                error,
+            },
+        }
+    }
+}
+
+/// The JSON form of a [`GenerateError`], for `generate --error-json`.
+#[derive(Debug, Serialize)]
+pub struct GenerateErrorJson {
+    pub kind: GenerateErrorKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// What the JavaScript runtime printed to stderr, when it failed to load
+    /// `grammar.js`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl GenerateError {
+    #[must_use]
+    pub fn to_json(&self) -> GenerateErrorJson {
+        // Syntax errors in `grammar.json` know where they are.
+        let json_error = self
+            .error
+            .chain()
+            .find_map(|error| error.downcast_ref::<serde_json::Error>())
+            .filter(|error| error.line() > 0);
+        GenerateErrorJson {
+            kind: self.kind,
+            message: format!("{:#}", self.error),
+            line: json_error.map(serde_json::Error::line),
+            column: json_error.map(serde_json::Error::column),
+            details: self
+                .error
+                .chain()
+                .find_map(|error| error.downcast_ref::<JsRuntimeError>())
+                .map(|error| error.stderr.clone()),
+        }
+    }
+}
+
+/// The failure of the JavaScript runtime that loads a `grammar.js` file, with what
+/// it printed to stderr, which is usually the exception that stopped it.
+#[derive(Debug)]
+struct JsRuntimeError {
+    status: i32,
+    stderr: String,
+}
+
+impl std::fmt::Display for JsRuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Node process exited with status {}", self.status)
+    }
+}
+
+impl std::error::Error for JsRuntimeError {}
+
+impl std::fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for GenerateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+trait ResultExt<T> {
+    fn in_step(self, kind: GenerateErrorKind) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn in_step(self, kind: GenerateErrorKind) -> Result<T> {
+        self.map_err(|error| GenerateError { kind, error }.into())
+    }
 }
 
 pub const ALLOC_HEADER: &str = include_str!("./templates/alloc.h");
 
-pub fn generate_parser_in_directory(
-    repo_path: &Path,
-    grammar_path: Option<&str>,
-    abi_version: usize,
-    generate_bindings: bool,
-    report_symbol_name: Option<&str>,
-    js_runtime: Option<&str>,
-) -> Result<()> {
+// The start of the comment that `generate --stamp` puts at the top of parser.c.
+const STAMP_PREFIX: &str = "/* tree-sitter stamp: grammar.json ";
+
+/// The options for [`generate_parser_in_directory`], which mostly correspond to
+/// the flags of `generate`.
+pub struct GenerateOptions<'a> {
+    pub grammar_path: Option<&'a str>,
+    pub abi_version: usize,
+    pub generate_bindings: bool,
+    pub report_symbol_name: Option<&'a str>,
+    pub js_runtime: Option<&'a str>,
+    pub warn_kind_merges: bool,
+    pub stats_path: Option<&'a Path>,
+    pub inline: &'a [String],
+    pub no_inline: &'a [String],
+    pub report_precedence: bool,
+    pub dump_parse_table: Option<Option<usize>>,
+    pub out_path: Option<&'a Path>,
+    pub header_prefix: Option<&'a str>,
+    pub emit_symbols_header: bool,
+    pub compact_node_types: bool,
+    pub stamp: bool,
+    pub minimal: bool,
+    pub dry_run: bool,
+}
+
+pub fn generate_parser_in_directory(repo_path: &Path, opts: &GenerateOptions) -> Result<()> {
     let mut repo_path = repo_path.to_owned();
-    let mut grammar_path = grammar_path;
+    let mut grammar_path = opts.grammar_path;
+    let mut output = OutputFiles {
+        dry_run: opts.dry_run,
+        files: Vec::new(),
+    };
 
     // Populate a new empty grammar directory.
-    if let Some(path) = grammar_path {
+    if let Some(path) = grammar_path.filter(|_| !opts.dry_run) {
         let path = PathBuf::from(path);
         if !path
             .try_exists()
@@ -67,7 +214,11 @@ pub fn generate_parser_in_directory(
         }
     }
 
-    if repo_path.is_dir() && !repo_path.join("grammar.js").exists() && !path_in_ignore(&repo_path) {
+    if !opts.dry_run
+        && repo_path.is_dir()
+        && !repo_path.join("grammar.js").exists()
+        && !path_in_ignore(&repo_path)
+    {
         if let Some(dir_name) = repo_path
             .file_name()
             .map(|x| x.to_string_lossy().to_ascii_lowercase())
@@ -83,58 +234,243 @@ pub fn generate_parser_in_directory(
 
     // Read the grammar.json.
     let grammar_json = if let Some(path) = grammar_path {
-        load_grammar_file(path.as_ref(), js_runtime)?
+        load_grammar_file(path.as_ref(), opts.js_runtime).in_step(GenerateErrorKind::LoadGrammar)?
     } else {
         let grammar_js_path =
             grammar_path.map_or(repo_path.join("grammar.js"), std::convert::Into::into);
-        load_grammar_file(&grammar_js_path, js_runtime)?
+        load_grammar_file(&grammar_js_path, opts.js_runtime)
+            .in_step(GenerateErrorKind::LoadGrammar)?
     };
 
-    let src_path = repo_path.join("src");
+    let src_path = opts
+        .out_path
+        .map_or_else(|| repo_path.join("src"), Path::to_owned);
     let header_path = src_path.join("tree_sitter");
 
     // Ensure that the output directories exist.
-    fs::create_dir_all(&src_path)?;
-    fs::create_dir_all(&header_path)?;
+    if !opts.dry_run {
+        fs::create_dir_all(&src_path)?;
+        fs::create_dir_all(&header_path)?;
+    }
 
-    if grammar_path.is_none() {
-        fs::write(src_path.join("grammar.json"), &grammar_json)
+    if grammar_path.is_none() && !opts.minimal {
+        output
+            .write(&src_path.join("grammar.json"), &grammar_json)
             .with_context(|| format!("Failed to write grammar.json to {src_path:?}"))?;
     }
 
     // Parse and preprocess the grammar.
-    let input_grammar = parse_grammar(&grammar_json)?;
+    let mut input_grammar =
+        parse_grammar(&grammar_json).in_step(GenerateErrorKind::ParseGrammar)?;
+    override_variables_to_inline(&mut input_grammar, opts.inline, opts.no_inline);
     let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
-        prepare_grammar(&input_grammar)?;
+        prepare_grammar(&input_grammar).in_step(GenerateErrorKind::PrepareGrammar)?;
     let language_name = input_grammar.name;
 
+    if opts.warn_kind_merges {
+        for merge in node_types::get_kind_merges(&syntax_grammar, &lexical_grammar, &simple_aliases)
+        {
+            let kind = if merge.named {
+                format!("({})", merge.kind)
+            } else {
+                format!("{:?}", merge.kind)
+            };
+            eprintln!(
+                "Warning: the rules {} all produce {kind} nodes",
+                merge.rules.join(", ")
+            );
+        }
+    }
+
     // Generate the parser and related files.
     let GeneratedParser {
         c_code,
+        symbols_header,
         node_types_json,
+        stats,
+        parse_table_dump,
     } = generate_parser_for_grammar_with_opts(
         &language_name,
         syntax_grammar,
         lexical_grammar,
         &inlines,
         simple_aliases,
-        abi_version,
-        report_symbol_name,
-    )?;
+        opts.abi_version,
+        opts.report_symbol_name,
+        opts.report_precedence,
+        opts.dump_parse_table,
+        opts.header_prefix,
+        opts.emit_symbols_header,
+        opts.compact_node_types,
+    )
+    .in_step(GenerateErrorKind::BuildTables)?;
+    if let Some(parse_table_dump) = parse_table_dump {
+        print!("{parse_table_dump}");
+    }
+
+    let c_code = if opts.stamp {
+        let options = StampOptions {
+            abi_version: opts.abi_version,
+            inline: opts.inline.to_vec(),
+            no_inline: opts.no_inline.to_vec(),
+            header_prefix: opts.header_prefix.map(ToString::to_string),
+        };
+        format!("{}\n{c_code}", stamp_comment(&grammar_json, &options)?)
+    } else {
+        c_code
+    };
 
-    write_file(&src_path.join("parser.c"), c_code)?;
-    write_file(&src_path.join("node-types.json"), node_types_json)?;
-    write_file(&header_path.join("alloc.h"), ALLOC_HEADER)?;
-    write_file(&header_path.join("array.h"), tree_sitter::ARRAY_HEADER)?;
-    write_file(&header_path.join("parser.h"), tree_sitter::PARSER_HEADER)?;
+    // In minimal mode, only write the files that are needed to compile the parser.
+    output.write(&src_path.join("parser.c"), c_code)?;
+    output.write(&header_path.join("parser.h"), tree_sitter::PARSER_HEADER)?;
+    if let Some(symbols_header) = symbols_header {
+        output.write(&src_path.join("symbols.h"), symbols_header)?;
+    }
+    if !opts.minimal {
+        output.write(&src_path.join("node-types.json"), node_types_json)?;
+        output.write(&header_path.join("alloc.h"), ALLOC_HEADER)?;
+        output.write(&header_path.join("array.h"), tree_sitter::ARRAY_HEADER)?;
+    }
 
-    if !path_in_ignore(&repo_path) {
-        grammar_files::generate_grammar_files(&repo_path, &language_name, generate_bindings)?;
+    if let Some(stats_path) = opts.stats_path {
+        output.write(stats_path, serde_json::to_string_pretty(&stats)?)?;
     }
 
+    let should_generate_grammar_files = !path_in_ignore(&repo_path) && !opts.minimal;
+    if opts.dry_run {
+        for (path, size) in &output.files {
+            println!("Would write {} ({size} bytes)", path.display());
+        }
+        if should_generate_grammar_files {
+            println!("Would also create any missing files of the grammar's package and bindings");
+        }
+    } else if should_generate_grammar_files {
+        grammar_files::generate_grammar_files(&repo_path, &language_name, opts.generate_bindings)?;
+    }
+
+    Ok(())
+}
+
+/// A hash of the grammar's JSON that doesn't depend on its formatting.
+pub fn grammar_hash(grammar_json: &str) -> Result<u64> {
+    let grammar_json = JSON_COMMENT_REGEX.replace_all(grammar_json, "\n");
+    let value = serde_json::from_str::<serde_json::Value>(&grammar_json)
+        .context("Failed to parse grammar.json")?;
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in value.to_string().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(hash)
+}
+
+/// The options of `generate` that change the contents of `parser.c`, which
+/// `--stamp` records so that `check-generated` can hash the grammar with them.
+#[derive(Serialize, Deserialize)]
+struct StampOptions {
+    abi_version: usize,
+    inline: Vec<String>,
+    no_inline: Vec<String>,
+    header_prefix: Option<String>,
+}
+
+/// A hash of the grammar, the options that it was generated with, and the version
+/// of the CLI that generated it.
+fn stamp_hash(grammar_json: &str, options_json: &str, version: &str) -> Result<String> {
+    let mut hash = grammar_hash(grammar_json)?;
+    for byte in [options_json, version].join("\0").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(format!("{hash:016x}"))
+}
+
+fn stamp_comment(grammar_json: &str, options: &StampOptions) -> Result<String> {
+    let options_json = serde_json::to_string(options)?;
+    let version = env!("CARGO_PKG_VERSION");
+    Ok(format!(
+        "{STAMP_PREFIX}{}, tree-sitter {version}, options {options_json} */",
+        stamp_hash(grammar_json, &options_json, version)?,
+    ))
+}
+
+/// Check that the `parser.c` in the grammar's `src` directory was generated, with
+/// `--stamp`, from the grammar as it is now. The grammar is loaded from its
+/// `grammar.js`, or from `src/grammar.json` if there is none.
+pub fn check_generated(repo_path: &Path, js_runtime: Option<&str>) -> Result<()> {
+    let parser_path = repo_path.join("src").join("parser.c");
+    let parser_c = fs::read_to_string(&parser_path)
+        .with_context(|| format!("Failed to read {parser_path:?}"))?;
+    let grammar_js_path = repo_path.join("grammar.js");
+    let grammar_path = if grammar_js_path.exists() {
+        grammar_js_path
+    } else {
+        repo_path.join("src").join("grammar.json")
+    };
+    let grammar_json = load_grammar_file(&grammar_path, js_runtime)?;
+    check_stamp(&parser_c, &grammar_json)
+        .with_context(|| format!("{parser_path:?} doesn't match {grammar_path:?}"))
+}
+
+/// Check the stamp at the top of `parser.c` against the grammar, hashed with the
+/// options in the stamp and the version of this CLI.
+fn check_stamp(parser_c: &str, grammar_json: &str) -> Result<()> {
+    let (stamped_hash, stamped_version, options_json) = parser_c
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix(STAMP_PREFIX)?.strip_suffix(" */"))
+        .and_then(|stamp| {
+            let (hash, rest) = stamp.split_once(", tree-sitter ")?;
+            let (version, options_json) = rest.split_once(", options ")?;
+            Some((hash, version, options_json))
+        })
+        .ok_or_else(|| {
+            anyhow!("parser.c has no stamp, run `tree-sitter generate --stamp` to add one")
+        })?;
+    serde_json::from_str::<StampOptions>(options_json)
+        .context("Invalid options in the stamp of parser.c")?;
+
+    let version = env!("CARGO_PKG_VERSION");
+    if stamped_version != version {
+        return Err(anyhow!(
+            "parser.c was generated by tree-sitter {stamped_version}, not {version}, run `tree-sitter generate --stamp` to update it"
+        ));
+    }
+    let hash = stamp_hash(grammar_json, options_json, version)?;
+    if hash != stamped_hash {
+        return Err(anyhow!(
+            "parser.c is out of date, it was generated with hash {stamped_hash}, but the grammar and options have hash {hash}"
+        ));
+    }
     Ok(())
 }
 
+/// Add rules to the grammar's `inline` list and remove others from it, so that the
+/// effect of inlining can be measured without editing the grammar.
+fn override_variables_to_inline(
+    grammar: &mut InputGrammar,
+    inline: &[String],
+    no_inline: &[String],
+) {
+    for name in inline.iter().chain(no_inline) {
+        if !grammar
+            .variables
+            .iter()
+            .any(|variable| &variable.name == name)
+        {
+            eprintln!("Warning: there is no rule named `{name}` to inline");
+        }
+    }
+    for name in inline {
+        if !grammar.variables_to_inline.contains(name) {
+            grammar.variables_to_inline.push(name.clone());
+        }
+    }
+    grammar
+        .variables_to_inline
+        .retain(|name| !no_inline.contains(name));
+}
+
 pub fn generate_parser_for_grammar(grammar_json: &str) -> Result<(String, String)> {
     let grammar_json = JSON_COMMENT_REGEX.replace_all(grammar_json, "\n");
     let input_grammar = parse_grammar(&grammar_json)?;
@@ -148,10 +484,16 @@ pub fn generate_parser_for_grammar(grammar_json: &str) -> Result<(String, String
         simple_aliases,
         tree_sitter::LANGUAGE_VERSION,
         None,
+        false,
+        None,
+        None,
+        false,
+        false,
     )?;
     Ok((input_grammar.name, parser.c_code))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn generate_parser_for_grammar_with_opts(
     name: &str,
     syntax_grammar: SyntaxGrammar,
@@ -160,6 +502,11 @@ fn generate_parser_for_grammar_with_opts(
     simple_aliases: AliasMap,
     abi_version: usize,
     report_symbol_name: Option<&str>,
+    report_precedence: bool,
+    dump_parse_table: Option<Option<usize>>,
+    header_prefix: Option<&str>,
+    emit_symbols_header: bool,
+    compact_node_types: bool,
 ) -> Result<GeneratedParser> {
     let variable_info =
         node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)?;
@@ -176,8 +523,26 @@ fn generate_parser_for_grammar_with_opts(
         &variable_info,
         inlines,
         report_symbol_name,
+        report_precedence,
     )?;
-    let c_code = render_c_code(
+    let parse_table_dump = dump_parse_table
+        .map(|state_id| {
+            render_parse_table(&parse_table, &syntax_grammar, &lexical_grammar, state_id)
+        })
+        .transpose()?;
+    let mut stats = GenerateStats {
+        symbol_count: parse_table.symbols.len(),
+        parse_state_count: parse_table.states.len(),
+        parse_table_entry_count: parse_table
+            .states
+            .iter()
+            .map(|state| state.terminal_entries.len() + state.nonterminal_entries.len())
+            .sum(),
+        lex_state_count: main_lex_table.states.len(),
+        keyword_lex_state_count: keyword_lex_table.states.len(),
+        parser_c_bytes: 0,
+    };
+    let (c_code, symbols_header) = render_c_code(
         name,
         parse_table,
         main_lex_table,
@@ -187,10 +552,20 @@ fn generate_parser_for_grammar_with_opts(
         lexical_grammar,
         simple_aliases,
         abi_version,
+        header_prefix,
+        emit_symbols_header,
     );
+    stats.parser_c_bytes = c_code.len();
     Ok(GeneratedParser {
         c_code,
-        node_types_json: serde_json::to_string_pretty(&node_types_json).unwrap(),
+        symbols_header,
+        node_types_json: if compact_node_types {
+            serde_json::to_string(&node_types_json).unwrap()
+        } else {
+            serde_json::to_string_pretty(&node_types_json).unwrap()
+        },
+        stats,
+        parse_table_dump,
     })
 }
 
@@ -211,14 +586,33 @@ pub fn load_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Resul
 }
 
 fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result<String> {
+    Ok(
+        serde_json::to_string_pretty(&run_js_grammar_file(grammar_path, js_runtime, false)?)
+            .with_context(|| "Failed to serialize grammar JSON")?
+            + "\n",
+    )
+}
+
+/// Run a grammar's `grammar.js` with the DSL, returning the grammar it exports, or
+/// the paths of all of the files that it required if `list_modules` is set.
+fn run_js_grammar_file(
+    grammar_path: &Path,
+    js_runtime: Option<&str>,
+    list_modules: bool,
+) -> Result<serde_json::Value> {
     let grammar_path = fs::canonicalize(grammar_path)?;
 
     let js_runtime = js_runtime.unwrap_or("node");
 
-    let mut node_process = Command::new(js_runtime)
-        .env("TREE_SITTER_GRAMMAR_PATH", grammar_path)
+    let mut command = Command::new(js_runtime);
+    command.env("TREE_SITTER_GRAMMAR_PATH", grammar_path);
+    if list_modules {
+        command.env("TREE_SITTER_LIST_MODULES", "1");
+    }
+    let mut node_process = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .with_context(|| format!("Failed to run `{js_runtime}`"))?;
 
@@ -244,6 +638,8 @@ fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result
     let output = node_process
         .wait_with_output()
         .with_context(|| "Failed to read output from node")?;
+    // The runtime's stderr is still shown, but it's also kept for the error.
+    std::io::stderr().write_all(&output.stderr)?;
     match output.status.code() {
         None => panic!("Node process was killed"),
         Some(0) => {
@@ -257,20 +653,110 @@ fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result
                 let node_output = &stdout[..pos];
                 grammar_json = &stdout[pos + 1..];
 
-                let mut stdout = std::io::stdout().lock();
-                stdout.write_all(node_output.as_bytes())?;
-                stdout.write_all(b"\n")?;
-                stdout.flush()?;
+                // The list of modules is printed as JSON, so the grammar's own output
+                // goes to stderr to keep it apart.
+                let mut out: Box<dyn Write> = if list_modules {
+                    Box::new(std::io::stderr().lock())
+                } else {
+                    Box::new(std::io::stdout().lock())
+                };
+                out.write_all(node_output.as_bytes())?;
+                out.write_all(b"\n")?;
+                out.flush()?;
             }
 
-            Ok(serde_json::to_string_pretty(
-                &serde_json::from_str::<serde_json::Value>(grammar_json)
-                    .with_context(|| "Failed to parse grammar JSON")?,
-            )
-            .with_context(|| "Failed to serialize grammar JSON")?
-                + "\n")
+            serde_json::from_str(grammar_json).with_context(|| "Failed to parse grammar JSON")
+        }
+        Some(status) => Err(JsRuntimeError {
+            status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into()),
+    }
+}
+
+#[derive(Serialize)]
+pub struct GrammarDependencies {
+    pub grammar: PathBuf,
+    /// The packages, other than the grammar's own, that the required files are in.
+    pub dependencies: Vec<GrammarDependency>,
+    /// Every file that was required while loading the grammar, besides itself.
+    pub modules: Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+pub struct GrammarDependency {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub path: PathBuf,
+}
+
+/// Load a grammar's `grammar.js` and find the packages of the files that it
+/// required while it was loading, such as other grammars that it extends.
+pub fn grammar_dependencies(
+    grammar_path: &Path,
+    js_runtime: Option<&str>,
+) -> Result<GrammarDependencies> {
+    let grammar_path = fs::canonicalize(grammar_path)
+        .with_context(|| format!("Failed to read {}", grammar_path.display()))?;
+    let modules = serde_json::from_value::<Vec<PathBuf>>(
+        run_js_grammar_file(&grammar_path, js_runtime, true)
+            .with_context(|| "Failed to load grammar.js")?,
+    )
+    .with_context(|| "Failed to parse the list of modules")?
+    .into_iter()
+    .filter(|path| *path != grammar_path)
+    .collect::<Vec<_>>();
+
+    let package_root = |path: &Path| {
+        path.ancestors()
+            .skip(1)
+            .find(|dir| dir.join("package.json").is_file())
+            .map(Path::to_path_buf)
+    };
+    let grammar_root = package_root(&grammar_path);
+    let mut dependencies = Vec::<GrammarDependency>::new();
+    for root in modules.iter().filter_map(|path| package_root(path)) {
+        if Some(&root) == grammar_root.as_ref() || dependencies.iter().any(|d| d.path == root) {
+            continue;
+        }
+        let package_json = fs::read_to_string(root.join("package.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok());
+        let field = |name: &str| {
+            package_json
+                .as_ref()
+                .and_then(|json| json[name].as_str())
+                .map(str::to_string)
+        };
+        dependencies.push(GrammarDependency {
+            name: field("name"),
+            version: field("version"),
+            path: root,
+        });
+    }
+
+    Ok(GrammarDependencies {
+        grammar: grammar_path,
+        dependencies,
+        modules,
+    })
+}
+
+/// The files written by `generate`, which are only listed with `--dry-run`.
+struct OutputFiles {
+    dry_run: bool,
+    files: Vec<(PathBuf, usize)>,
+}
+
+impl OutputFiles {
+    fn write(&mut self, path: &Path, body: impl AsRef<[u8]>) -> Result<()> {
+        if self.dry_run {
+            self.files.push((path.to_owned(), body.as_ref().len()));
+            Ok(())
+        } else {
+            write_file(path, body)
         }
-        Some(code) => Err(anyhow!("Node process exited with status {code}")),
     }
 }
 
@@ -278,3 +764,220 @@ fn write_file(path: &Path, body: impl AsRef<[u8]>) -> Result<()> {
     fs::write(path, body)
         .with_context(|| format!("Failed to write {:?}", path.file_name().unwrap()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAMMAR_JSON: &str =
+        r#"{"name": "test", "rules": {"source_file": {"type": "STRING", "value": "a"}}}"#;
+
+    fn stamped_parser_c(grammar_json: &str, inline: &[&str]) -> String {
+        let options = StampOptions {
+            abi_version: 14,
+            inline: inline.iter().map(ToString::to_string).collect(),
+            no_inline: Vec::new(),
+            header_prefix: None,
+        };
+        format!(
+            "{}\n#include \"tree_sitter/parser.h\"\n",
+            stamp_comment(grammar_json, &options).unwrap()
+        )
+    }
+
+    #[test]
+    fn test_check_stamp() {
+        let parser_c = stamped_parser_c(GRAMMAR_JSON, &[]);
+        check_stamp(&parser_c, GRAMMAR_JSON).unwrap();
+
+        // The hash doesn't depend on the formatting of grammar.json.
+        let reformatted = serde_json::to_string_pretty(
+            &serde_json::from_str::<serde_json::Value>(GRAMMAR_JSON).unwrap(),
+        )
+        .unwrap();
+        check_stamp(&parser_c, &reformatted).unwrap();
+
+        // A change to the grammar is detected.
+        let changed = GRAMMAR_JSON.replace(r#""value": "a""#, r#""value": "b""#);
+        assert!(check_stamp(&parser_c, &changed).is_err());
+
+        // So is a stamp whose options were changed after it was generated.
+        let other_parser_c = stamped_parser_c(GRAMMAR_JSON, &["_rule"]);
+        assert_ne!(parser_c, other_parser_c);
+        let (stamp, _) = parser_c.split_once('\n').unwrap();
+        let (other_stamp, _) = other_parser_c.split_once('\n').unwrap();
+        let (_, options) = other_stamp.split_once(", options ").unwrap();
+        let (stamp_without_options, _) = stamp.split_once(", options ").unwrap();
+        let edited = format!("{stamp_without_options}, options {options}\n");
+        assert!(check_stamp(&edited, GRAMMAR_JSON).is_err());
+
+        // And one from another version of the CLI.
+        let old_version = parser_c.replace(
+            &format!(", tree-sitter {}, ", env!("CARGO_PKG_VERSION")),
+            ", tree-sitter 0.0.0, ",
+        );
+        assert!(check_stamp(&old_version, GRAMMAR_JSON).is_err());
+
+        assert!(check_stamp("#include \"tree_sitter/parser.h\"\n", GRAMMAR_JSON).is_err());
+    }
+
+    fn generate_options<'a>(grammar_path: &'a Path, out_path: &'a Path) -> GenerateOptions<'a> {
+        GenerateOptions {
+            grammar_path: grammar_path.to_str(),
+            abi_version: tree_sitter::LANGUAGE_VERSION,
+            generate_bindings: false,
+            report_symbol_name: None,
+            js_runtime: None,
+            warn_kind_merges: false,
+            stats_path: None,
+            inline: &[],
+            no_inline: &[],
+            report_precedence: false,
+            dump_parse_table: None,
+            out_path: Some(out_path),
+            header_prefix: None,
+            emit_symbols_header: false,
+            compact_node_types: false,
+            stamp: false,
+            minimal: true,
+            dry_run: false,
+        }
+    }
+
+    fn generate_error(grammar_file_name: &str, grammar: &str, out_dir: &str) -> GenerateError {
+        let dir = tempfile::tempdir().unwrap();
+        // Keep `generate` from scaffolding a new grammar in the directory.
+        fs::write(dir.path().join("grammar.js"), "").unwrap();
+        let grammar_path = dir.path().join(grammar_file_name);
+        fs::write(&grammar_path, grammar).unwrap();
+        let out_path = dir.path().join(out_dir);
+        generate_parser_in_directory(dir.path(), &generate_options(&grammar_path, &out_path))
+            .unwrap_err()
+            .into()
+    }
+
+    #[test]
+    fn test_generate_error_kinds() {
+        let error = generate_error("grammar.txt", GRAMMAR_JSON, "src");
+        assert_eq!(error.kind, GenerateErrorKind::LoadGrammar);
+        assert_eq!(error.to_json().details, None);
+
+        let error = generate_error("grammar.js", "module.exports = grammar(oops);", "src");
+        assert_eq!(error.kind, GenerateErrorKind::LoadGrammar);
+        let details = error.to_json().details.unwrap();
+        assert!(
+            details.contains("ReferenceError: oops is not defined"),
+            "{details}"
+        );
+
+        let error = generate_error(
+            "grammar.json",
+            "{\n  \"name\": \"test\",\n  \"rules\": }",
+            "src",
+        );
+        assert_eq!(error.kind, GenerateErrorKind::ParseGrammar);
+        let json = error.to_json();
+        assert_eq!(json.kind, GenerateErrorKind::ParseGrammar);
+        assert_eq!((json.line, json.column), (Some(3), Some(12)));
+        assert_eq!(json.message, error.to_string());
+
+        let error = generate_error(
+            "grammar.json",
+            r#"{"name": "test", "rules": {"source_file": {"type": "PATTERN", "value": "^a"}}}"#,
+            "src",
+        );
+        assert_eq!(error.kind, GenerateErrorKind::PrepareGrammar);
+        let json = error.to_json();
+        assert_eq!((json.line, json.column), (None, None));
+        assert_eq!(
+            json.message,
+            "Error processing rule source_file_token1: Regex error: Assertions are not supported"
+        );
+
+        let error = generate_error(
+            "grammar.json",
+            r#"
+            {
+                "name": "test",
+                "rules": {
+                    "expression": {
+                        "type": "CHOICE",
+                        "members": [
+                            {
+                                "type": "SEQ",
+                                "members": [
+                                    {"type": "SYMBOL", "name": "expression"},
+                                    {"type": "STRING", "value": "+"},
+                                    {"type": "SYMBOL", "name": "expression"}
+                                ]
+                            },
+                            {"type": "STRING", "value": "a"}
+                        ]
+                    }
+                }
+            }
+            "#,
+            "src",
+        );
+        assert_eq!(error.kind, GenerateErrorKind::BuildTables);
+        assert!(
+            error.to_json().message.contains("Unresolved conflict"),
+            "{}",
+            error
+        );
+
+        // The output directory can't be created inside of a file.
+        let error = generate_error("grammar.json", GRAMMAR_JSON, "grammar.js/src");
+        assert_eq!(error.kind, GenerateErrorKind::Other);
+        assert_eq!(error.to_json().kind, GenerateErrorKind::Other);
+    }
+
+    #[test]
+    fn test_output_files_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("parser.c");
+        let mut output = OutputFiles {
+            dry_run: true,
+            files: Vec::new(),
+        };
+        output.write(&path, "abc").unwrap();
+        output
+            .write(&dir.path().join("node-types.json"), [])
+            .unwrap();
+        assert_eq!(
+            output.files,
+            [(path.clone(), 3), (dir.path().join("node-types.json"), 0)]
+        );
+        assert!(!path.exists());
+
+        let mut output = OutputFiles {
+            dry_run: false,
+            files: Vec::new(),
+        };
+        output.write(&path, "abc").unwrap();
+        assert!(output.files.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_generate_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let grammar_path = dir.path().join("grammar.json");
+        fs::write(&grammar_path, GRAMMAR_JSON).unwrap();
+        let out_path = dir.path().join("src");
+        let opts = GenerateOptions {
+            minimal: false,
+            stats_path: Some(&dir.path().join("stats.json")),
+            dry_run: true,
+            ..generate_options(&grammar_path, &out_path)
+        };
+        generate_parser_in_directory(dir.path(), &opts).unwrap();
+
+        // Neither the output directory nor the grammar's scaffolding is created.
+        let entries = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(entries, ["grammar.json"]);
+    }
+}
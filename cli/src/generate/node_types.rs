@@ -3,7 +3,7 @@ use super::rules::{Alias, AliasMap, Symbol, SymbolType};
 use anyhow::{anyhow, Result};
 use serde::Serialize;
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ChildType {
@@ -425,7 +425,7 @@ pub fn generate_node_types_json(
     let mut aliases_by_symbol = HashMap::new();
     for (symbol, alias) in default_aliases {
         aliases_by_symbol.insert(*symbol, {
-            let mut aliases = HashSet::new();
+            let mut aliases = BTreeSet::new();
             aliases.insert(Some(alias.clone()));
             aliases
         });
@@ -434,7 +434,7 @@ pub fn generate_node_types_json(
         if !default_aliases.contains_key(extra_symbol) {
             aliases_by_symbol
                 .entry(*extra_symbol)
-                .or_insert_with(HashSet::new)
+                .or_insert_with(BTreeSet::new)
                 .insert(None);
         }
     }
@@ -443,7 +443,7 @@ pub fn generate_node_types_json(
             for step in &production.steps {
                 aliases_by_symbol
                     .entry(step.symbol)
-                    .or_insert_with(HashSet::new)
+                    .or_insert_with(BTreeSet::new)
                     .insert(
                         step.alias
                             .as_ref()
@@ -492,7 +492,7 @@ pub fn generate_node_types_json(
             // contributes to multiple entries in the final JSON.
             for alias in aliases_by_symbol
                 .get(&Symbol::non_terminal(i))
-                .unwrap_or(&HashSet::new())
+                .unwrap_or(&BTreeSet::new())
             {
                 let kind;
                 let is_named;
@@ -584,7 +584,7 @@ pub fn generate_node_types_json(
 
     let mut anonymous_node_types = Vec::new();
 
-    let empty = HashSet::new();
+    let empty = BTreeSet::new();
     let regular_tokens = lexical_grammar
         .variables
         .iter()
@@ -661,11 +661,89 @@ pub fn generate_node_types_json(
                 a_is_leaf.cmp(&b_is_leaf)
             })
             .then_with(|| a.kind.cmp(&b.kind))
+            .then_with(|| a.cmp(b))
     });
     result.dedup();
     result
 }
 
+/// A node kind that is produced by more than one rule of the grammar.
+#[derive(Debug, PartialEq, Eq)]
+pub struct KindMerge {
+    pub kind: String,
+    pub named: bool,
+    pub rules: Vec<String>,
+}
+
+/// Find the visible node kinds that several distinct rules produce, either through
+/// aliases or because an alias reuses the name of another rule. All of those nodes
+/// share a single kind in the generated parser, so queries can't tell them apart.
+pub fn get_kind_merges(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+) -> Vec<KindMerge> {
+    let mut rules_by_kind = BTreeMap::<(&str, bool), BTreeSet<&str>>::new();
+
+    let symbols = (0..syntax_grammar.variables.len())
+        .map(Symbol::non_terminal)
+        .chain((0..lexical_grammar.variables.len()).map(Symbol::terminal))
+        .chain((0..syntax_grammar.external_tokens.len()).map(Symbol::external));
+    for symbol in symbols {
+        let (name, kind) = match symbol.kind {
+            SymbolType::NonTerminal => {
+                let variable = &syntax_grammar.variables[symbol.index];
+                (variable.name.as_str(), variable.kind)
+            }
+            SymbolType::Terminal => {
+                let variable = &lexical_grammar.variables[symbol.index];
+                (variable.name.as_str(), variable.kind)
+            }
+            _ => {
+                let token = &syntax_grammar.external_tokens[symbol.index];
+                (token.name.as_str(), token.kind)
+            }
+        };
+        let visible_kind = if let Some(alias) = default_aliases.get(&symbol) {
+            (alias.value.as_str(), alias.is_named)
+        } else {
+            match kind {
+                VariableType::Named => (name, true),
+                VariableType::Anonymous => (name, false),
+                VariableType::Hidden | VariableType::Auxiliary => continue,
+            }
+        };
+        rules_by_kind.entry(visible_kind).or_default().insert(name);
+    }
+
+    for variable in &syntax_grammar.variables {
+        for step in variable.productions.iter().flat_map(|p| &p.steps) {
+            if let Some(alias) = &step.alias {
+                let name = match step.symbol.kind {
+                    SymbolType::NonTerminal => &syntax_grammar.variables[step.symbol.index].name,
+                    SymbolType::Terminal => &lexical_grammar.variables[step.symbol.index].name,
+                    SymbolType::External => &syntax_grammar.external_tokens[step.symbol.index].name,
+                    _ => continue,
+                };
+                rules_by_kind
+                    .entry((alias.value.as_str(), alias.is_named))
+                    .or_default()
+                    .insert(name);
+            }
+        }
+    }
+
+    rules_by_kind
+        .into_iter()
+        .filter(|(_, rules)| rules.len() > 1)
+        .map(|((kind, named), rules)| KindMerge {
+            kind: kind.to_string(),
+            named,
+            rules: rules.into_iter().map(str::to_string).collect(),
+        })
+        .collect()
+}
+
 fn process_supertypes(info: &mut FieldInfoJSON, subtype_map: &[(NodeTypeJSON, Vec<NodeTypeJSON>)]) {
     for (supertype, subtypes) in subtype_map {
         if info.types.contains(supertype) {
@@ -719,7 +797,7 @@ mod tests {
         InputGrammar, LexicalVariable, Production, ProductionStep, SyntaxVariable, Variable,
     };
     use crate::generate::prepare_grammar::prepare_grammar;
-    use crate::generate::rules::Rule;
+    use crate::generate::rules::{Precedence, Rule};
 
     #[test]
     fn test_node_types_simple() {
@@ -1465,6 +1543,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_kind_merges() {
+        let (syntax_grammar, lexical_grammar, _, default_aliases) =
+            prepare_grammar(&InputGrammar {
+                variables: vec![
+                    Variable {
+                        name: "script".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::choice(vec![
+                            Rule::named("a"),
+                            Rule::alias(Rule::named("b"), "a".to_string(), true),
+                            Rule::named("c"),
+                        ]),
+                    },
+                    Variable {
+                        name: "a".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::seq(vec![Rule::string("1"), Rule::string("2")]),
+                    },
+                    Variable {
+                        name: "b".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::seq(vec![Rule::string("2"), Rule::string("1")]),
+                    },
+                    // The same token appearing with and without a precedence is not a merge.
+                    Variable {
+                        name: "c".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::seq(vec![
+                            Rule::prec(Precedence::Integer(1), Rule::token(Rule::string("1"))),
+                            Rule::string("1"),
+                        ]),
+                    },
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            get_kind_merges(&syntax_grammar, &lexical_grammar, &default_aliases),
+            vec![KindMerge {
+                kind: "a".to_string(),
+                named: true,
+                rules: vec!["a".to_string(), "b".to_string()],
+            }]
+        );
+    }
+
     #[test]
     fn test_get_variable_info() {
         let variable_info = get_variable_info(
@@ -1765,6 +1891,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_node_types_are_deterministic() {
+        let grammar = InputGrammar {
+            extra_symbols: vec![Rule::named("comment")],
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::alias(Rule::named("pair"), "entry".to_string(), true),
+                        Rule::alias(Rule::named("pair"), "item".to_string(), true),
+                        Rule::named("pair"),
+                        Rule::alias(Rule::string(":"), "colon".to_string(), false),
+                        Rule::alias(Rule::string(":"), "separator".to_string(), true),
+                        Rule::named("identifier"),
+                        Rule::string("identifier"),
+                    ])),
+                },
+                Variable {
+                    name: "pair".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::field("key".to_string(), Rule::named("identifier")),
+                        Rule::string(":"),
+                        Rule::field(
+                            "value".to_string(),
+                            Rule::choice(vec![Rule::named("identifier"), Rule::named("number")]),
+                        ),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+                Variable {
+                    name: "comment".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("#.*", ""),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let node_types_json = serde_json::to_string_pretty(&get_node_types(&grammar)).unwrap();
+        for _ in 0..10 {
+            assert_eq!(
+                serde_json::to_string_pretty(&get_node_types(&grammar)).unwrap(),
+                node_types_json
+            );
+        }
+    }
+
     fn get_node_types(grammar: &InputGrammar) -> Vec<NodeInfoJSON> {
         let (syntax_grammar, lexical_grammar, _, default_aliases) =
             prepare_grammar(grammar).unwrap();
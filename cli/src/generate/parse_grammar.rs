@@ -1,8 +1,9 @@
 use super::grammars::{InputGrammar, PrecedenceEntry, Variable, VariableType};
 use super::rules::{Precedence, Rule};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::Deserialize;
 use serde_json::{Map, Value};
+use std::collections::HashSet;
 
 #[derive(Deserialize)]
 #[serde(tag = "type")]
@@ -99,7 +100,9 @@ pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
         variables.push(Variable {
             name: name.clone(),
             kind: VariableType::Named,
-            rule: parse_rule(serde_json::from_value(value)?),
+            rule: parse_rule(
+                serde_json::from_value(value).with_context(|| format!("Invalid rule `{name}`"))?,
+            ),
         });
     }
 
@@ -138,7 +141,7 @@ pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
 
     let external_tokens = grammar_json.externals.into_iter().map(parse_rule).collect();
 
-    Ok(InputGrammar {
+    let grammar = InputGrammar {
         name: grammar_json.name,
         word_token: grammar_json.word,
         expected_conflicts: grammar_json.conflicts,
@@ -148,7 +151,82 @@ pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
         variables,
         extra_symbols,
         external_tokens,
-    })
+    };
+    validate_grammar(&grammar)?;
+    Ok(grammar)
+}
+
+/// Check that every name used in the grammar refers to a rule or an external
+/// token, reporting all of the problems at once.
+fn validate_grammar(grammar: &InputGrammar) -> Result<()> {
+    fn check_rule(rule: &Rule, names: &HashSet<&str>, context: &str, errors: &mut Vec<String>) {
+        match rule {
+            Rule::NamedSymbol(name) if !names.contains(name.as_str()) => {
+                errors.push(format!(
+                    "{context} references the undefined symbol `{name}`"
+                ));
+            }
+            Rule::Choice(elements) | Rule::Seq(elements) => {
+                for element in elements {
+                    check_rule(element, names, context, errors);
+                }
+            }
+            Rule::Repeat(rule) | Rule::Metadata { rule, .. } => {
+                check_rule(rule, names, context, errors);
+            }
+            _ => {}
+        }
+    }
+
+    let names = grammar
+        .variables
+        .iter()
+        .map(|variable| variable.name.as_str())
+        .chain(
+            grammar
+                .external_tokens
+                .iter()
+                .filter_map(|rule| match rule {
+                    Rule::NamedSymbol(name) => Some(name.as_str()),
+                    _ => None,
+                }),
+        )
+        .collect::<HashSet<_>>();
+
+    let mut errors = Vec::new();
+    for variable in &grammar.variables {
+        let context = format!("The rule `{}`", variable.name);
+        check_rule(&variable.rule, &names, &context, &mut errors);
+    }
+    for rule in &grammar.extra_symbols {
+        check_rule(rule, &names, "The `extras` array", &mut errors);
+    }
+    if let Some(word) = &grammar.word_token {
+        if !names.contains(word.as_str()) {
+            errors.push(format!("The `word` token `{word}` is not defined"));
+        }
+    }
+    for supertype in &grammar.supertype_symbols {
+        if !names.contains(supertype.as_str()) {
+            errors.push(format!("The supertype `{supertype}` is not defined"));
+        }
+    }
+    for conflict in &grammar.expected_conflicts {
+        for name in conflict {
+            if !names.contains(name.as_str()) {
+                errors.push(format!(
+                    "The conflict [{}] references the undefined symbol `{name}`",
+                    conflict.join(", ")
+                ));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("Invalid grammar:\n  {}", errors.join("\n  ")))
+    }
 }
 
 fn parse_rule(json: RuleJSON) -> Rule {
@@ -214,6 +292,44 @@ impl From<PrecedenceValueJSON> for Precedence {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_grammar_with_undefined_symbols() {
+        let error = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "word": "identifier",
+            "supertypes": ["_expression"],
+            "conflicts": [["file", "statement"]],
+            "extras": [{"type": "SYMBOL", "name": "comment"}],
+            "externals": [{"type": "SYMBOL", "name": "heredoc"}],
+            "rules": {
+                "file": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "heredoc"},
+                        {"type": "SYMBOL", "name": "statement"}
+                    ]
+                },
+                "name": {"type": "PATTERN", "value": "\\w+"}
+            }
+        }"#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            [
+                "Invalid grammar:",
+                "  The rule `file` references the undefined symbol `statement`",
+                "  The `extras` array references the undefined symbol `comment`",
+                "  The `word` token `identifier` is not defined",
+                "  The supertype `_expression` is not defined",
+                "  The conflict [file, statement] references the undefined symbol `statement`",
+            ]
+            .join("\n")
+        );
+    }
+
     #[test]
     fn test_parse_grammar() {
         let grammar = parse_grammar(
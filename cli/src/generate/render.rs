@@ -74,6 +74,8 @@ struct Generator {
     unique_aliases: Vec<Alias>,
     symbol_map: HashMap<Symbol, Symbol>,
     field_names: Vec<String>,
+    header_prefix: String,
+    emit_symbols_header: bool,
 
     #[allow(unused)]
     abi_version: usize,
@@ -92,7 +94,7 @@ struct LargeCharacterSetInfo {
 }
 
 impl Generator {
-    fn generate(mut self) -> String {
+    fn generate(mut self) -> (String, Option<String>) {
         self.init();
         self.add_includes();
         self.add_pragmas();
@@ -139,7 +141,40 @@ impl Generator {
 
         self.add_parser_export();
 
-        self.buffer
+        let symbols_header = self.emit_symbols_header.then(|| self.symbols_header());
+        (self.buffer, symbols_header)
+    }
+
+    /// A header for external scanners, with the ids of the language's tokens
+    /// under the same names as in `parser.c`, and the indices of its external
+    /// tokens in the scanner's `valid_symbols` array.
+    fn symbols_header(&self) -> String {
+        let guard = format!(
+            "TREE_SITTER_{}_SYMBOLS_H_",
+            self.sanitize_identifier(&self.language_name)
+                .to_ascii_uppercase()
+        );
+        let mut result = String::new();
+        writeln!(&mut result, "#ifndef {guard}").unwrap();
+        writeln!(&mut result, "#define {guard}\n").unwrap();
+        for symbol in &self.parse_table.symbols {
+            if symbol.is_terminal() || symbol.is_external() {
+                writeln!(
+                    &mut result,
+                    "#define {} {}",
+                    self.symbol_ids[symbol], self.symbol_order[symbol]
+                )
+                .unwrap();
+            }
+        }
+        if !self.syntax_grammar.external_tokens.is_empty() {
+            result += "\n";
+            for (i, token) in self.syntax_grammar.external_tokens.iter().enumerate() {
+                writeln!(&mut result, "#define {} {i}", self.external_token_id(token)).unwrap();
+            }
+        }
+        writeln!(&mut result, "\n#endif  // {guard}").unwrap();
+        result
     }
 
     fn init(&mut self) {
@@ -257,7 +292,12 @@ impl Generator {
     }
 
     fn add_includes(&mut self) {
-        add_line!(self, "#include \"tree_sitter/parser.h\"");
+        let header_path = if self.header_prefix.is_empty() {
+            "parser.h".to_string()
+        } else {
+            format!("{}/parser.h", self.header_prefix.trim_end_matches('/'))
+        };
+        add_line!(self, "#include \"{header_path}\"");
         add_line!(self, "");
     }
 
@@ -1671,7 +1711,8 @@ impl Generator {
     }
 }
 
-/// Returns a String of C code for the given components of a parser.
+/// Returns a String of C code for the given components of a parser, along with
+/// the contents of `symbols.h` if it was requested.
 ///
 /// # Arguments
 ///
@@ -1689,6 +1730,10 @@ impl Generator {
 /// * `abi_version` - The language ABI version that should be generated. Usually
 ///    you want Tree-sitter's current version, but right after making an ABI
 ///    change, it may be useful to generate code with the previous ABI.
+/// * `header_prefix` - The directory through which `parser.c` includes `parser.h`,
+///   `tree_sitter` by default.
+/// * `emit_symbols_header` - Whether to also return the contents of a `symbols.h`
+///   header, with the ids of the language's tokens.
 #[allow(clippy::too_many_arguments)]
 pub fn render_c_code(
     name: &str,
@@ -1700,7 +1745,9 @@ pub fn render_c_code(
     lexical_grammar: LexicalGrammar,
     default_aliases: AliasMap,
     abi_version: usize,
-) -> String {
+    header_prefix: Option<&str>,
+    emit_symbols_header: bool,
+) -> (String, Option<String>) {
     assert!(
         (ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&abi_version),
         "This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {abi_version}",
@@ -1724,7 +1771,123 @@ pub fn render_c_code(
         symbol_map: HashMap::new(),
         unique_aliases: Vec::new(),
         field_names: Vec::new(),
+        header_prefix: header_prefix.unwrap_or("tree_sitter").to_string(),
+        emit_symbols_header,
         abi_version,
     }
     .generate()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::build_tables::build_tables;
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::parse_grammar::parse_grammar;
+    use crate::generate::prepare_grammar::prepare_grammar;
+
+    fn render(header_prefix: Option<&str>, emit_symbols_header: bool) -> (String, Option<String>) {
+        let input_grammar = parse_grammar(
+            r#"
+            {
+                "name": "test_grammar",
+                "externals": [{"type": "SYMBOL", "name": "string_content"}],
+                "rules": {
+                    "program": {
+                        "type": "REPEAT",
+                        "content": {"type": "SYMBOL", "name": "_value"}
+                    },
+                    "_value": {
+                        "type": "CHOICE",
+                        "members": [
+                            {"type": "SYMBOL", "name": "number"},
+                            {"type": "SYMBOL", "name": "string"}
+                        ]
+                    },
+                    "string": {
+                        "type": "SEQ",
+                        "members": [
+                            {"type": "STRING", "value": "\""},
+                            {"type": "SYMBOL", "name": "string_content"},
+                            {"type": "STRING", "value": "\""}
+                        ]
+                    },
+                    "number": {"type": "PATTERN", "value": "\\d+"}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
+            prepare_grammar(&input_grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases).unwrap();
+        let (parse_table, main_lex_table, keyword_lex_table, keyword_capture_token) = build_tables(
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            &variable_info,
+            &inlines,
+            None,
+            false,
+        )
+        .unwrap();
+        render_c_code(
+            &input_grammar.name,
+            parse_table,
+            main_lex_table,
+            keyword_lex_table,
+            keyword_capture_token,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            ABI_VERSION_MAX,
+            header_prefix,
+            emit_symbols_header,
+        )
+    }
+
+    #[test]
+    fn test_header_prefix() {
+        let include = |header_prefix| {
+            let (c_code, _) = render(header_prefix, false);
+            c_code.lines().next().unwrap().to_string()
+        };
+        assert_eq!(include(None), "#include \"tree_sitter/parser.h\"");
+        assert_eq!(include(Some("")), "#include \"parser.h\"");
+        assert_eq!(
+            include(Some("vendor/ts")),
+            "#include \"vendor/ts/parser.h\""
+        );
+        assert_eq!(
+            include(Some("vendor/ts/")),
+            "#include \"vendor/ts/parser.h\""
+        );
+    }
+
+    #[test]
+    fn test_symbols_header() {
+        assert_eq!(render(None, false).1, None);
+
+        let (c_code, symbols_header) = render(None, true);
+        assert_eq!(
+            symbols_header.unwrap(),
+            concat!(
+                "#ifndef TREE_SITTER_TEST_GRAMMAR_SYMBOLS_H_\n",
+                "#define TREE_SITTER_TEST_GRAMMAR_SYMBOLS_H_\n",
+                "\n",
+                "#define anon_sym_DQUOTE 1\n",
+                "#define sym_number 2\n",
+                "#define sym_string_content 3\n",
+                "\n",
+                "#define ts_external_token_string_content 0\n",
+                "\n",
+                "#endif  // TREE_SITTER_TEST_GRAMMAR_SYMBOLS_H_\n",
+            )
+        );
+        // The names are the ones that `parser.c` uses.
+        assert!(c_code.contains("  anon_sym_DQUOTE = 1,\n"));
+        assert!(c_code.contains("  sym_string_content = 3,\n"));
+        assert!(c_code.contains("  ts_external_token_string_content = 0,\n"));
+    }
+}
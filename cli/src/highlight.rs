@@ -4,8 +4,10 @@ use lazy_static::lazy_static;
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{json, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::ops::Range;
 use std::sync::atomic::AtomicUsize;
 use std::time::Instant;
 use std::{fs, io, path, str, usize};
@@ -367,11 +369,246 @@ pub fn ansi(
     Ok(())
 }
 
+/// Highlight the source code and return each of its lines painted with ANSI styles.
+pub fn ansi_lines(
+    theme: &Theme,
+    source: &[u8],
+    config: &HighlightConfiguration,
+    cancellation_flag: Option<&AtomicUsize>,
+    max_width: Option<usize>,
+) -> Result<Vec<String>> {
+    let mut highlighter = Highlighter::new();
+    let events = highlighter.highlight(config, source, cancellation_flag, |_| None)?;
+
+    // Lines that are wider than `max_width` are cut short where `truncate_line` would
+    // cut them, keeping the styles of the characters that are left, and end with the
+    // same ellipsis.
+    let line_budgets = String::from_utf8_lossy(source)
+        .split('\n')
+        .map(|line| {
+            let line = line.trim_end_matches('\r');
+            match max_width.map(|max_width| truncate_line(line, max_width)) {
+                Some(Cow::Owned(truncated)) => {
+                    let kept = truncated.strip_suffix('…').unwrap_or(&truncated);
+                    Some((kept.chars().count(), truncated[kept.len()..].to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let finish_line = |line: &mut String, row: usize| {
+        if let Some((_, ellipsis)) = &line_budgets[row] {
+            line.push_str(ellipsis);
+        }
+    };
+
+    let mut lines = vec![String::new()];
+    let mut line_width = 0;
+    let mut style_stack = vec![theme.default_style().ansi];
+    for event in events {
+        match event? {
+            HighlightEvent::HighlightStart(highlight) => {
+                style_stack.push(theme.styles[highlight.0].ansi);
+            }
+            HighlightEvent::HighlightEnd => {
+                style_stack.pop();
+            }
+            HighlightEvent::Source { start, end } => {
+                let style = style_stack.last().unwrap();
+                let text = String::from_utf8_lossy(&source[start..end]);
+                let mut segments = text.split('\n').peekable();
+                while let Some(segment) = segments.next() {
+                    let row = lines.len() - 1;
+                    let line = lines.last_mut().unwrap();
+                    let is_last = segments.peek().is_none();
+                    let segment = if is_last {
+                        segment
+                    } else {
+                        segment.trim_end_matches('\r')
+                    };
+                    if let Some((budget, _)) = line_budgets[row] {
+                        let kept = segment
+                            .chars()
+                            .take(budget.saturating_sub(line_width))
+                            .collect::<String>();
+                        if !kept.is_empty() {
+                            line_width += kept.chars().count();
+                            write!(line, "{}", style.paint(kept))?;
+                        }
+                    } else {
+                        write!(line, "{}", style.paint(segment))?;
+                    }
+                    if !is_last {
+                        finish_line(line, row);
+                        lines.push(String::new());
+                        line_width = 0;
+                    }
+                }
+            }
+        }
+    }
+    let row = lines.len() - 1;
+    finish_line(lines.last_mut().unwrap(), row);
+
+    Ok(lines)
+}
+
+/// Shorten a line to at most `max_width` characters, ending it with an ellipsis
+/// when any of it was cut off.
+#[must_use]
+pub fn truncate_line(line: &str, max_width: usize) -> Cow<'_, str> {
+    if line.chars().count() <= max_width {
+        return Cow::Borrowed(line);
+    }
+    let mut result = line
+        .chars()
+        .take(max_width.saturating_sub(1))
+        .collect::<String>();
+    if max_width > 0 {
+        result.push('…');
+    }
+    Cow::Owned(result)
+}
+
+/// The parts of a file that didn't receive any highlight, as found by `highlight --check`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HighlightCoverage {
+    /// The unhighlighted spans, without leading or trailing whitespace.
+    pub unhighlighted_ranges: Vec<Range<usize>>,
+    /// The number of non-whitespace bytes in the unhighlighted spans.
+    pub unhighlighted_bytes: usize,
+    /// The number of non-whitespace bytes in the file.
+    pub total_bytes: usize,
+}
+
+impl HighlightCoverage {
+    #[must_use]
+    pub fn unhighlighted_percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.unhighlighted_bytes as f64 * 100.0 / self.total_bytes as f64
+        }
+    }
+}
+
+/// Highlight the source code, and find the spans that are only covered by the
+/// default style. Whitespace is never expected to be highlighted, so it is ignored.
+pub fn coverage(
+    loader: &Loader,
+    source: &[u8],
+    config: &HighlightConfiguration,
+    cancellation_flag: Option<&AtomicUsize>,
+) -> Result<HighlightCoverage> {
+    let mut highlighter = Highlighter::new();
+    let events = highlighter.highlight(config, source, cancellation_flag, |string| {
+        loader.highlight_config_for_injection_string(string)
+    })?;
+
+    let mut result = HighlightCoverage {
+        total_bytes: source.iter().filter(|b| !b.is_ascii_whitespace()).count(),
+        ..Default::default()
+    };
+    let mut depth = 0;
+    for event in events {
+        match event? {
+            HighlightEvent::HighlightStart(_) => depth += 1,
+            HighlightEvent::HighlightEnd => depth -= 1,
+            HighlightEvent::Source { mut start, mut end } => {
+                if depth > 0 {
+                    continue;
+                }
+                while start < end && source[start].is_ascii_whitespace() {
+                    start += 1;
+                }
+                while start < end && source[end - 1].is_ascii_whitespace() {
+                    end -= 1;
+                }
+                if start == end {
+                    continue;
+                }
+                result.unhighlighted_bytes += source[start..end]
+                    .iter()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .count();
+                // The highlighter can split a span between events, e.g. at injections.
+                match result.unhighlighted_ranges.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => result.unhighlighted_ranges.push(start..end),
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The capture names in the highlight queries that don't match any of the theme's
+/// highlight names, and so never produce a highlight. Private captures, and the
+/// captures used for locals and injections, are skipped.
+#[must_use]
+pub fn names_missing_from_theme<'a>(
+    config: &'a HighlightConfiguration,
+    theme: &Theme,
+) -> Vec<&'a str> {
+    config
+        .names()
+        .iter()
+        .filter(|name| {
+            !(name.starts_with('_') || name.starts_with("local.") || name.starts_with("injection."))
+        })
+        .filter(|name| {
+            let parts = name.split('.').collect::<Vec<_>>();
+            !theme
+                .highlight_names
+                .iter()
+                .any(|theme_name| theme_name.split('.').all(|part| parts.contains(&part)))
+        })
+        .copied()
+        .collect()
+}
+
+/// The CSS class for a highlight name, with every character that isn't valid in
+/// a class name replaced by `-`, e.g. `hl-function-builtin` for `function.builtin`.
+#[must_use]
+pub fn css_class(highlight_name: &str) -> String {
+    let name = highlight_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    format!("hl-{name}")
+}
+
+/// A stylesheet with a rule for the class of each styled highlight name in the
+/// theme, for the HTML that `html` produces with `css_classes`.
+#[must_use]
+pub fn stylesheet(theme: &Theme) -> String {
+    let mut result = String::new();
+    for (name, style) in theme.highlight_names.iter().zip(&theme.styles) {
+        let declarations = style
+            .css
+            .as_deref()
+            .and_then(|css| css.strip_prefix("style='")?.strip_suffix('\''));
+        if let Some(declarations) = declarations.filter(|d| !d.is_empty()) {
+            writeln!(&mut result, ".{} {{ {declarations} }}", css_class(name)).unwrap();
+        }
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn html(
     loader: &Loader,
     theme: &Theme,
     source: &[u8],
     config: &HighlightConfiguration,
+    css_classes: bool,
+    start_line: usize,
     quiet: bool,
     print_time: bool,
     cancellation_flag: Option<&AtomicUsize>,
@@ -387,12 +624,26 @@ pub fn html(
         loader.highlight_config_for_injection_string(string)
     })?;
 
+    let class_attributes = if css_classes {
+        theme
+            .highlight_names
+            .iter()
+            .map(|name| format!("class='{}'", css_class(name)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     let mut renderer = HtmlRenderer::new();
-    renderer.render(events, source, &move |highlight| {
-        theme.styles[highlight.0]
-            .css
-            .as_ref()
-            .map_or_else(|| "".as_bytes(), |css_style| css_style.as_bytes())
+    renderer.render(events, source, &|highlight| {
+        if css_classes {
+            class_attributes[highlight.0].as_bytes()
+        } else {
+            theme.styles[highlight.0]
+                .css
+                .as_ref()
+                .map_or_else(|| "".as_bytes(), |css_style| css_style.as_bytes())
+        }
     })?;
 
     if !quiet {
@@ -401,7 +652,7 @@ pub fn html(
             writeln!(
                 &mut stdout,
                 "<tr><td class=line-number>{}</td><td class=line>{line}</td></tr>",
-                i + 1,
+                i + start_line,
             )?;
         }
 
@@ -455,4 +706,42 @@ mod tests {
             env::remove_var("COLORTERM");
         }
     }
+
+    #[test]
+    fn test_css_class() {
+        assert_eq!(css_class("keyword"), "hl-keyword");
+        assert_eq!(css_class("function.builtin"), "hl-function-builtin");
+        assert_eq!(css_class("punctuation bracket"), "hl-punctuation-bracket");
+    }
+
+    #[test]
+    fn test_stylesheet() {
+        let theme: Theme = serde_json::from_value(json!({
+            "keyword": {"color": 56, "bold": true},
+            "comment": {"italic": true},
+            "function.builtin": "#26A69A",
+            "variable": {},
+        }))
+        .unwrap();
+        let stylesheet = stylesheet(&theme);
+        let mut rules = stylesheet.lines().collect::<Vec<_>>();
+        rules.sort_unstable();
+        assert_eq!(
+            rules,
+            [
+                ".hl-comment { font-style: italic; }",
+                ".hl-function-builtin { color: #26a69a }",
+                ".hl-keyword { font-weight: bold;color: #5f00d7 }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncate_line() {
+        assert_eq!(truncate_line("abc", 5), "abc");
+        assert_eq!(truncate_line("abcde", 5), "abcde");
+        assert_eq!(truncate_line("abcdef", 5), "abcd…");
+        assert_eq!(truncate_line("héllo wörld", 4), "hél…");
+        assert_eq!(truncate_line("abc", 0), "");
+    }
 }
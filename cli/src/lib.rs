@@ -1,12 +1,17 @@
 #![doc = include_str!("../README.md")]
 
+pub mod freshness;
 pub mod generate;
 pub mod highlight;
 pub mod logger;
+pub mod node_types;
 pub mod parse;
 pub mod playground;
 pub mod query;
 pub mod query_testing;
+pub mod repl;
+pub mod saved_tree;
+pub mod symbols;
 pub mod tags;
 pub mod test;
 pub mod test_highlight;
@@ -1,22 +1,24 @@
 use anstyle::{AnsiColor, Color, Style};
 use anyhow::{anyhow, Context, Result};
 use clap::{crate_authors, Args, Command, FromArgMatches as _, Subcommand};
-use glob::glob;
 use regex::Regex;
 use std::collections::HashSet;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::{env, fs, u64};
-use tree_sitter::{ffi, Parser, Point};
+use tree_sitter::{Parser, Point};
 use tree_sitter_cli::test::TestOptions;
 use tree_sitter_cli::{
-    generate, highlight, logger,
-    parse::{self, ParseFileOptions, ParseOutput},
-    playground, query, tags, test, test_highlight, test_tags, util, wasm,
+    freshness, generate, highlight, logger, node_types,
+    parse::{self, Encoding, ParseFileOptions, ParseOutput},
+    playground, query, repl, saved_tree, symbols, tags, test, test_highlight, test_tags, util,
+    wasm,
 };
 use tree_sitter_config::Config;
 use tree_sitter_highlight::Highlighter;
 use tree_sitter_loader as loader;
 use tree_sitter_tags::TagsContext;
+use walkdir::WalkDir;
 
 const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
 const BUILD_SHA: Option<&'static str> = option_env!("BUILD_SHA");
@@ -26,6 +28,7 @@ const DEFAULT_GENERATE_ABI_VERSION: usize = 14;
 #[command(about="Generates and tests parsers", author=crate_authors!("\n"), styles=get_styles())]
 enum Commands {
     InitConfig(InitConfig),
+    Config(ShowConfig),
     Generate(Generate),
     Build(Build),
     Parse(Parse),
@@ -34,12 +37,34 @@ enum Commands {
     Highlight(Highlight),
     Tags(Tags),
     Playground(Playground),
+    Repl(Repl),
     DumpLanguages(DumpLanguages),
+    NodeTypes(NodeTypes),
+    ValidateNodeTypes(ValidateNodeTypes),
+    CheckGenerated(CheckGenerated),
+    CheckFreshness(CheckFreshness),
+    DumpSymbols(DumpSymbols),
+    Deps(Deps),
 }
 
 #[derive(Args)]
 #[command(about = "Generate a default config file")]
-struct InitConfig;
+struct InitConfig {
+    #[arg(
+        long,
+        help = "Add any missing default settings to the existing config file instead of failing"
+    )]
+    pub merge: bool,
+}
+
+#[derive(Args)]
+#[command(about = "Print the location and the effective contents of the config file")]
+struct ShowConfig {
+    #[arg(long, help = "Only print the path to the config file")]
+    pub path: bool,
+    #[arg(long, help = "The path to an alternative config.json file")]
+    pub config_path: Option<PathBuf>,
+}
 
 #[derive(Args)]
 #[command(about = "Generate a parser", alias = "gen", alias = "g")]
@@ -62,6 +87,30 @@ struct Generate {
     pub abi_version: Option<String>,
     #[arg(long, help = "Don't generate language bindings")]
     pub no_bindings: bool,
+    #[arg(
+        long,
+        short,
+        value_name = "DIRECTORY",
+        conflicts_with = "build",
+        help = "The directory to write the parser and its headers to, instead of `src`"
+    )]
+    pub output_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The directory through which `parser.c` includes `parser.h`, instead of `tree_sitter`"
+    )]
+    pub header_prefix: Option<String>,
+    #[arg(
+        long,
+        help = "Also write `symbols.h` next to `parser.c`, with the ids of the grammar's tokens for use in an external scanner"
+    )]
+    pub emit_symbols_header: bool,
+    #[arg(
+        long,
+        help = "Only write `parser.c` and the header it needs, without grammar.json, node-types.json or bindings"
+    )]
+    pub minimal: bool,
     #[arg(
         long,
         short = 'b',
@@ -73,14 +122,75 @@ struct Generate {
     #[arg(
         long,
         value_name = "PATH",
-        help = "The path to the directory containing the parser library"
+        help = "The path to the directory containing the parser library. Can be repeated, in which case the first directory that has a library is used, and new libraries are compiled into the first one"
     )]
-    pub libdir: Option<String>,
+    pub libdir: Vec<PathBuf>,
     #[arg(
         long,
         help = "Produce a report of the states for the given rule, use `-` to report every rule"
     )]
     pub report_states_for_rule: Option<String>,
+    #[arg(
+        long,
+        help = "Report each conflict that was resolved by precedence or associativity, and how"
+    )]
+    pub precedence_report: bool,
+    #[arg(
+        long,
+        help = "Print the shift, reduce and goto actions of each parse state"
+    )]
+    pub dump_parse_table: bool,
+    #[arg(
+        long,
+        value_name = "STATE",
+        requires = "dump_parse_table",
+        help = "Only print the actions of this parse state"
+    )]
+    pub state: Option<usize>,
+    #[arg(
+        long,
+        help = "Warn when distinct rules produce nodes of the same kind, e.g. through aliases"
+    )]
+    pub warn_kind_merges: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the sizes of the generated tables to this file as JSON"
+    )]
+    pub stats_json: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "When generation fails, print the error as a JSON object with its `kind` and `message`, instead of as text"
+    )]
+    pub error_json: bool,
+    #[arg(
+        long,
+        value_name = "RULE",
+        help = "Inline this rule, in addition to the grammar's `inline` list"
+    )]
+    pub inline: Vec<String>,
+    #[arg(
+        long,
+        value_name = "RULE",
+        help = "Don't inline this rule, even if it is in the grammar's `inline` list"
+    )]
+    pub no_inline: Vec<String>,
+    #[arg(
+        long,
+        help = "Start parser.c with a comment holding a hash of the grammar, the options that affect parser.c and the CLI version, for `check-generated`"
+    )]
+    pub stamp: bool,
+    #[arg(
+        long,
+        help = "Write node-types.json without indentation or line breaks, to keep it small"
+    )]
+    pub compact_node_types: bool,
+    #[arg(
+        long,
+        conflicts_with = "build",
+        help = "Generate the parser, but only print the paths and sizes of the files that would be written"
+    )]
+    pub dry_run: bool,
 
     #[arg(
         long,
@@ -115,6 +225,37 @@ struct Build {
     pub internal_build: bool,
 }
 
+// The flags that filter the paths that `parse`, `query`, `highlight` and `tags`
+// collect from their arguments.
+#[derive(Args)]
+struct PathFilters {
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Skip the collected paths that match the glob, which can be repeated"
+    )]
+    pub exclude: Vec<String>,
+    #[arg(
+        long,
+        overrides_with = "no_follow_symlinks",
+        help = "Follow symbolic links when expanding directories and globs"
+    )]
+    pub follow_symlinks: bool,
+    #[arg(
+        long,
+        overrides_with = "follow_symlinks",
+        help = "Skip symbolic links when expanding directories and globs (the default)"
+    )]
+    pub no_follow_symlinks: bool,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 0,
+        help = "Skip input files larger than this many bytes (0 means no limit)"
+    )]
+    pub max_file_size: u64,
+}
+
 #[derive(Args)]
 #[command(about = "Parse files", alias = "p")]
 struct Parse {
@@ -123,13 +264,22 @@ struct Parse {
         help = "The path to a file with paths to source file(s)"
     )]
     pub paths_file: Option<String>,
-    #[arg(num_args=1.., help = "The source file(s) to use")]
+    #[arg(num_args = 1.., help = "The source file(s) to use, or `-` to read from stdin (with --scope)")]
     pub paths: Option<Vec<String>>,
+    #[command(flatten)]
+    pub path_filters: PathFilters,
     #[arg(
         long,
         help = "Select a language by the scope instead of a file extension"
     )]
     pub scope: Option<String>,
+    #[arg(
+        long,
+        value_name = "GLOB=SCOPE",
+        conflicts_with = "scope",
+        help = "Select a language by the scope for the files that match the glob, which can be repeated"
+    )]
+    pub scope_for: Vec<String>,
     #[arg(long, short = 'd', help = "Show parsing debug log")]
     pub debug: bool,
     #[arg(long, short = '0', help = "Compile a parser in debug mode")]
@@ -145,30 +295,233 @@ struct Parse {
         help = "Compile parsers to wasm instead of native dynamic libraries"
     )]
     pub wasm: bool,
-    #[arg(long = "dot", help = "Output the parse data with graphviz dot")]
-    pub output_dot: bool,
+    #[arg(
+        long = "dot",
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        help = "Output the syntax tree as a graphviz dot graph, to `--dot=PATH` or to stdout"
+    )]
+    pub output_dot: Option<Option<PathBuf>>,
     #[arg(
         long = "xml",
         short = 'x',
         help = "Output the parse data in XML format"
     )]
     pub output_xml: bool,
+    #[arg(
+        long,
+        help = "Output the source code with the kinds of the nodes starting on each row"
+    )]
+    pub print_source_with_tree: bool,
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        num_args = 0..=1,
+        require_equals = true,
+        requires = "print_source_with_tree",
+        help = "Cut off source lines that are wider than `--max-line-width=WIDTH`, ending them with `…`. Without a width, the width of the terminal is used, from the `COLUMNS` environment variable or 80"
+    )]
+    pub max_line_width: Option<Option<usize>>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Print N lines of the source before and after each syntax error"
+    )]
+    pub context: Option<usize>,
+    #[arg(
+        long,
+        value_name = "START:END",
+        help = "Only parse this range of byte offsets in each file. The nodes keep their positions in the whole file. With --encoding latin1, the offsets are in the file as transcoded to UTF-8"
+    )]
+    pub byte_range: Option<String>,
+    #[arg(
+        long,
+        value_name = "START_ROW:START_COLUMN-END_ROW:END_COLUMN",
+        conflicts_with = "byte_range",
+        help = "Only parse this range of positions in each file, such as the text of a language embedded in another one. Can be repeated, in order. The nodes keep their positions in the whole file. With --encoding latin1, the columns are in the file as transcoded to UTF-8"
+    )]
+    pub range: Vec<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Report up to N syntax errors in each file instead of only the first one"
+    )]
+    pub max_errors: Option<usize>,
+    #[arg(
+        long,
+        conflicts_with = "max_errors",
+        help = "Report every syntax error in each file instead of only the first one, including the errors nested in other errors"
+    )]
+    pub all_errors: bool,
+    #[arg(
+        long = "sexp-canonical",
+        help = "Output the syntax tree as a single-line S-expression without positions"
+    )]
+    pub output_sexp_canonical: bool,
+    #[arg(
+        long,
+        help = "Mark extra nodes, such as comments, with `extra` in the S-expression output"
+    )]
+    pub mark_extras: bool,
+    #[arg(
+        long,
+        help = "Include anonymous nodes, such as punctuation, in the S-expression output, with their kinds quoted"
+    )]
+    pub anonymous_nodes: bool,
+    #[arg(
+        long,
+        help = "Print the numeric ID of each field after its name in the S-expression output, like `name#2:`"
+    )]
+    pub field_ids: bool,
+    #[arg(
+        long,
+        help = "Leave out the positions of the nodes in the S-expression, XML and dot output, which also keeps them out of --diff"
+    )]
+    pub no_positions: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "load_tree",
+        help = "Print a unified diff between the S-expression output in this file, such as from an earlier run, and the file's syntax tree, instead of the tree"
+    )]
+    pub diff: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save the syntax tree to this file, so it can be printed again with --load-tree"
+    )]
+    pub save_tree: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["paths", "paths_file", "save_tree"],
+        help = "Print a syntax tree saved with --save-tree, instead of parsing any files"
+    )]
+    pub load_tree: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print a stable hash of each syntax tree, for detecting changes in parse results"
+    )]
+    pub checksum: bool,
+    #[arg(
+        long,
+        help = "Print the checksums as a JSON object that maps each path to its checksum"
+    )]
+    pub checksum_json: bool,
+    #[arg(
+        long,
+        help = "Output the kinds of nodes found under each field of each named node, across the whole file"
+    )]
+    pub fields: bool,
     #[arg(long, short, help = "Show parsing statistic")]
     pub stat: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "20",
+        requires = "stat",
+        help = "With --stat, also print the N most frequent kinds of named nodes in all of the files (20 by default)"
+    )]
+    pub stat_kinds: Option<usize>,
+    #[arg(
+        long,
+        help = "Stop at the first file that has a parse error, instead of parsing every file"
+    )]
+    pub fail_fast: bool,
+    #[arg(
+        long,
+        help = "Print the number of nodes in each syntax tree and an estimate of its memory use"
+    )]
+    pub measure_memory: bool,
     #[arg(long, help = "Interrupt the parsing process by timeout (µs)")]
     pub timeout: Option<u64>,
     #[arg(long, short, help = "Measure execution time")]
     pub time: bool,
     #[arg(long, short, help = "Suppress main output")]
     pub quiet: bool,
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "quiet",
+            "time",
+            "stat",
+            "measure_memory",
+            "output_dot",
+            "output_xml",
+            "print_source_with_tree",
+            "ancestors",
+            "output_sexp_canonical",
+            "checksum",
+            "checksum_json",
+            "fields",
+        ],
+        help = "Print nothing but one line for each file that has a parse error, and exit with a non-zero status if any does"
+    )]
+    pub check: bool,
     #[arg(
         long,
         num_args = 1..,
-        help = "Apply edits in the format: \"row, col delcount insert_text\""
+        help = "Apply edits in the format: \"row,col|byte delcount|row,col insert_text\""
     )]
     pub edits: Option<Vec<String>>,
-    #[arg(long, help = "The encoding of the input files")]
+    #[arg(
+        long,
+        help = "Print the ranges changed by each edit as a JSON array, one line per edit"
+    )]
+    pub dump_changed_ranges: bool,
+    #[arg(
+        long,
+        requires = "edits",
+        help = "After each edit, print how many nodes were reused from the previous tree"
+    )]
+    pub show_reused_nodes: bool,
+    #[arg(
+        long,
+        requires = "edits",
+        help = "After each edit, check that the incrementally reparsed tree matches a parse from scratch"
+    )]
+    pub validate_incremental: bool,
+    #[arg(
+        long,
+        value_name = "ROW:COLUMN",
+        help = "Only output the smallest named node containing this position, with its subtree"
+    )]
+    pub extract: Vec<String>,
+    #[arg(
+        long,
+        value_name = "KIND",
+        conflicts_with = "extract",
+        help = "Only output the first node of this kind, with its subtree. The file is still parsed from the grammar's start rule"
+    )]
+    pub root_kind: Option<String>,
+    #[arg(
+        long,
+        value_name = "ROW:COLUMN",
+        conflicts_with_all = [
+            "output_dot",
+            "output_xml",
+            "print_source_with_tree",
+            "output_sexp_canonical",
+            "checksum",
+            "checksum_json",
+            "fields",
+        ],
+        help = "Output the nodes containing this position, from the root down to the smallest"
+    )]
+    pub ancestors: Vec<String>,
+    #[arg(
+        long,
+        help = "The encoding of the input files (utf8, utf16-le, utf16-be, latin1), overriding the byte order mark. Latin-1 is never detected automatically"
+    )]
     pub encoding: Option<String>,
+    #[arg(
+        long,
+        help = "Print the encoding of each file and whether it has a byte order mark, before parsing it"
+    )]
+    pub encoding_report: bool,
     #[arg(
         long,
         help = "Open `log.html` in the default browser, if `--debug-graph` is supplied"
@@ -205,6 +558,24 @@ struct Test {
         help = "Update all syntax trees in corpus files with current parser output"
     )]
     pub update: bool,
+    #[arg(
+        long = "corpus",
+        value_name = "DIR",
+        help = "Run the corpus tests in this directory instead of `test/corpus` (can be repeated)"
+    )]
+    pub corpus_dirs: Vec<PathBuf>,
+    #[arg(
+        long = "test-ext",
+        value_name = "EXTENSION",
+        help = "Only treat files with this extension as corpus files (can be repeated). By default, all files are"
+    )]
+    pub test_extensions: Vec<String>,
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Compare the expected trees with the tree's S-expression (`tree`, the default), or with the output of `parse` without positions (`s-expression`)"
+    )]
+    pub compare_output: Option<String>,
     #[arg(long, short = 'd', help = "Show parsing debug log")]
     pub debug: bool,
     #[arg(long, short = '0', help = "Compile a parser in debug mode")]
@@ -232,7 +603,11 @@ struct Test {
 #[derive(Args)]
 #[command(about = "Search files using a syntax tree query", alias = "q")]
 struct Query {
-    #[arg(help = "Path to a file with queries", index = 1, required = true)]
+    #[arg(
+        help = "Path to a file with queries, or `-` to read them from stdin",
+        index = 1,
+        required = true
+    )]
     query_path: String,
     #[arg(long, short, help = "Measure execution time")]
     pub time: bool,
@@ -245,11 +620,14 @@ struct Query {
     pub paths_file: Option<String>,
     #[arg(index = 2, num_args=1.., help = "The source file(s) to use")]
     pub paths: Option<Vec<String>>,
+    #[command(flatten)]
+    pub path_filters: PathFilters,
     #[arg(
         long,
-        help = "The range of byte offsets in which the query will be executed"
+        value_name = "START:END",
+        help = "The range of byte offsets in which the query will be executed. When it is repeated, only the matches with a capture in one of the ranges are printed"
     )]
-    pub byte_range: Option<String>,
+    pub byte_range: Vec<String>,
     #[arg(long, help = "The range of rows in which the query will be executed")]
     pub row_range: Option<String>,
     #[arg(
@@ -257,10 +635,82 @@ struct Query {
         help = "Select a language by the scope instead of a file extension"
     )]
     pub scope: Option<String>,
+    #[arg(
+        long,
+        value_name = "GLOB=SCOPE",
+        conflicts_with = "scope",
+        help = "Select a language by the scope for the files that match the glob, which can be repeated"
+    )]
+    pub scope_for: Vec<String>,
     #[arg(long, short, help = "Order by captures instead of matches")]
     pub captures: bool,
+    #[arg(
+        long,
+        value_name = "ORDER",
+        conflicts_with = "captures",
+        help = "Print the captures of each file sorted by their position, name, or pattern"
+    )]
+    pub sort: Option<String>,
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Print the captures as `text` (the default), or as an aligned `table` without colors"
+    )]
+    pub format: Option<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=65536),
+        help = "The maximum number of in-progress matches, past which the oldest matches are dropped"
+    )]
+    pub match_limit: Option<u32>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only start matching patterns at nodes up to this depth below the root"
+    )]
+    pub max_start_depth: Option<u32>,
+    #[arg(long, short = 'd', help = "Show the query cursor's limits")]
+    pub debug: bool,
     #[arg(long, help = "Whether to run query tests or not")]
     pub test: bool,
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Only print the captures whose text matches this regex"
+    )]
+    pub grep: Option<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        value_delimiter = ',',
+        help = "Only run this pattern of the query, by its index. Can be repeated or given as a comma-separated list"
+    )]
+    pub pattern: Vec<usize>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Remove this capture from all of the query's patterns. Can be repeated"
+    )]
+    pub disable_capture: Vec<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop printing captures after this many in each file"
+    )]
+    pub limit: Option<usize>,
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "format",
+        help = "Print N lines of the source before and after each printed capture"
+    )]
+    pub context: Option<usize>,
+    #[arg(
+        long,
+        help = "After all of the files, print the kinds of the nodes that each capture matched, and how often"
+    )]
+    pub capture_kinds: bool,
     #[arg(long, help = "The path to an alternative config.json file")]
     pub config_path: Option<PathBuf>,
 }
@@ -272,9 +722,35 @@ struct Highlight {
     pub html: bool,
     #[arg(
         long,
-        help = "Check that highlighting captures conform strictly to standards"
+        requires = "html",
+        help = "In the HTML, mark highlights with `hl-` classes instead of inline styles"
+    )]
+    pub css_classes: bool,
+    #[arg(
+        long,
+        value_name = "LINE",
+        default_value_t = 1,
+        requires = "html",
+        help = "The number of the first line in the HTML's line number gutter"
+    )]
+    pub start_line: usize,
+    #[arg(
+        long,
+        help = "Print a stylesheet for the classes of --css-classes, derived from the theme"
+    )]
+    pub emit_css: bool,
+    #[arg(
+        long,
+        help = "Instead of highlighting, check that highlighting captures conform strictly to standards, are all in the theme, and cover the whole file"
     )]
     pub check: bool,
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        requires = "check",
+        help = "With --check, allow up to this percentage of the non-whitespace text to be unhighlighted, instead of none"
+    )]
+    pub max_unhighlighted: Option<f64>,
     #[arg(long, help = "The path to a file with captures")]
     pub captures_path: Option<String>,
     #[arg(long, num_args = 1.., help = "The paths to files with queries")]
@@ -293,8 +769,10 @@ struct Highlight {
         help = "The path to a file with paths to source file(s)"
     )]
     pub paths_file: Option<String>,
-    #[arg(num_args = 1.., help = "The source file(s) to use")]
+    #[arg(num_args = 1.., help = "The source file(s) to use, or `-` to read from stdin (with --scope)")]
     pub paths: Option<Vec<String>>,
+    #[command(flatten)]
+    pub path_filters: PathFilters,
     #[arg(long, help = "The path to an alternative config.json file")]
     pub config_path: Option<PathBuf>,
 }
@@ -311,13 +789,26 @@ struct Tags {
     pub time: bool,
     #[arg(long, short, help = "Suppress main output")]
     pub quiet: bool,
+    #[arg(
+        long,
+        value_name = "ORDER",
+        help = "Print the tags of each file sorted by their name or position"
+    )]
+    pub sort: Option<String>,
+    #[arg(
+        long,
+        help = "Drop the tags with the same name, kind, and range as an earlier tag"
+    )]
+    pub dedup: bool,
     #[arg(
         long = "paths",
         help = "The path to a file with paths to source file(s)"
     )]
     pub paths_file: Option<String>,
-    #[arg(num_args = 1.., help = "The source file(s) to use")]
+    #[arg(num_args = 1.., help = "The source file(s) to use, or `-` to read from stdin (with --scope)")]
     pub paths: Option<Vec<String>>,
+    #[command(flatten)]
+    pub path_filters: PathFilters,
     #[arg(long, help = "The path to an alternative config.json file")]
     pub config_path: Option<PathBuf>,
 }
@@ -339,6 +830,24 @@ struct Playground {
     pub grammar_path: Option<String>,
 }
 
+#[derive(Args)]
+#[command(about = "Parse snippets read from stdin, one line at a time")]
+struct Repl {
+    #[arg(
+        long,
+        help = "Select a language by the scope instead of using the grammar in the current directory"
+    )]
+    pub scope: Option<String>,
+    #[arg(
+        long,
+        short,
+        help = "Append each line to the previous text and reparse it incrementally"
+    )]
+    pub incremental: bool,
+    #[arg(long, help = "The path to an alternative config.json file")]
+    pub config_path: Option<PathBuf>,
+}
+
 #[derive(Args)]
 #[command(about = "Print info about all known language parsers", alias = "langs")]
 struct DumpLanguages {
@@ -346,6 +855,84 @@ struct DumpLanguages {
     pub config_path: Option<PathBuf>,
 }
 
+#[derive(Args)]
+#[command(about = "Print the node types of a language")]
+struct NodeTypes {
+    #[arg(index = 1, help = "The name or scope of the language")]
+    pub language: String,
+    #[arg(index = 2, help = "Only print the node type with this kind")]
+    pub kind: Option<String>,
+    #[arg(long, help = "Print the raw entries from node-types.json")]
+    pub json: bool,
+    #[arg(long, help = "The path to an alternative config.json file")]
+    pub config_path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[command(about = "Check that node-types.json matches the compiled language")]
+struct ValidateNodeTypes {
+    #[arg(index = 1, help = "The path to the grammar directory")]
+    pub grammar_path: Option<String>,
+}
+
+#[derive(Args)]
+#[command(about = "Check that parser.c was generated with --stamp from the current grammar")]
+struct CheckGenerated {
+    #[arg(index = 1, help = "The path to the grammar directory")]
+    pub grammar_path: Option<String>,
+    #[arg(
+        long,
+        value_name = "EXECUTABLE",
+        env = "TREE_SITTER_JS_RUNTIME",
+        help = "The path to the JavaScript runtime to use for loading grammar.js"
+    )]
+    pub js_runtime: Option<String>,
+}
+
+#[derive(Args)]
+#[command(
+    about = "Check that grammar.json, parser.c and the compiled library are newer than their sources"
+)]
+struct CheckFreshness {
+    #[arg(index = 1, help = "The path to the grammar directory")]
+    pub grammar_path: Option<String>,
+}
+
+#[derive(Args)]
+#[command(about = "Print the packages that a grammar.js requires while it loads, as JSON")]
+struct Deps {
+    #[arg(
+        index = 1,
+        help = "The path to the grammar.js file, or to the grammar directory"
+    )]
+    pub grammar_path: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "EXECUTABLE",
+        env = "TREE_SITTER_JS_RUNTIME",
+        help = "The path to the JavaScript runtime to use for loading grammar.js"
+    )]
+    pub js_runtime: Option<String>,
+}
+
+#[derive(Args)]
+#[command(about = "Print the symbols of a parser and their C constants in parser.c")]
+struct DumpSymbols {
+    #[arg(index = 1, help = "The path to the grammar directory")]
+    pub grammar_path: Option<String>,
+    #[arg(
+        long,
+        help = "Print a JSON object mapping each symbol id to its name and C constant"
+    )]
+    pub map: bool,
+    #[arg(
+        long,
+        conflicts_with = "map",
+        help = "Print the extra tokens and the word token of the language as JSON, as listed in `src/grammar.json`"
+    )]
+    pub list_extras: bool,
+}
+
 fn main() {
     let result = run();
     if let Err(err) = &result {
@@ -392,10 +979,35 @@ fn run() -> Result<()> {
     let mut loader = loader::Loader::new()?;
 
     match command {
-        Commands::InitConfig(_) => {
+        Commands::InitConfig(init_config_options) => {
             if let Ok(Some(config_path)) = Config::find_config_file() {
+                if init_config_options.merge {
+                    let mut config = Config::load(Some(config_path))?;
+                    let mut added = config.add_missing(tree_sitter_loader::Config::initial())?;
+                    added.extend(
+                        config.add_missing(tree_sitter_cli::highlight::ThemeConfig::default())?,
+                    );
+                    if added.is_empty() {
+                        println!(
+                            "{} already has all of the default settings",
+                            config.location.display()
+                        );
+                    } else {
+                        config.save()?;
+                        println!(
+                            "Added {} to {}",
+                            added
+                                .iter()
+                                .map(|key| format!("`{key}`"))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            config.location.display()
+                        );
+                    }
+                    return Ok(());
+                }
                 return Err(anyhow!(
-                    "Remove your existing config file first: {}",
+                    "Remove your existing config file first, or pass --merge to add the missing default settings to it: {}",
                     config_path.to_string_lossy()
                 ));
             }
@@ -409,6 +1021,35 @@ fn run() -> Result<()> {
             );
         }
 
+        Commands::Config(config_options) => {
+            let config = Config::load(config_options.config_path)?;
+            if config_options.path {
+                println!("{}", config.location.display());
+                return Ok(());
+            }
+            if config.location.is_file() {
+                println!("Config file: {}", config.location.display());
+            } else {
+                println!(
+                    "Config file: {} (not found, using the defaults)",
+                    config.location.display()
+                );
+            }
+
+            // Print the settings as they are resolved by the commands that use them,
+            // with the defaults filled in and paths expanded.
+            let mut effective_config = Config {
+                location: config.location.clone(),
+                config: serde_json::json!({}),
+            };
+            effective_config.add(config.get::<tree_sitter_loader::Config>()?)?;
+            effective_config.add(config.get::<tree_sitter_cli::highlight::ThemeConfig>()?)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&effective_config.config)?
+            );
+        }
+
         Commands::Generate(generate_options) => {
             if generate_options.log {
                 logger::init();
@@ -423,17 +1064,44 @@ fn run() -> Result<()> {
                     }
                 },
             );
-            generate::generate_parser_in_directory(
-                &current_dir,
-                generate_options.grammar_path.as_deref(),
+            let opts = generate::GenerateOptions {
+                grammar_path: generate_options.grammar_path.as_deref(),
                 abi_version,
-                !generate_options.no_bindings,
-                generate_options.report_states_for_rule.as_deref(),
-                generate_options.js_runtime.as_deref(),
-            )?;
+                generate_bindings: !generate_options.no_bindings,
+                report_symbol_name: generate_options.report_states_for_rule.as_deref(),
+                js_runtime: generate_options.js_runtime.as_deref(),
+                warn_kind_merges: generate_options.warn_kind_merges,
+                stats_path: generate_options.stats_json.as_deref(),
+                inline: &generate_options.inline,
+                no_inline: &generate_options.no_inline,
+                report_precedence: generate_options.precedence_report,
+                dump_parse_table: generate_options
+                    .dump_parse_table
+                    .then_some(generate_options.state),
+                out_path: generate_options.output_dir.as_deref(),
+                header_prefix: generate_options.header_prefix.as_deref(),
+                emit_symbols_header: generate_options.emit_symbols_header,
+                compact_node_types: generate_options.compact_node_types,
+                stamp: generate_options.stamp,
+                minimal: generate_options.minimal,
+                dry_run: generate_options.dry_run,
+            };
+            let result = generate::generate_parser_in_directory(&current_dir, &opts);
+            if generate_options.error_json {
+                if let Err(error) = result {
+                    let error = generate::GenerateError::from(error);
+                    println!("{}", serde_json::to_string_pretty(&error.to_json())?);
+                    return Err(anyhow!(""));
+                }
+            }
+            result?;
             if generate_options.build {
-                if let Some(path) = generate_options.libdir {
-                    loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
+                let mut libdirs = generate_options.libdir.into_iter();
+                if let Some(path) = libdirs.next() {
+                    loader = loader::Loader::with_parser_lib_path(path);
+                    for path in libdirs {
+                        loader.add_parser_lib_path(path);
+                    }
                 }
                 loader.use_debug_build(generate_options.debug_build);
                 loader.languages_at_path(&current_dir)?;
@@ -499,26 +1167,91 @@ fn run() -> Result<()> {
         }
 
         Commands::Parse(parse_options) => {
+            let sexp_flags = parse::SexpFlags {
+                mark_extras: parse_options.mark_extras,
+                anonymous_nodes: parse_options.anonymous_nodes,
+                field_ids: parse_options.field_ids,
+                hide_positions: parse_options.no_positions,
+            };
+            if let Some(path) = &parse_options.load_tree {
+                let tree = saved_tree::SavedTree::load(path)?;
+                tree.write_sexp(&mut std::io::stdout().lock(), sexp_flags)?;
+                return Ok(());
+            }
+
             let config = Config::load(parse_options.config_path)?;
-            let output = if parse_options.output_dot {
+            let output = if parse_options.check {
+                ParseOutput::Check
+            } else if parse_options.output_dot.is_some() {
                 ParseOutput::Dot
             } else if parse_options.output_xml {
                 ParseOutput::Xml
+            } else if parse_options.print_source_with_tree {
+                ParseOutput::SourceWithTree
+            } else if !parse_options.ancestors.is_empty() {
+                ParseOutput::Ancestors
+            } else if parse_options.output_sexp_canonical {
+                ParseOutput::CanonicalSexp
+            } else if parse_options.checksum || parse_options.checksum_json {
+                ParseOutput::Checksum
+            } else if parse_options.fields {
+                ParseOutput::Fields
             } else if parse_options.quiet {
                 ParseOutput::Quiet
             } else {
                 ParseOutput::Normal
             };
 
-            let encoding = if let Some(encoding) = parse_options.encoding {
-                match encoding.as_str() {
-                    "utf16" => Some(ffi::TSInputEncodingUTF16),
-                    "utf8" => Some(ffi::TSInputEncodingUTF8),
-                    _ => return Err(anyhow!("Invalid encoding. Expected one of: utf8, utf16")),
-                }
+            let byte_range = parse_options
+                .byte_range
+                .as_deref()
+                .map(parse::parse_byte_range_flag)
+                .transpose()?;
+            let ranges = parse_options
+                .range
+                .iter()
+                .map(|range| parse::parse_range_flag(range))
+                .collect::<Result<Vec<_>>>()?;
+            if ranges.windows(2).any(|pair| pair[0].end > pair[1].start) {
+                return Err(anyhow!(
+                    "The ranges given with --range must be in order and must not overlap"
+                ));
+            }
+            let max_errors = if parse_options.all_errors {
+                usize::MAX
             } else {
-                None
+                parse_options.max_errors.unwrap_or(1)
             };
+            if max_errors == 0 {
+                return Err(anyhow!("--max-errors must be at least 1"));
+            }
+
+            let encoding = parse_options
+                .encoding
+                .as_deref()
+                .map(Encoding::from_name)
+                .transpose()?;
+
+            let extract = parse_options
+                .extract
+                .iter()
+                .map(|point| parse::parse_point_flag(point))
+                .collect::<Result<Vec<_>>>()?;
+            let ancestors = parse_options
+                .ancestors
+                .iter()
+                .map(|point| parse::parse_point_flag(point))
+                .collect::<Result<Vec<_>>>()?;
+            if (!extract.is_empty() || parse_options.root_kind.is_some())
+                && !matches!(
+                    output,
+                    ParseOutput::Normal | ParseOutput::CanonicalSexp | ParseOutput::Fields
+                )
+            {
+                return Err(anyhow!(
+                    "--extract and --root-kind can only be used with the default, --sexp-canonical or --fields output"
+                ));
+            }
 
             let time = parse_options.time;
             let edits = parse_options.edits.unwrap_or_default();
@@ -531,6 +1264,10 @@ fn run() -> Result<()> {
             }
 
             loader.use_debug_build(parse_options.debug_build);
+            loader.log_language_selection(parse_options.debug);
+            for mapping in &parse_options.scope_for {
+                loader.add_scope_override(mapping)?;
+            }
 
             #[cfg(feature = "wasm")]
             if parse_options.wasm {
@@ -543,25 +1280,107 @@ fn run() -> Result<()> {
 
             let timeout = parse_options.timeout.unwrap_or_default();
 
-            let paths = collect_paths(parse_options.paths_file.as_deref(), parse_options.paths)?;
+            let theme_config: tree_sitter_cli::highlight::ThemeConfig = config.get()?;
+            if parse_options.print_source_with_tree {
+                loader.configure_highlights(&theme_config.theme.highlight_names);
+            }
+            let loader_config = config.get()?;
+            loader.find_all_languages(&loader_config)?;
+            let paths = collect_paths(
+                parse_options.paths_file.as_deref(),
+                parse_options.paths,
+                &parse_options.path_filters,
+                true,
+                |path| {
+                    selects_language(
+                        &mut loader,
+                        path,
+                        &current_dir,
+                        parse_options.scope.as_deref(),
+                    )
+                },
+            )?;
+            if parse_options.save_tree.is_some() && paths.len() != 1 {
+                return Err(anyhow!(
+                    "--save-tree can only be used with a single input file"
+                ));
+            }
+            let old_sexp = match &parse_options.diff {
+                Some(_) if paths.len() != 1 || output != ParseOutput::Normal => {
+                    return Err(anyhow!(
+                        "--diff can only be used with a single input file and the default output"
+                    ));
+                }
+                Some(path) => Some(
+                    fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?,
+                ),
+                None => None,
+            };
 
             let max_path_length = paths.iter().map(|p| p.chars().count()).max().unwrap_or(0);
+            let max_line_width = parse_options.max_line_width.map(|width| {
+                width.unwrap_or_else(|| {
+                    env::var("COLUMNS")
+                        .ok()
+                        .and_then(|columns| columns.parse().ok())
+                        .unwrap_or(80)
+                })
+            });
             let mut has_error = false;
-            let loader_config = config.get()?;
-            loader.find_all_languages(&loader_config)?;
 
             let should_track_stats = parse_options.stat;
-            let mut stats = parse::Stats::default();
+            let mut stats = parse::Stats {
+                top_kind_count: parse_options.stat_kinds,
+                ..Default::default()
+            };
+            let mut checksums = serde_json::Map::new();
+            let mut dot_file = match &parse_options.output_dot {
+                Some(Some(path)) => Some(
+                    fs::File::create(path)
+                        .with_context(|| format!("Failed to create {}", path.display()))?,
+                ),
+                _ => None,
+            };
+            let mut total_memory = parse::TreeMemory::default();
+            let mut file_count = 0;
 
             for path in paths {
                 let path = Path::new(&path);
-
-                let language =
-                    loader.select_language(path, &current_dir, parse_options.scope.as_deref())?;
+                if path == Path::new(util::STDIN_PATH) && parse_options.scope.is_none() {
+                    return Err(util::stdin_requires_scope());
+                }
+                let language_path = util::language_detection_path(path);
+                let language_contents = util::language_detection_contents(path)?;
+
+                let language = loader.select_language_with_contents(
+                    &language_path,
+                    &current_dir,
+                    parse_options.scope.as_deref(),
+                    language_contents.as_deref(),
+                )?;
                 parser
                     .set_language(&language)
                     .context("incompatible language")?;
 
+                let highlight_config = if parse_options.print_source_with_tree {
+                    let language_config = match parse_options.scope.as_deref() {
+                        Some(scope) => loader.language_configuration_for_scope(scope)?,
+                        None => loader.language_configuration_for_file_name_with_contents(
+                            &language_path,
+                            language_contents.as_deref(),
+                        )?,
+                    };
+                    match language_config {
+                        Some((language, language_config)) => {
+                            language_config.highlight_config(language, None)?
+                        }
+                        None => None,
+                    }
+                } else {
+                    None
+                };
+
                 let opts = ParseFileOptions {
                     language: language.clone(),
                     path,
@@ -569,6 +1388,18 @@ fn run() -> Result<()> {
                         .iter()
                         .map(std::string::String::as_str)
                         .collect::<Vec<&str>>(),
+                    extract: &extract,
+                    root_kind: parse_options.root_kind.as_deref(),
+                    ancestors: &ancestors,
+                    dump_changed_ranges: parse_options.dump_changed_ranges,
+                    show_reused_nodes: parse_options.show_reused_nodes,
+                    validate_incremental: parse_options.validate_incremental,
+                    sexp_flags,
+                    diff_against: parse_options.diff.as_deref().zip(old_sexp.as_deref()),
+                    save_tree: parse_options.save_tree.as_deref(),
+                    measure_memory: parse_options.measure_memory,
+                    count_kinds: parse_options.stat_kinds.is_some(),
+                    find_deepest_path: should_track_stats,
                     max_path_length,
                     output,
                     print_time: time,
@@ -577,11 +1408,49 @@ fn run() -> Result<()> {
                     debug_graph: parse_options.debug_graph,
                     cancellation_flag: Some(&cancellation_flag),
                     encoding,
+                    encoding_report: parse_options.encoding_report,
                     open_log: parse_options.open_log,
+                    highlight: highlight_config.map(|config| (config, &theme_config.theme)),
+                    max_line_width,
+                    context: parse_options.context,
+                    max_errors,
+                    nested_errors: parse_options.all_errors,
+                    byte_range: byte_range.clone(),
+                    ranges: &ranges,
                 };
 
                 let parse_result = parse::parse_file_at_path(&mut parser, &opts)?;
 
+                if let Some(checksum) = parse_result.checksum {
+                    let checksum = format!("{checksum:016x}");
+                    if parse_options.checksum_json {
+                        checksums.insert(path.to_string_lossy().to_string(), checksum.into());
+                    } else {
+                        println!(
+                            "{:width$}\t{checksum}",
+                            path.to_string_lossy(),
+                            width = max_path_length
+                        );
+                    }
+                }
+
+                if let Some(graph) = &parse_result.dot_graph {
+                    match &mut dot_file {
+                        Some(file) => file.write_all(graph.as_bytes())?,
+                        None => print!("{graph}"),
+                    }
+                }
+
+                if let Some(memory) = parse_result.memory {
+                    println!(
+                        "{:width$}\t{memory}",
+                        path.to_string_lossy(),
+                        width = max_path_length
+                    );
+                    total_memory += memory;
+                    file_count += 1;
+                }
+
                 if should_track_stats {
                     stats.total_parses += 1;
                     if parse_result.successful {
@@ -591,9 +1460,32 @@ fn run() -> Result<()> {
                         stats.total_bytes += parse_result.bytes;
                         stats.total_duration += duration;
                     }
+                    for (kind, count) in parse_result.kind_counts.into_iter().flatten() {
+                        *stats.kind_counts.entry(kind).or_default() += count;
+                    }
+                    if let Some(kinds) = parse_result.deepest_path {
+                        if stats
+                            .deepest_path
+                            .as_ref()
+                            .map_or(true, |(_, deepest)| kinds.len() > deepest.len())
+                        {
+                            stats.deepest_path = Some((path.to_string_lossy().to_string(), kinds));
+                        }
+                    }
+                }
+
+                has_error |= !parse_result.successful || parse_result.sexp_differs;
+                if has_error && parse_options.fail_fast {
+                    break;
                 }
+            }
 
-                has_error |= !parse_result.successful;
+            if parse_options.checksum_json {
+                println!("{}", serde_json::to_string_pretty(&checksums)?);
+            }
+
+            if file_count > 1 {
+                println!("Total for {file_count} files: {total_memory}");
             }
 
             if should_track_stats {
@@ -634,19 +1526,43 @@ fn run() -> Result<()> {
 
             let test_dir = current_dir.join("test");
 
-            // Run the corpus tests. Look for them in `test/corpus`.
-            let test_corpus_dir = test_dir.join("corpus");
-            if test_corpus_dir.is_dir() {
+            let compare_output = test_options
+                .compare_output
+                .as_deref()
+                .map(test::CompareOutput::from_name)
+                .transpose()?
+                .unwrap_or_default();
+
+            // Run the corpus tests. Look for them in `test/corpus`, unless other
+            // directories were given.
+            let corpus_dirs = if test_options.corpus_dirs.is_empty() {
+                let test_corpus_dir = test_dir.join("corpus");
+                if test_corpus_dir.is_dir() {
+                    vec![test_corpus_dir]
+                } else {
+                    Vec::new()
+                }
+            } else {
+                for dir in &test_options.corpus_dirs {
+                    if !dir.is_dir() {
+                        return Err(anyhow!("Corpus directory {dir:?} does not exist"));
+                    }
+                }
+                test_options.corpus_dirs
+            };
+            for corpus_dir in corpus_dirs {
                 let mut opts = TestOptions {
-                    path: test_corpus_dir,
+                    path: corpus_dir,
                     debug: test_options.debug,
                     debug_graph: test_options.debug_graph,
                     filter: test_options.filter.as_deref(),
-                    include: test_options.include,
-                    exclude: test_options.exclude,
+                    include: test_options.include.clone(),
+                    exclude: test_options.exclude.clone(),
                     update: test_options.update,
                     open_log: test_options.open_log,
                     languages: languages.iter().map(|(l, n)| (n.as_str(), l)).collect(),
+                    extensions: &test_options.test_extensions,
+                    compare_output,
                 };
 
                 test::run_tests_at_path(&mut parser, &mut opts)?;
@@ -679,40 +1595,84 @@ fn run() -> Result<()> {
 
         Commands::Query(query_options) => {
             let config = Config::load(query_options.config_path)?;
-            let paths = collect_paths(query_options.paths_file.as_deref(), query_options.paths)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
-            let language = loader.select_language(
-                Path::new(&paths[0]),
-                &current_dir,
-                query_options.scope.as_deref(),
+            for mapping in &query_options.scope_for {
+                loader.add_scope_override(mapping)?;
+            }
+            let paths = collect_paths(
+                query_options.paths_file.as_deref(),
+                query_options.paths,
+                &query_options.path_filters,
+                false,
+                |path| {
+                    selects_language(
+                        &mut loader,
+                        path,
+                        &current_dir,
+                        query_options.scope.as_deref(),
+                    )
+                },
             )?;
             let query_path = Path::new(&query_options.query_path);
 
-            let byte_range = query_options.byte_range.as_ref().and_then(|range| {
-                let mut parts = range.split(':');
-                let start = parts.next()?.parse().ok()?;
-                let end = parts.next().unwrap().parse().ok()?;
-                Some(start..end)
-            });
+            let byte_ranges = query_options
+                .byte_range
+                .iter()
+                .map(|range| {
+                    range
+                        .split_once(':')
+                        .and_then(|(start, end)| Some(start.parse().ok()?..end.parse().ok()?))
+                        .ok_or_else(|| anyhow!("Invalid byte range {range:?}, expected START:END"))
+                })
+                .collect::<Result<Vec<_>>>()?;
             let point_range = query_options.row_range.as_ref().and_then(|range| {
                 let mut parts = range.split(':');
                 let start = parts.next()?.parse().ok()?;
                 let end = parts.next().unwrap().parse().ok()?;
                 Some(Point::new(start, 0)..Point::new(end, 0))
             });
-
-            query::query_files_at_paths(
-                &language,
-                paths,
+            let sort = query_options
+                .sort
+                .as_deref()
+                .map(query::CaptureOrder::from_name)
+                .transpose()?;
+            let format = query_options
+                .format
+                .as_deref()
+                .map(query::OutputFormat::from_name)
+                .transpose()?
+                .unwrap_or_default();
+            let text_filter = query_options
+                .grep
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .context("Invalid --grep regex")?;
+
+            let opts = query::QueryFileOptions {
+                current_dir: &current_dir,
+                scope: query_options.scope.as_deref(),
                 query_path,
-                query_options.captures,
-                byte_range,
+                ordered_captures: query_options.captures,
+                sort,
+                format,
+                byte_ranges: &byte_ranges,
                 point_range,
-                query_options.test,
-                query_options.quiet,
-                query_options.time,
-            )?;
+                match_limit: query_options.match_limit,
+                max_start_depth: query_options.max_start_depth,
+                should_test: query_options.test,
+                text_filter: text_filter.as_ref(),
+                patterns: &query_options.pattern,
+                disabled_captures: &query_options.disable_capture,
+                limit: query_options.limit,
+                context: query_options.context,
+                capture_kinds: query_options.capture_kinds,
+                quiet: query_options.quiet,
+                print_time: query_options.time,
+                debug: query_options.debug,
+            };
+            query::query_files_at_paths(&mut loader, paths, &opts)?;
         }
 
         Commands::Highlight(highlight_options) => {
@@ -722,17 +1682,29 @@ fn run() -> Result<()> {
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
 
+            if highlight_options.emit_css {
+                print!("{}", highlight::stylesheet(&theme_config.theme));
+                return Ok(());
+            }
+
             let quiet = highlight_options.quiet;
             let html_mode = quiet || highlight_options.html;
             let paths = collect_paths(
                 highlight_options.paths_file.as_deref(),
                 highlight_options.paths,
+                &highlight_options.path_filters,
+                true,
+                |path| {
+                    has_language_configuration(&loader, path, highlight_options.scope.as_deref())
+                },
             )?;
 
-            if html_mode && !quiet {
+            let check = highlight_options.check;
+            if html_mode && !quiet && !check {
                 println!("{}", highlight::HTML_HEADER);
             }
 
+            let mut failed_paths = Vec::new();
             let cancellation_flag = util::cancel_on_signal();
 
             let mut language = None;
@@ -748,7 +1720,15 @@ fn run() -> Result<()> {
                 let (language, language_config) = match language.clone() {
                     Some(v) => v,
                     None => {
-                        if let Some(v) = loader.language_configuration_for_file_name(path)? {
+                        if path == Path::new(util::STDIN_PATH) {
+                            return Err(util::stdin_requires_scope());
+                        }
+                        let language_path = util::language_detection_path(path);
+                        let language_contents = util::language_detection_contents(path)?;
+                        if let Some(v) = loader.language_configuration_for_file_name_with_contents(
+                            &language_path,
+                            language_contents.as_deref(),
+                        )? {
                             v
                         } else {
                             eprintln!("{}", util::lang_not_found_for_path(path, &loader_config));
@@ -777,6 +1757,9 @@ fn run() -> Result<()> {
                         } else {
                             highlight_config.nonconformant_capture_names(&HashSet::new())
                         };
+                        // Every gap that the check finds fails it, while unhighlighted
+                        // text only does so beyond `--max-unhighlighted`, if it's given.
+                        let mut gaps = Vec::new();
                         if names.is_empty() {
                             eprintln!("All highlight captures conform to standards.");
                         } else {
@@ -791,16 +1774,66 @@ fn run() -> Result<()> {
                             for name in names {
                                 eprintln!("* {name}");
                             }
+                            gaps.push("non-standard captures".to_string());
+                        }
+
+                        let names = highlight::names_missing_from_theme(
+                            highlight_config,
+                            &theme_config.theme,
+                        );
+                        if !names.is_empty() {
+                            eprintln!("Highlight captures that aren't in the theme:");
+                            for name in names {
+                                eprintln!("* {name}");
+                            }
+                            gaps.push("captures that aren't in the theme".to_string());
+                        }
+
+                        let source = util::read_source(path)?;
+                        let coverage = highlight::coverage(
+                            &loader,
+                            &source,
+                            highlight_config,
+                            Some(&cancellation_flag),
+                        )?;
+                        let percent = coverage.unhighlighted_percent();
+                        if !quiet {
+                            for range in &coverage.unhighlighted_ranges {
+                                let (start, end) = (
+                                    parse::position_for_offset(&source, range.start)?,
+                                    parse::position_for_offset(&source, range.end)?,
+                                );
+                                eprintln!(
+                                    "{}: unhighlighted text at {start} - {end}: `{}`",
+                                    path.display(),
+                                    String::from_utf8_lossy(&source[range.clone()])
+                                );
+                            }
+                        }
+                        eprintln!(
+                            "{}: {} of {} non-whitespace bytes are unhighlighted ({percent:.1}%)",
+                            path.display(),
+                            coverage.unhighlighted_bytes,
+                            coverage.total_bytes,
+                        );
+                        if percent > highlight_options.max_unhighlighted.unwrap_or(0.0) {
+                            gaps.push(format!("{percent:.1}% of the text is unhighlighted"));
                         }
+                        if !gaps.is_empty() {
+                            failed_paths.push(format!("{}: {}", path.display(), gaps.join(", ")));
+                        }
+                        continue;
                     }
 
-                    let source = fs::read(path)?;
+                    let source = util::read_source(path)?;
                     if html_mode {
                         highlight::html(
                             &loader,
                             &theme_config.theme,
                             &source,
                             highlight_config,
+                            highlight_options.css_classes,
+                            highlight_options.start_line,
                             quiet,
                             highlight_options.time,
                             Some(&cancellation_flag),
@@ -820,16 +1853,34 @@ fn run() -> Result<()> {
                 }
             }
 
-            if html_mode && !quiet {
+            if html_mode && !quiet && !check {
                 println!("{}", highlight::HTML_FOOTER);
             }
+
+            if !failed_paths.is_empty() {
+                return Err(anyhow!(
+                    "The highlighting check failed for:\n  {}",
+                    failed_paths.join("\n  ")
+                ));
+            }
         }
 
         Commands::Tags(tags_options) => {
+            let sort = tags_options
+                .sort
+                .as_deref()
+                .map(tags::TagOrder::from_name)
+                .transpose()?;
             let config = Config::load(tags_options.config_path)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
-            let paths = collect_paths(tags_options.paths_file.as_deref(), tags_options.paths)?;
+            let paths = collect_paths(
+                tags_options.paths_file.as_deref(),
+                tags_options.paths,
+                &tags_options.path_filters,
+                true,
+                |path| has_language_configuration(&loader, path, tags_options.scope.as_deref()),
+            )?;
             tags::generate_tags(
                 &loader,
                 &config.get()?,
@@ -837,6 +1888,8 @@ fn run() -> Result<()> {
                 &paths,
                 tags_options.quiet,
                 tags_options.time,
+                sort,
+                tags_options.dedup,
             )?;
         }
 
@@ -849,15 +1902,61 @@ fn run() -> Result<()> {
             playground::serve(&grammar_path, open_in_browser)?;
         }
 
+        Commands::Repl(repl_options) => {
+            let config = Config::load(repl_options.config_path)?;
+            let loader_config = config.get()?;
+            loader.find_all_languages(&loader_config)?;
+            let language = if let Some(scope) = repl_options.scope.as_deref() {
+                loader
+                    .language_configuration_for_scope(scope)?
+                    .ok_or_else(|| anyhow!("Unknown scope '{scope}'"))?
+                    .0
+            } else {
+                loader
+                    .languages_at_path(&current_dir)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No language found in the current directory, use --scope to select one"
+                        )
+                    })?
+                    .0
+            };
+            repl::run(&language, repl_options.incremental)?;
+        }
+
         Commands::DumpLanguages(dump_options) => {
             let config = Config::load(dump_options.config_path)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
             for (configuration, language_path) in loader.get_all_language_configurations() {
+                // Only the grammars that were already compiled are loaded, as compiling
+                // every grammar would make listing them slow.
+                let abi_version = match loader.compiled_language_for_configuration(configuration) {
+                    Ok(Some(language)) => {
+                        let version = language.version();
+                        if (tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION
+                            ..=tree_sitter::LANGUAGE_VERSION)
+                            .contains(&version)
+                        {
+                            version.to_string()
+                        } else {
+                            format!(
+                                "{version} (incompatible, this version of tree-sitter supports {} - {})",
+                                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                                tree_sitter::LANGUAGE_VERSION
+                            )
+                        }
+                    }
+                    Ok(None) => "unknown (not compiled)".to_string(),
+                    Err(e) => format!("unknown (failed to load the parser: {e})"),
+                };
                 println!(
                     concat!(
                         "scope: {}\n",
                         "parser: {:?}\n",
+                        "abi_version: {}\n",
                         "highlights: {:?}\n",
                         "file_types: {:?}\n",
                         "content_regex: {:?}\n",
@@ -865,6 +1964,7 @@ fn run() -> Result<()> {
                     ),
                     configuration.scope.as_ref().unwrap_or(&String::new()),
                     language_path,
+                    abi_version,
                     configuration.highlights_filenames,
                     configuration.file_types,
                     configuration.content_regex,
@@ -872,6 +1972,156 @@ fn run() -> Result<()> {
                 );
             }
         }
+
+        Commands::NodeTypes(node_types_options) => {
+            let config = Config::load(node_types_options.config_path)?;
+            let loader_config = config.get()?;
+            loader.find_all_languages(&loader_config)?;
+            let language = &node_types_options.language;
+            let (configuration, _) = loader
+                .get_all_language_configurations()
+                .into_iter()
+                .find(|(configuration, _)| {
+                    configuration.scope.as_ref() == Some(language)
+                        || &configuration.language_name == language
+                })
+                .ok_or_else(|| anyhow!("No language found with name or scope {language:?}"))?;
+            let node_types = node_types::NodeTypes::load(
+                &configuration.root_path.join("src").join("node-types.json"),
+            )?;
+
+            let nodes = if let Some(kind) = &node_types_options.kind {
+                let nodes = node_types.lookup(kind).collect::<Vec<_>>();
+                if nodes.is_empty() {
+                    return Err(anyhow!("No node type with kind {kind:?}"));
+                }
+                nodes
+            } else {
+                node_types.iter().collect()
+            };
+            if node_types_options.json {
+                let entries = nodes.into_iter().map(|(_, raw)| raw).collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                for (i, (node, _)) in nodes.into_iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    print!("{}", node_types::describe(node));
+                }
+            }
+        }
+
+        Commands::ValidateNodeTypes(validate_options) => {
+            let grammar_path = validate_options
+                .grammar_path
+                .map(PathBuf::from)
+                .unwrap_or(current_dir);
+            let languages = loader.languages_at_path(&grammar_path)?;
+            let language = &languages
+                .first()
+                .ok_or_else(|| anyhow!("No language found"))?
+                .0;
+            let node_types_path = grammar_path.join("src").join("node-types.json");
+            let mismatch =
+                node_types::NodeTypes::load(&node_types_path)?.compare_with_language(language);
+
+            for node_type in &mismatch.missing_from_node_types {
+                let name = node_types::node_type_name(&node_type.kind, node_type.named);
+                println!("Missing from node-types.json: {name}");
+            }
+            for node_type in &mismatch.missing_from_language {
+                let name = node_types::node_type_name(&node_type.kind, node_type.named);
+                println!("Missing from the language: {name}");
+            }
+            if !mismatch.is_empty() {
+                return Err(anyhow!(
+                    "{node_types_path:?} is out of date, run `tree-sitter generate` to update it"
+                ));
+            }
+        }
+
+        Commands::CheckGenerated(check_options) => {
+            let grammar_path = check_options
+                .grammar_path
+                .map(PathBuf::from)
+                .unwrap_or(current_dir);
+            generate::check_generated(&grammar_path, check_options.js_runtime.as_deref())?;
+        }
+
+        Commands::CheckFreshness(check_options) => {
+            let grammar_path = check_options
+                .grammar_path
+                .map(PathBuf::from)
+                .unwrap_or(current_dir);
+            let grammar_name = fs::read_to_string(grammar_path.join("src").join("grammar.json"))
+                .ok()
+                .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+                .and_then(|grammar| Some(grammar.get("name")?.as_str()?.to_string()));
+            let artifacts = freshness::grammar_artifacts(
+                &grammar_path,
+                grammar_name.map(|name| loader.library_path(&name)),
+            );
+            let statuses =
+                freshness::check_freshness(&grammar_path.join("grammar.js"), artifacts, |path| {
+                    fs::metadata(path).and_then(|m| m.modified()).ok()
+                });
+            for status in &statuses {
+                println!("{status}");
+            }
+            let stale_count = statuses
+                .iter()
+                .filter(|status| matches!(status.freshness, freshness::Freshness::Stale(_)))
+                .count();
+            if stale_count > 0 {
+                return Err(anyhow!(
+                    "{stale_count} of the grammar's files are out of date"
+                ));
+            }
+        }
+
+        Commands::DumpSymbols(dump_options) => {
+            let grammar_path = dump_options
+                .grammar_path
+                .map(PathBuf::from)
+                .unwrap_or(current_dir);
+            let languages = loader.languages_at_path(&grammar_path)?;
+            let language = &languages
+                .first()
+                .ok_or_else(|| anyhow!("No language found"))?
+                .0;
+            if dump_options.list_extras {
+                let grammar_json_path = grammar_path.join("src").join("grammar.json");
+                let grammar_json = fs::read_to_string(&grammar_json_path)
+                    .with_context(|| format!("Failed to read {grammar_json_path:?}"))?;
+                let extras = symbols::extras_info(language, &grammar_json)?;
+                println!("{}", serde_json::to_string_pretty(&extras)?);
+                return Ok(());
+            }
+
+            let parser_path = grammar_path.join("src").join("parser.c");
+            let parser_c = fs::read_to_string(&parser_path)
+                .with_context(|| format!("Failed to read {parser_path:?}"))?;
+            let symbols = symbols::symbol_map(language, &parser_c)?;
+
+            if dump_options.map {
+                println!("{}", serde_json::to_string_pretty(&symbols)?);
+            } else {
+                for (id, symbol) in &symbols {
+                    println!("{id}\t{}\t{:?}", symbol.c_symbol, symbol.name);
+                }
+            }
+        }
+
+        Commands::Deps(deps_options) => {
+            let mut grammar_path = deps_options.grammar_path.unwrap_or(current_dir);
+            if grammar_path.is_dir() {
+                grammar_path = grammar_path.join("grammar.js");
+            }
+            let dependencies =
+                generate::grammar_dependencies(&grammar_path, deps_options.js_runtime.as_deref())?;
+            println!("{}", serde_json::to_string_pretty(&dependencies)?);
+        }
     }
 
     Ok(())
@@ -909,14 +2159,28 @@ const fn get_styles() -> clap::builder::Styles {
         .placeholder(Style::new().fg_color(Some(Color::Ansi(AnsiColor::White))))
 }
 
-fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result<Vec<String>> {
+/// The names of the directories that are skipped when expanding a directory, along
+/// with hidden ones, as they hold dependencies or build output.
+const SKIPPED_DIRECTORIES: &[&str] = &["node_modules", "target", "vendor"];
+
+/// Collect the paths to process. The files beneath a directory are only collected
+/// if `has_language` accepts them.
+fn collect_paths(
+    paths_file: Option<&str>,
+    paths: Option<Vec<String>>,
+    path_filters: &PathFilters,
+    allow_stdin: bool,
+    mut has_language: impl FnMut(&Path) -> Result<bool>,
+) -> Result<Vec<String>> {
+    let max_file_size = path_filters.max_file_size;
     if let Some(paths_file) = paths_file {
-        return Ok(fs::read_to_string(paths_file)
-            .with_context(|| format!("Failed to read paths file {paths_file}"))?
-            .trim()
-            .lines()
-            .map(String::from)
-            .collect::<Vec<_>>());
+        let contents = fs::read_to_string(paths_file)
+            .with_context(|| format!("Failed to read paths file {paths_file}"))?;
+        let paths = util::paths_from_paths_file(&contents)
+            .into_iter()
+            .filter(|path| !exceeds_max_file_size(path, max_file_size))
+            .collect();
+        return util::exclude_paths(paths, &path_filters.exclude);
     }
 
     if let Some(paths) = paths {
@@ -924,7 +2188,9 @@ fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result
 
         let mut incorporate_path = |path: &str, positive| {
             if positive {
-                result.push(path.to_string());
+                if !exceeds_max_file_size(path, max_file_size) {
+                    result.push(path.to_string());
+                }
             } else if let Some(index) = result.iter().position(|p| p == path) {
                 result.remove(index);
             }
@@ -937,19 +2203,51 @@ fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result
                 path = path.trim_start_matches('!').to_string();
             }
 
-            if Path::new(&path).exists() {
+            if allow_stdin && path == util::STDIN_PATH {
+                incorporate_path(&path, positive);
+            } else if Path::new(&path).is_dir() {
+                // Directories expand to every file beneath them that has a known language.
+                let mut skipped = 0;
+                let walk = WalkDir::new(&path)
+                    .follow_links(path_filters.follow_symlinks)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_entry(|entry| entry.depth() == 0 || !is_skipped_directory(entry));
+                for entry in walk {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(e) if e.loop_ancestor().is_some() => {
+                            eprintln!("Warning: skipping a symbolic link cycle, {e}");
+                            continue;
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    if !entry.file_type().is_file() {
+                        continue;
+                    }
+                    if has_language(entry.path())? {
+                        if let Some(path) = entry.path().to_str() {
+                            incorporate_path(path, positive);
+                        }
+                    } else {
+                        skipped += 1;
+                    }
+                }
+                if skipped > 0 {
+                    eprintln!("Skipped {skipped} file(s) in {path:?} with no known language");
+                }
+            } else if Path::new(&path).exists() {
                 incorporate_path(&path, positive);
             } else {
-                let paths =
-                    glob(&path).with_context(|| format!("Invalid glob pattern {path:?}"))?;
-                for path in paths {
-                    if let Some(path) = path?.to_str() {
+                for entry in util::glob_paths(&path, path_filters.follow_symlinks)? {
+                    if let Some(path) = entry.to_str() {
                         incorporate_path(path, positive);
                     }
                 }
             }
         }
 
+        let result = util::exclude_paths(result, &path_filters.exclude)?;
         if result.is_empty() {
             return Err(anyhow!(
                 "No files were found at or matched by the provided pathname/glob"
@@ -961,3 +2259,68 @@ fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result
 
     Err(anyhow!("Must provide one or more paths"))
 }
+
+fn is_skipped_directory(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_dir()
+        && entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with('.') || SKIPPED_DIRECTORIES.contains(&name))
+}
+
+/// Whether `parse` and `query` select a language for a file, by which they filter
+/// the files beneath a directory.
+fn selects_language(
+    loader: &mut loader::Loader,
+    path: &Path,
+    current_dir: &Path,
+    scope: Option<&str>,
+) -> Result<bool> {
+    if scope.is_some() {
+        return Ok(true);
+    }
+    let language_contents = util::language_detection_contents(path)?;
+    Ok(loader
+        .select_language_with_contents(
+            &util::language_detection_path(path),
+            current_dir,
+            None,
+            language_contents.as_deref(),
+        )
+        .is_ok())
+}
+
+/// Whether `highlight` and `tags` find the language configuration of a file, by
+/// which they filter the files beneath a directory.
+fn has_language_configuration(
+    loader: &loader::Loader,
+    path: &Path,
+    scope: Option<&str>,
+) -> Result<bool> {
+    if scope.is_some() {
+        return Ok(true);
+    }
+    let language_contents = util::language_detection_contents(path)?;
+    Ok(loader
+        .language_configuration_for_file_name_with_contents(
+            &util::language_detection_path(path),
+            language_contents.as_deref(),
+        )?
+        .is_some())
+}
+
+fn exceeds_max_file_size(path: &str, max_file_size: u64) -> bool {
+    if max_file_size == 0 {
+        return false;
+    }
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() > max_file_size => {
+            eprintln!(
+                "Warning: skipping {path:?}, its size of {} bytes exceeds the limit of {max_file_size} bytes",
+                metadata.len()
+            );
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use tree_sitter::Language;
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct NodeType {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub named: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub multiple: bool,
+    pub required: bool,
+    pub types: Vec<NodeType>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct NodeInfo {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub named: bool,
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldInfo>,
+    pub children: Option<FieldInfo>,
+    pub subtypes: Option<Vec<NodeType>>,
+}
+
+/// The contents of a grammar's `node-types.json`, along with the raw JSON of
+/// each entry so it can be printed back unchanged.
+pub struct NodeTypes {
+    nodes: Vec<(NodeInfo, Value)>,
+}
+
+impl NodeInfo {
+    /// A leaf node has no fields, no unnamed children, and is not a supertype.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool {
+        self.fields.is_empty() && self.children.is_none() && self.subtypes.is_none()
+    }
+}
+
+impl NodeTypes {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read node types from {path:?}"))?;
+        Self::parse(&json).with_context(|| format!("Failed to parse node types in {path:?}"))
+    }
+
+    pub fn parse(json: &str) -> Result<Self> {
+        let entries = serde_json::from_str::<Vec<Value>>(json)?;
+        let nodes = entries
+            .into_iter()
+            .map(|entry| Ok((NodeInfo::deserialize(&entry)?, entry)))
+            .collect::<Result<_>>()?;
+        Ok(Self { nodes })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(NodeInfo, Value)> {
+        self.nodes.iter()
+    }
+
+    /// Find the entries for the given kind. A named node and an anonymous one
+    /// can share a kind, so there may be more than one.
+    pub fn lookup<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a (NodeInfo, Value)> {
+        self.nodes.iter().filter(move |(node, _)| node.kind == kind)
+    }
+
+    /// Compare the node kinds of the language with the entries of `node-types.json`.
+    /// Every visible kind must have an entry, and every entry must name a kind of
+    /// the language. Supertypes are hidden, so those only need to exist.
+    #[must_use]
+    pub fn compare_with_language(&self, language: &Language) -> NodeTypesMismatch {
+        let mut visible_kinds = BTreeSet::new();
+        let mut hidden_kinds = BTreeSet::new();
+        for id in 0..language.node_kind_count() as u16 {
+            if let Some(kind) = language.node_kind_for_id(id) {
+                if language.node_kind_is_visible(id) {
+                    visible_kinds.insert((kind, language.node_kind_is_named(id)));
+                } else {
+                    hidden_kinds.insert(kind);
+                }
+            }
+        }
+
+        let mut mismatch = NodeTypesMismatch::default();
+        let mut node_types_kinds = BTreeSet::new();
+        for (node, _) in &self.nodes {
+            let key = (node.kind.as_str(), node.named);
+            node_types_kinds.insert(key);
+            let is_supertype = node.subtypes.is_some() && hidden_kinds.contains(key.0);
+            if !visible_kinds.contains(&key) && !is_supertype {
+                mismatch.missing_from_language.push(NodeType {
+                    kind: node.kind.clone(),
+                    named: node.named,
+                });
+            }
+        }
+        for (kind, named) in visible_kinds.difference(&node_types_kinds) {
+            mismatch.missing_from_node_types.push(NodeType {
+                kind: (*kind).to_string(),
+                named: *named,
+            });
+        }
+        mismatch
+    }
+}
+
+/// The node kinds that appear in only one of a compiled language and its
+/// `node-types.json`. Any mismatch means that `node-types.json` is stale.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NodeTypesMismatch {
+    pub missing_from_node_types: Vec<NodeType>,
+    pub missing_from_language: Vec<NodeType>,
+}
+
+impl NodeTypesMismatch {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.missing_from_node_types.is_empty() && self.missing_from_language.is_empty()
+    }
+}
+
+/// Describe a node's shape, using query syntax for the types and quantifiers
+/// for how many children a field may hold.
+#[must_use]
+pub fn describe(node: &NodeInfo) -> String {
+    let mut result = format!("{}\n", node_type_name(&node.kind, node.named));
+    if let Some(subtypes) = &node.subtypes {
+        let _ = writeln!(result, "  subtypes: {}", node_type_list(subtypes));
+    }
+    for (name, field) in &node.fields {
+        let _ = writeln!(
+            result,
+            "  {name}{}: {}",
+            quantifier(field),
+            node_type_list(&field.types)
+        );
+    }
+    if let Some(children) = &node.children {
+        let _ = writeln!(
+            result,
+            "  children{}: {}",
+            quantifier(children),
+            node_type_list(&children.types)
+        );
+    }
+    let _ = writeln!(result, "  leaf: {}", node.is_leaf());
+    result
+}
+
+#[must_use]
+pub fn node_type_name(kind: &str, named: bool) -> String {
+    if named {
+        format!("({kind})")
+    } else {
+        format!("{kind:?}")
+    }
+}
+
+fn node_type_list(types: &[NodeType]) -> String {
+    types
+        .iter()
+        .map(|t| node_type_name(&t.kind, t.named))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+const fn quantifier(field: &FieldInfo) -> &'static str {
+    match (field.required, field.multiple) {
+        (true, false) => "",
+        (true, true) => "+",
+        (false, false) => "?",
+        (false, true) => "*",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_and_describe() {
+        let node_types = NodeTypes::parse(
+            r#"[
+                {
+                    "type": "_expression",
+                    "named": true,
+                    "subtypes": [
+                        {"type": "binary", "named": true},
+                        {"type": "number", "named": true}
+                    ]
+                },
+                {
+                    "type": "binary",
+                    "named": true,
+                    "fields": {
+                        "left": {
+                            "multiple": false,
+                            "required": true,
+                            "types": [{"type": "_expression", "named": true}]
+                        },
+                        "operator": {
+                            "multiple": false,
+                            "required": true,
+                            "types": [{"type": "+", "named": false}, {"type": "-", "named": false}]
+                        }
+                    },
+                    "children": {
+                        "multiple": true,
+                        "required": false,
+                        "types": [{"type": "comment", "named": true}]
+                    }
+                },
+                {"type": "number", "named": true},
+                {"type": "+", "named": false}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(node_types.iter().count(), 4);
+        assert_eq!(node_types.lookup("missing").count(), 0);
+
+        let (number, raw) = node_types.lookup("number").next().unwrap();
+        assert!(number.is_leaf());
+        assert_eq!(raw["type"], "number");
+
+        let (expression, _) = node_types.lookup("_expression").next().unwrap();
+        assert!(!expression.is_leaf());
+
+        let (binary, _) = node_types.lookup("binary").next().unwrap();
+        assert!(!binary.is_leaf());
+        assert_eq!(
+            describe(binary),
+            concat!(
+                "(binary)\n",
+                "  left: (_expression)\n",
+                "  operator: \"+\" | \"-\"\n",
+                "  children*: (comment)\n",
+                "  leaf: false\n",
+            )
+        );
+        assert_eq!(
+            describe(expression),
+            "(_expression)\n  subtypes: (binary) | (number)\n  leaf: false\n"
+        );
+    }
+}
@@ -1,11 +1,18 @@
+use super::highlight::{self, Theme};
+use super::node_types::node_type_name;
+use super::saved_tree::SavedTree;
 use super::util;
 use anyhow::{anyhow, Context, Result};
+use difference::{Changeset, Difference};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::{self, Write};
 use std::path::Path;
 use std::sync::atomic::AtomicUsize;
 use std::time::{Duration, Instant};
-use std::{fmt, fs, usize};
-use tree_sitter::{ffi, InputEdit, Language, LogType, Parser, Point, Tree};
+use std::{fmt, usize};
+use tree_sitter::{InputEdit, Language, LogType, Node, Parser, Point, Range, Tree, TreeCursor};
+use tree_sitter_highlight::HighlightConfiguration;
 
 #[derive(Debug)]
 pub struct Edit {
@@ -20,8 +27,19 @@ pub struct Stats {
     pub total_parses: usize,
     pub total_bytes: usize,
     pub total_duration: Duration,
+    /// The number of named nodes of each kind in all of the trees.
+    pub kind_counts: HashMap<String, usize>,
+    /// How many of the most frequent kinds to print, if any.
+    pub top_kind_count: Option<usize>,
+    /// The file with the deepest node in all of the trees, and the kinds of the
+    /// nodes from the root down to that node.
+    pub deepest_path: Option<(String, Vec<String>)>,
 }
 
+/// How many kinds of the deepest path are printed at each end of it. The ones
+/// in between are elided.
+const DEEPEST_PATH_END_LENGTH: usize = 10;
+
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let duration_us = self.total_duration.as_micros();
@@ -37,7 +55,95 @@ impl fmt::Display for Stats {
             } else {
                 0
             }
-        )
+        )?;
+
+        if let Some(top_kind_count) = self.top_kind_count {
+            let mut kind_counts = self.kind_counts.iter().collect::<Vec<_>>();
+            kind_counts.sort_unstable_by(|(a_kind, a_count), (b_kind, b_count)| {
+                b_count.cmp(a_count).then_with(|| a_kind.cmp(b_kind))
+            });
+            let count_width = kind_counts
+                .first()
+                .map_or(0, |(_, count)| count.to_string().len());
+            writeln!(f, "Most frequent node kinds:")?;
+            for (kind, count) in kind_counts.iter().take(top_kind_count) {
+                writeln!(f, "  {count:>count_width$} {kind}")?;
+            }
+            if kind_counts.len() > top_kind_count {
+                writeln!(
+                    f,
+                    "  ... and {} more kinds",
+                    kind_counts.len() - top_kind_count
+                )?;
+            }
+        }
+
+        if let Some((path, kinds)) = &self.deepest_path {
+            writeln!(f, "Maximum depth: {} in {path}", kinds.len())?;
+            if kinds.len() > 2 * DEEPEST_PATH_END_LENGTH {
+                let elided = kinds.len() - 2 * DEEPEST_PATH_END_LENGTH;
+                writeln!(
+                    f,
+                    "  {} > ... {elided} more ... > {}",
+                    kinds[..DEEPEST_PATH_END_LENGTH].join(" > "),
+                    kinds[kinds.len() - DEEPEST_PATH_END_LENGTH..].join(" > ")
+                )?;
+            } else {
+                writeln!(f, "  {}", kinds.join(" > "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1. This has no byte order mark, so it is never detected
+    /// automatically and must be selected explicitly.
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "utf8",
+            Self::Utf16Le => "utf16-le",
+            Self::Utf16Be => "utf16-be",
+            Self::Latin1 => "latin1",
+        })
+    }
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "utf8" => Ok(Self::Utf8),
+            // Kept for compatibility from before the byte order could be chosen.
+            "utf16" | "utf16-le" => Ok(Self::Utf16Le),
+            "utf16-be" => Ok(Self::Utf16Be),
+            "latin1" => Ok(Self::Latin1),
+            _ => Err(anyhow!(
+                "Invalid encoding {name:?}. Expected one of: utf8, utf16-le, utf16-be, latin1"
+            )),
+        }
+    }
+}
+
+/// Detect the encoding of a file from its byte order mark, returning the
+/// encoding along with the length of the BOM, which is zero when there is none.
+#[must_use]
+pub fn detect_encoding(source_code: &[u8]) -> (Encoding, usize) {
+    if source_code.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        (Encoding::Utf8, 3)
+    } else if source_code.starts_with(&[0xFF, 0xFE]) {
+        (Encoding::Utf16Le, 2)
+    } else if source_code.starts_with(&[0xFE, 0xFF]) {
+        (Encoding::Utf16Be, 2)
+    } else {
+        (Encoding::Utf8, 0)
     }
 }
 
@@ -45,14 +151,33 @@ impl fmt::Display for Stats {
 pub enum ParseOutput {
     Normal,
     Quiet,
+    Check,
     Xml,
     Dot,
+    SourceWithTree,
+    CanonicalSexp,
+    Checksum,
+    Ancestors,
+    Fields,
 }
 
 pub struct ParseFileOptions<'a> {
     pub language: Language,
     pub path: &'a Path,
     pub edits: &'a [&'a str],
+    pub extract: &'a [Point],
+    pub root_kind: Option<&'a str>,
+    pub ancestors: &'a [Point],
+    pub dump_changed_ranges: bool,
+    pub show_reused_nodes: bool,
+    pub validate_incremental: bool,
+    pub sexp_flags: SexpFlags,
+    /// With `--diff`, the S-expression output to compare with, and its path.
+    pub diff_against: Option<(&'a Path, &'a str)>,
+    pub save_tree: Option<&'a Path>,
+    pub measure_memory: bool,
+    pub count_kinds: bool,
+    pub find_deepest_path: bool,
     pub max_path_length: usize,
     pub output: ParseOutput,
     pub print_time: bool,
@@ -60,22 +185,98 @@ pub struct ParseFileOptions<'a> {
     pub debug: bool,
     pub debug_graph: bool,
     pub cancellation_flag: Option<&'a AtomicUsize>,
-    pub encoding: Option<u32>,
+    pub encoding: Option<Encoding>,
+    pub encoding_report: bool,
     pub open_log: bool,
+    pub highlight: Option<(&'a HighlightConfiguration, &'a Theme)>,
+    pub max_line_width: Option<usize>,
+    pub context: Option<usize>,
+    pub max_errors: usize,
+    /// Also report the errors that are nested in `ERROR` nodes.
+    pub nested_errors: bool,
+    pub byte_range: Option<std::ops::Range<usize>>,
+    pub ranges: &'a [std::ops::Range<Point>],
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct ParseResult {
     pub successful: bool,
     pub bytes: usize,
     pub duration: Option<Duration>,
+    pub checksum: Option<u64>,
+    pub memory: Option<TreeMemory>,
+    pub kind_counts: Option<HashMap<String, usize>>,
+    pub dot_graph: Option<String>,
+    pub deepest_path: Option<Vec<String>>,
+    /// Whether the S-expression differs from the one given with `--diff`.
+    pub sexp_differs: bool,
+}
+
+/// An estimate of the heap memory used by a syntax tree.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeMemory {
+    pub node_count: usize,
+    pub estimated_bytes: usize,
+}
+
+impl std::ops::AddAssign for TreeMemory {
+    fn add_assign(&mut self, other: Self) {
+        self.node_count += other.node_count;
+        self.estimated_bytes += other.estimated_bytes;
+    }
+}
+
+impl fmt::Display for TreeMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, ~{:.1} KiB",
+            self.node_count,
+            self.estimated_bytes as f64 / 1024.0
+        )
+    }
+}
+
+/// Up to `limit` of the `ERROR` and `MISSING` nodes of the tree, in document order.
+/// The errors nested in an `ERROR` node are only included if `nested` is set.
+#[must_use]
+pub fn error_nodes(tree: &Tree, limit: usize, nested: bool) -> Vec<Node<'_>> {
+    let mut result = Vec::new();
+    walk_nodes(tree.root_node(), |cursor| {
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            result.push(node);
+            if result.len() >= limit {
+                Walk::Stop
+            } else if nested && node.has_error() {
+                Walk::Continue
+            } else {
+                Walk::SkipChildren
+            }
+        } else if node.has_error() {
+            Walk::Continue
+        } else {
+            Walk::SkipChildren
+        }
+    });
+    result
+}
+
+/// Describe a syntax error in the form used by `parse --check`.
+fn describe_error_node(node: Node) -> String {
+    if !node.is_missing() {
+        format!("syntax error ({})", node.kind())
+    } else if node.is_named() {
+        format!("missing {}", node.kind())
+    } else {
+        format!("missing \"{}\"", node.kind().replace('\n', "\\n"))
+    }
 }
 
 pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Result<ParseResult> {
     let mut _log_session = None;
     parser.set_language(&opts.language)?;
-    let mut source_code = fs::read(opts.path)
-        .with_context(|| format!("Error reading source file {:?}", opts.path))?;
+    let mut source_code = util::read_source(opts.path)?;
 
     // If the `--cancel` flag was passed, then cancel the parse
     // when the user types a newline.
@@ -98,29 +299,67 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
         })));
     }
 
+    let (detected_encoding, bom_length) = detect_encoding(&source_code);
+    if opts.encoding_report {
+        let encoding = match opts.encoding {
+            Some(encoding) => format!("{encoding} (from --encoding, detected {detected_encoding})"),
+            None => format!("{detected_encoding} (detected)"),
+        };
+        let bom = if bom_length > 0 {
+            format!("{bom_length}-byte BOM")
+        } else {
+            "no BOM".to_string()
+        };
+        println!("{}: encoding {encoding}, {bom}", opts.path.display());
+    }
+    let encoding = opts.encoding.unwrap_or(detected_encoding);
+    if bom_length > 0 && encoding != detected_encoding {
+        eprintln!(
+            "Warning: {} has a {detected_encoding} byte order mark, but it is parsed as {encoding}",
+            opts.path.display()
+        );
+    }
+
     let time = Instant::now();
 
-    #[inline(always)]
-    fn is_utf16_bom(bom_bytes: &[u8]) -> bool {
-        bom_bytes == [0xFF, 0xFE] || bom_bytes == [0xFE, 0xFF]
+    // Latin-1 input is transcoded up front, so positions in the output and in
+    // `--edits`, `--byte-range` and `--range` refer to the UTF-8 text.
+    if encoding == Encoding::Latin1 {
+        source_code = decode_latin1(&source_code);
     }
 
-    let tree = match opts.encoding {
-        Some(encoding) if encoding == ffi::TSInputEncodingUTF16 => {
-            let source_code_utf16 = source_code
-                .chunks_exact(2)
-                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-                .collect::<Vec<_>>();
-            parser.parse_utf16(&source_code_utf16, None)
-        }
-        None if source_code.len() >= 2 && is_utf16_bom(&source_code[0..2]) => {
-            let source_code_utf16 = source_code
-                .chunks_exact(2)
-                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-                .collect::<Vec<_>>();
-            parser.parse_utf16(&source_code_utf16, None)
+    // Likewise, big-endian input is swapped to little-endian up front, which keeps
+    // the byte offsets the same.
+    if encoding == Encoding::Utf16Be {
+        for chunk in source_code.chunks_exact_mut(2) {
+            chunk.swap(0, 1);
         }
-        _ => parser.parse(&source_code, None),
+    }
+
+    let is_utf16 = matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be);
+
+    // With `--byte-range` or `--range`, only those parts of the file are parsed, but
+    // the nodes keep their positions in the whole file.
+    if is_utf16 && (opts.byte_range.is_some() || !opts.ranges.is_empty()) {
+        return Err(anyhow!(
+            "--byte-range and --range are not supported for UTF-16 files"
+        ));
+    }
+    parser.set_included_ranges(&included_ranges(
+        &source_code,
+        opts.path,
+        opts.byte_range.as_ref(),
+        opts.ranges,
+    )?)?;
+
+    let tree = if is_utf16 {
+        let source_code_utf16 = source_code
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect::<Vec<_>>();
+        parser.parse_utf16(&source_code_utf16, None)
+    } else {
+        parser.parse(&source_code, None)
     };
 
     parser.stop_printing_dot_graphs();
@@ -135,8 +374,29 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
 
         for (i, edit) in opts.edits.iter().enumerate() {
             let edit = parse_edit_flag(&source_code, edit)?;
-            perform_edit(&mut tree, &mut source_code, &edit)?;
-            tree = parser.parse(&source_code, Some(&tree)).unwrap();
+            let old_tree = tree;
+            let changed_ranges;
+            (tree, changed_ranges) =
+                reparse_with_edits(parser, &old_tree, &mut source_code, &[edit])?;
+
+            if opts.dump_changed_ranges {
+                writeln!(&mut stdout, "{}", changed_ranges_json(&changed_ranges))?;
+            }
+
+            if opts.show_reused_nodes {
+                writeln!(
+                    &mut stdout,
+                    "Edit {}: {}",
+                    i + 1,
+                    node_reuse(&old_tree, &tree)
+                )?;
+            }
+
+            if opts.validate_incremental {
+                if let Some(divergence) = validate_incremental_parse(parser, &tree, &source_code)? {
+                    return Err(anyhow!("Edit {}: {divergence}, in {:?}", i + 1, opts.path));
+                }
+            }
 
             if opts.debug_graph {
                 println!("AFTER {i}:\n{}", String::from_utf8_lossy(&source_code));
@@ -145,62 +405,66 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
 
         let duration = time.elapsed();
         let duration_ms = duration.as_micros() as f64 / 1e3;
+        // Rendering a large tree can take longer than parsing it, so `--time`
+        // reports the two phases separately.
+        let render_time = Instant::now();
         let mut cursor = tree.walk();
 
+        if let Some(path) = opts.save_tree {
+            SavedTree::from_tree(&tree).save(path)?;
+        }
+
+        // With `--extract`, only print the subtrees of the smallest named nodes that
+        // contain the given positions.
+        let roots = if opts.extract.is_empty() {
+            vec![tree.root_node()]
+        } else {
+            opts.extract
+                .iter()
+                .filter_map(|point| {
+                    tree.root_node()
+                        .named_descendant_for_point_range(*point, *point)
+                })
+                .collect()
+        };
+
+        // With `--root-kind`, print the first node of that kind instead of the root.
+        // This doesn't change how the file is parsed, so the fragment must still be
+        // valid where the grammar's start rule expects it.
+        let roots = match opts.root_kind {
+            Some(kind) => {
+                let node = roots
+                    .iter()
+                    .find_map(|root| first_node_of_kind(*root, kind))
+                    .ok_or_else(|| {
+                        anyhow!("No node of kind `{kind}` in {}", opts.path.display())
+                    })?;
+                vec![node]
+            }
+            None => roots,
+        };
+
+        let mut sexp_differs = false;
         if opts.output == ParseOutput::Normal {
-            let mut needs_newline = false;
-            let mut indent_level = 0;
-            let mut did_visit_children = false;
-            loop {
-                let node = cursor.node();
-                let is_named = node.is_named();
-                if did_visit_children {
-                    if is_named {
-                        stdout.write_all(b")")?;
-                        needs_newline = true;
-                    }
-                    if cursor.goto_next_sibling() {
-                        did_visit_children = false;
-                    } else if cursor.goto_parent() {
-                        did_visit_children = true;
-                        indent_level -= 1;
-                    } else {
-                        break;
-                    }
-                } else {
-                    if is_named {
-                        if needs_newline {
-                            stdout.write_all(b"\n")?;
-                        }
-                        for _ in 0..indent_level {
-                            stdout.write_all(b"  ")?;
-                        }
-                        let start = node.start_position();
-                        let end = node.end_position();
-                        if let Some(field_name) = cursor.field_name() {
-                            write!(&mut stdout, "{field_name}: ")?;
-                        }
-                        write!(
-                            &mut stdout,
-                            "({} [{}, {}] - [{}, {}]",
-                            node.kind(),
-                            start.row,
-                            start.column,
-                            end.row,
-                            end.column
-                        )?;
-                        needs_newline = true;
-                    }
-                    if cursor.goto_first_child() {
-                        did_visit_children = false;
-                        indent_level += 1;
-                    } else {
-                        did_visit_children = true;
-                    }
+            if let Some((old_path, old_sexp)) = opts.diff_against {
+                let mut sexp = Vec::new();
+                for root in &roots {
+                    write_sexp(&mut sexp, *root, opts.sexp_flags)?;
+                }
+                sexp_differs = write_unified_diff(
+                    &mut stdout,
+                    (&old_path.to_string_lossy(), old_sexp),
+                    (
+                        &opts.path.to_string_lossy(),
+                        &String::from_utf8_lossy(&sexp),
+                    ),
+                )?;
+            } else {
+                for root in &roots {
+                    write_sexp(&mut stdout, *root, opts.sexp_flags)?;
                 }
             }
             cursor.reset(tree.root_node());
-            println!();
         }
 
         if opts.output == ParseOutput::Xml {
@@ -258,12 +522,14 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
                         if let Some(field_name) = cursor.field_name() {
                             write!(&mut stdout, " field=\"{field_name}\"")?;
                         }
-                        let start = node.start_position();
-                        let end = node.end_position();
-                        write!(&mut stdout, " srow=\"{}\"", start.row)?;
-                        write!(&mut stdout, " scol=\"{}\"", start.column)?;
-                        write!(&mut stdout, " erow=\"{}\"", end.row)?;
-                        write!(&mut stdout, " ecol=\"{}\"", end.column)?;
+                        if !opts.sexp_flags.hide_positions {
+                            let start = node.start_position();
+                            let end = node.end_position();
+                            write!(&mut stdout, " srow=\"{}\"", start.row)?;
+                            write!(&mut stdout, " scol=\"{}\"", start.column)?;
+                            write!(&mut stdout, " erow=\"{}\"", end.row)?;
+                            write!(&mut stdout, " ecol=\"{}\"", end.column)?;
+                        }
                         write!(&mut stdout, ">")?;
                         tags.push(node.kind());
                         needs_newline = true;
@@ -276,8 +542,7 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
                         did_visit_children = true;
                         let start = node.start_byte();
                         let end = node.end_byte();
-                        let value =
-                            std::str::from_utf8(&source_code[start..end]).expect("has a string");
+                        let value = decode_node_text(&source_code[start..end], start, is_utf16)?;
                         // if !is_named {
                         //     for _ in 0..indent_level {
                         //         stdout.write_all(b"  ")?;
@@ -289,7 +554,7 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
                                 stdout.write_all(b"  ")?;
                             }
                         }
-                        write!(&mut stdout, "{}", html_escape::encode_text(value))?;
+                        write!(&mut stdout, "{}", html_escape::encode_text(&value))?;
                     }
                 }
             }
@@ -297,38 +562,149 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
             println!();
         }
 
-        if opts.output == ParseOutput::Dot {
-            util::print_tree_graph(&tree, "log.html", opts.open_log).unwrap();
+        if opts.output == ParseOutput::CanonicalSexp {
+            for root in &roots {
+                write_canonical_sexp(&mut stdout, *root)?;
+            }
         }
 
-        let mut first_error = None;
-        loop {
-            let node = cursor.node();
-            if node.has_error() {
-                if node.is_error() || node.is_missing() {
-                    first_error = Some(node);
-                    break;
+        if opts.output == ParseOutput::Ancestors {
+            for point in opts.ancestors {
+                writeln!(&mut stdout, "{}:{}", point.row, point.column)?;
+                for (depth, (node, field_name)) in
+                    ancestors_for_point(&tree, *point).into_iter().enumerate()
+                {
+                    let start = node.start_position();
+                    let end = node.end_position();
+                    let kind = if node.is_named() {
+                        node.kind().to_string()
+                    } else {
+                        format!("{:?}", node.kind())
+                    };
+                    writeln!(
+                        &mut stdout,
+                        "{:indent$}{}{kind} [{}, {}] - [{}, {}]",
+                        "",
+                        field_name.map_or(String::new(), |name| format!("{name}: ")),
+                        start.row,
+                        start.column,
+                        end.row,
+                        end.column,
+                        indent = 2 * depth,
+                    )?;
                 }
-                if !cursor.goto_first_child() {
-                    break;
+            }
+        }
+
+        if opts.output == ParseOutput::Fields {
+            for (kind, fields) in field_schema(&roots) {
+                writeln!(&mut stdout, "{kind}")?;
+                for (field_name, kinds) in fields {
+                    let kinds = kinds.into_iter().collect::<Vec<_>>().join(" | ");
+                    writeln!(&mut stdout, "  {field_name}: {kinds}")?;
                 }
-            } else if !cursor.goto_next_sibling() {
-                break;
             }
         }
 
-        if first_error.is_some() || opts.print_time {
+        if opts.output == ParseOutput::SourceWithTree {
+            // Multiline nodes are only listed on the row where they start.
+            let mut kinds_by_row = Vec::<Vec<&str>>::new();
+            visit_nodes(&tree, |node| {
+                if node.is_named() {
+                    let row = node.start_position().row;
+                    if kinds_by_row.len() <= row {
+                        kinds_by_row.resize_with(row + 1, Vec::new);
+                    }
+                    kinds_by_row[row].push(node.kind());
+                }
+            });
+
+            let source = String::from_utf8_lossy(&source_code);
+            let mut lines = source
+                .split('\n')
+                .map(|line| line.trim_end_matches('\r'))
+                .collect::<Vec<_>>();
+            if lines.len() > 1 && lines.last() == Some(&"") {
+                lines.pop();
+            }
+            let lines = lines
+                .into_iter()
+                .map(|line| match opts.max_line_width {
+                    Some(max_width) => highlight::truncate_line(line, max_width),
+                    None => line.into(),
+                })
+                .collect::<Vec<_>>();
+            let row_width = (lines.len() - 1).to_string().len();
+            let line_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+            // Color the source code when the language has a highlight query.
+            let highlighted_lines = match opts.highlight {
+                Some((config, theme)) => Some(highlight::ansi_lines(
+                    theme,
+                    &source_code,
+                    config,
+                    opts.cancellation_flag,
+                    opts.max_line_width,
+                )?),
+                None => None,
+            };
+
+            for (row, line) in lines.iter().enumerate() {
+                let kinds = kinds_by_row.get(row).map_or(String::new(), |k| k.join(" "));
+                let padding = " ".repeat(line_width - line.chars().count());
+                let text = highlighted_lines
+                    .as_ref()
+                    .and_then(|lines| lines.get(row))
+                    .map_or(line.as_ref(), String::as_str);
+                let listing = format!("{row:>row_width$} | {text}{padding}  {kinds}");
+                writeln!(&mut stdout, "{}", listing.trim_end())?;
+            }
+        }
+
+        let errors = error_nodes(&tree, opts.max_errors, opts.nested_errors);
+
+        if opts.output == ParseOutput::Check {
+            for node in &errors {
+                let start = node.start_position();
+                writeln!(
+                    &mut stdout,
+                    "{}:{}:{}: {}",
+                    opts.path.to_str().unwrap(),
+                    start.row + 1,
+                    start.column + 1,
+                    describe_error_node(*node)
+                )?;
+            }
+        } else if !errors.is_empty() || opts.print_time {
             write!(
                 &mut stdout,
-                "{:width$}\t{duration_ms:>7.2} ms\t{:>6} bytes/ms",
+                "{:width$}\t{duration_ms:>7.2} ms",
                 opts.path.to_str().unwrap(),
-                (source_code.len() as u128 * 1_000_000) / duration.as_nanos(),
                 width = opts.max_path_length
             )?;
-            if let Some(node) = first_error {
+            if opts.print_time {
+                let render_ms = render_time.elapsed().as_micros() as f64 / 1e3;
+                write!(&mut stdout, " parse\t{render_ms:>7.2} ms render")?;
+            }
+            write!(
+                &mut stdout,
+                "\t{:>6} bytes/ms",
+                (source_code.len() as u128 * 1_000_000) / duration.as_nanos()
+            )?;
+            if opts.print_time {
+                let seconds = duration.as_secs_f64();
+                let line_count = source_code.split_inclusive(|b| *b == b'\n').count();
+                write!(
+                    &mut stdout,
+                    "\t{:>8.2} MB/s\t{:>10.0} lines/s",
+                    source_code.len() as f64 / 1e6 / seconds,
+                    line_count as f64 / seconds
+                )?;
+            }
+            for (i, node) in errors.iter().enumerate() {
                 let start = node.start_position();
                 let end = node.end_position();
-                write!(&mut stdout, "\t(")?;
+                write!(&mut stdout, "{}(", if i == 0 { "\t" } else { " " })?;
                 if node.is_missing() {
                     if node.is_named() {
                         write!(&mut stdout, "MISSING {}", node.kind())?;
@@ -351,14 +727,35 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
             writeln!(&mut stdout)?;
         }
 
+        if let Some(context) = opts.context.filter(|_| !errors.is_empty()) {
+            let mut source_context = util::SourceContext::new(&source_code, context);
+            for node in error_nodes(&tree, usize::MAX, opts.nested_errors) {
+                source_context.write(
+                    &mut stdout,
+                    "  ",
+                    node.start_position().row,
+                    node.end_position().row,
+                )?;
+            }
+        }
+
         return Ok(ParseResult {
-            successful: first_error.is_none(),
+            successful: errors.is_empty(),
             bytes: source_code.len(),
             duration: Some(duration),
+            checksum: (opts.output == ParseOutput::Checksum).then(|| tree_checksum(&tree)),
+            memory: opts.measure_memory.then(|| tree_memory(&tree)),
+            kind_counts: opts.count_kinds.then(|| count_node_kinds(&tree)),
+            dot_graph: (opts.output == ParseOutput::Dot)
+                .then(|| dot_graph(&tree, opts.sexp_flags.hide_positions)),
+            deepest_path: opts.find_deepest_path.then(|| deepest_path(&tree)),
+            sexp_differs,
         });
     }
 
-    if opts.print_time {
+    if opts.output == ParseOutput::Check {
+        writeln!(&mut stdout, "{}: timed out", opts.path.to_str().unwrap())?;
+    } else if opts.print_time {
         let duration = time.elapsed();
         let duration_ms = duration.as_micros() as f64 / 1e3;
         writeln!(
@@ -373,9 +770,744 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
         successful: false,
         bytes: source_code.len(),
         duration: None,
+        checksum: None,
+        memory: None,
+        kind_counts: None,
+        dot_graph: None,
+        deepest_path: None,
+        sexp_differs: false,
     })
 }
 
+/// How many of the nodes in a tree were reused from the tree it was reparsed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeReuse {
+    pub reused_count: usize,
+    pub node_count: usize,
+}
+
+impl fmt::Display for NodeReuse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reused {} of {} nodes ({:.1}%), created {}",
+            self.reused_count,
+            self.node_count,
+            if self.node_count == 0 {
+                0.0
+            } else {
+                self.reused_count as f64 * 100.0 / self.node_count as f64
+            },
+            self.node_count - self.reused_count
+        )
+    }
+}
+
+/// Compare the nodes of a tree with those of the tree it was reparsed from, before it
+/// was edited. A node counts as reused if the new tree shares it with the old one.
+/// Small tokens are stored inline in their parent, so they only count as reused when
+/// their parent is.
+#[must_use]
+pub fn node_reuse(old_tree: &Tree, new_tree: &Tree) -> NodeReuse {
+    let mut old_ids = HashSet::new();
+    visit_nodes(old_tree, |node| {
+        old_ids.insert(node.id());
+    });
+    let mut result = NodeReuse {
+        reused_count: 0,
+        node_count: 0,
+    };
+    visit_nodes(new_tree, |node| {
+        result.node_count += 1;
+        if old_ids.contains(&node.id()) {
+            result.reused_count += 1;
+        }
+    });
+    result
+}
+
+/// The first node, in pre-order, at which an incrementally reparsed tree differs
+/// from the tree that a full parse of the same text produces.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TreeDivergence {
+    pub incremental: String,
+    pub full: String,
+}
+
+impl fmt::Display for TreeDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the incremental parse has {}, where a full parse has {}",
+            self.incremental, self.full
+        )
+    }
+}
+
+/// Parse the current source code from scratch, and compare the result with the
+/// tree that was produced by reparsing it incrementally. The trees must have the
+/// same nodes, with the same kinds, fields and ranges; node ids are ignored.
+pub fn validate_incremental_parse(
+    parser: &mut Parser,
+    incremental_tree: &Tree,
+    source_code: &[u8],
+) -> Result<Option<TreeDivergence>> {
+    let full_tree = parser
+        .parse(source_code, None)
+        .ok_or_else(|| anyhow!("Failed to parse the edited source code from scratch"))?;
+    Ok(first_divergence(incremental_tree, &full_tree))
+}
+
+/// Walk two trees in parallel, returning the first node at which they differ. Only
+/// the range of a node differs when one of its descendants does, so a node is
+/// reported for its range only if its children all match.
+#[must_use]
+pub fn first_divergence(incremental_tree: &Tree, full_tree: &Tree) -> Option<TreeDivergence> {
+    let describe = |node: Node, field_name: Option<&str>| {
+        let kind = if node.is_named() {
+            node.kind().to_string()
+        } else {
+            format!("{:?}", node.kind())
+        };
+        format!(
+            "{}({}{kind} [{}, {}] - [{}, {}])",
+            field_name.map_or(String::new(), |name| format!("{name}: ")),
+            if node.is_missing() { "MISSING " } else { "" },
+            node.start_position().row,
+            node.start_position().column,
+            node.end_position().row,
+            node.end_position().column,
+        )
+    };
+
+    // Each entry holds a pair of corresponding nodes, with their field name, and
+    // the index of the next pair of children to compare.
+    let root_pair = (incremental_tree.root_node(), full_tree.root_node(), None);
+    let mut stack = vec![(root_pair, 0)];
+    while let Some(((a, b, field_name), child_index)) = stack.last_mut() {
+        let divergence = || TreeDivergence {
+            incremental: describe(*a, *field_name),
+            full: describe(*b, *field_name),
+        };
+        if *child_index == 0
+            && (a.kind_id() != b.kind_id()
+                || a.is_missing() != b.is_missing()
+                || a.child_count() != b.child_count())
+        {
+            return Some(divergence());
+        }
+        if *child_index < a.child_count() {
+            let i = *child_index;
+            *child_index += 1;
+            let (field_a, field_b) = (
+                a.field_name_for_child(i as u32),
+                b.field_name_for_child(i as u32),
+            );
+            let (child_a, child_b) = (a.child(i).unwrap(), b.child(i).unwrap());
+            if field_a != field_b {
+                return Some(TreeDivergence {
+                    incremental: describe(child_a, field_a),
+                    full: describe(child_b, field_b),
+                });
+            }
+            stack.push(((child_a, child_b, field_a), 0));
+        } else if a.byte_range() != b.byte_range() {
+            return Some(divergence());
+        } else {
+            stack.pop();
+        }
+    }
+    None
+}
+
+/// What [`walk_nodes`] should do after visiting a node.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Walk {
+    Continue,
+    SkipChildren,
+    Stop,
+}
+
+/// Visit the given node and every node beneath it in pre-order, with a cursor that
+/// is positioned at each node in turn, so that the callback can also read the
+/// node's depth below the given node and its field name.
+pub(crate) fn walk_nodes<'tree>(
+    node: Node<'tree>,
+    mut callback: impl FnMut(&TreeCursor<'tree>) -> Walk,
+) {
+    let mut cursor = node.walk();
+    loop {
+        match callback(&cursor) {
+            Walk::Stop => return,
+            Walk::Continue if cursor.goto_first_child() => continue,
+            Walk::Continue | Walk::SkipChildren => {}
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return;
+            }
+        }
+    }
+}
+
+fn visit_nodes<'tree>(tree: &'tree Tree, mut callback: impl FnMut(Node<'tree>)) {
+    walk_nodes(tree.root_node(), |cursor| {
+        callback(cursor.node());
+        Walk::Continue
+    });
+}
+
+/// Estimate the memory used by a tree from the nodes that can be reached with a
+/// cursor. Every node with children is a heap-allocated subtree followed by an
+/// array of its children. Most leaves are stored inline in their parent's array,
+/// so they take no extra space. Hidden nodes aren't visited, so this is a lower
+/// bound.
+#[must_use]
+pub fn tree_memory(tree: &Tree) -> TreeMemory {
+    // The size of `SubtreeHeapData`, and of the `Subtree` union that is stored
+    // for each child, on 64-bit platforms.
+    const SUBTREE_HEAP_DATA_SIZE: usize = 80;
+    const SUBTREE_SIZE: usize = 8;
+
+    let mut memory = TreeMemory::default();
+    visit_nodes(tree, |node| {
+        memory.node_count += 1;
+        if node.child_count() > 0 {
+            memory.estimated_bytes += SUBTREE_HEAP_DATA_SIZE + SUBTREE_SIZE * node.child_count();
+        }
+    });
+    memory
+}
+
+/// Count the named nodes of each kind in the tree.
+fn count_node_kinds(tree: &Tree) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    visit_nodes(tree, |node| {
+        if node.is_named() {
+            *counts.entry(node.kind().to_string()).or_default() += 1;
+        }
+    });
+    counts
+}
+
+/// The kinds of the nodes from the root down to the first of the deepest nodes in
+/// the tree. This takes two passes with a cursor, the first to find the deepest
+/// node and the second to record the path to it, so that deeply nested trees
+/// neither recurse nor copy the path at every new depth.
+#[must_use]
+pub fn deepest_path(tree: &Tree) -> Vec<String> {
+    let (mut max_depth, mut deepest_index) = (0, 0);
+    let mut index = 0;
+    walk_nodes(tree.root_node(), |cursor| {
+        let depth = cursor.depth() as usize + 1;
+        if depth > max_depth {
+            (max_depth, deepest_index) = (depth, index);
+        }
+        index += 1;
+        Walk::Continue
+    });
+
+    let mut kinds = Vec::new();
+    let mut index = 0;
+    walk_nodes(tree.root_node(), |cursor| {
+        let node = cursor.node();
+        kinds.truncate(cursor.depth() as usize);
+        kinds.push(if node.is_named() {
+            node.kind().to_string()
+        } else {
+            format!("{:?}", node.kind())
+        });
+        index += 1;
+        if index > deepest_index {
+            Walk::Stop
+        } else {
+            Walk::Continue
+        }
+    });
+    kinds
+}
+
+/// Render the tree as a Graphviz graph, with each node labeled with its kind and,
+/// unless `hide_positions` is set, its byte range, and each edge to a child
+/// labeled with the child's field name.
+#[must_use]
+pub fn dot_graph(tree: &Tree, hide_positions: bool) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut graph = String::from("digraph tree {\n  node [shape=box];\n");
+    let mut parent_ids = Vec::new();
+    let mut next_id = 0;
+    walk_nodes(tree.root_node(), |cursor| {
+        let node = cursor.node();
+        let id = next_id;
+        next_id += 1;
+
+        let kind = if node.is_missing() {
+            format!("MISSING {}", node.kind())
+        } else if node.is_named() {
+            node.kind().to_string()
+        } else {
+            format!("{:?}", node.kind())
+        };
+        let range = if hide_positions {
+            String::new()
+        } else {
+            format!(" {}..{}", node.start_byte(), node.end_byte())
+        };
+        graph += &format!(
+            "  n{id} [label=\"{}{range}\"{}];\n",
+            escape(&kind),
+            if node.is_error() || node.is_missing() {
+                ", color=red"
+            } else {
+                ""
+            }
+        );
+        parent_ids.truncate(cursor.depth() as usize);
+        if let Some(parent_id) = parent_ids.last() {
+            match cursor.field_name() {
+                Some(field_name) => {
+                    graph += &format!("  n{parent_id} -> n{id} [label=\"{field_name}\"];\n");
+                }
+                None => graph += &format!("  n{parent_id} -> n{id};\n"),
+            }
+        }
+        parent_ids.push(id);
+        Walk::Continue
+    });
+    graph.push_str("}\n");
+    graph
+}
+
+/// Hash the depth, kind, byte range and field name of every node in the tree, in
+/// document order. This uses FNV-1a so that the checksum of a tree is the same on
+/// every platform and with every version of Rust.
+#[must_use]
+pub fn tree_checksum(tree: &Tree) -> u64 {
+    fn fnv1a(hash: &mut u64, bytes: &[u8]) {
+        for byte in bytes {
+            *hash ^= u64::from(*byte);
+            *hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    walk_nodes(tree.root_node(), |cursor| {
+        let node = cursor.node();
+        fnv1a(&mut hash, &u64::from(cursor.depth()).to_le_bytes());
+        fnv1a(&mut hash, node.kind().as_bytes());
+        fnv1a(&mut hash, &[0, u8::from(node.is_missing())]);
+        fnv1a(&mut hash, &(node.start_byte() as u64).to_le_bytes());
+        fnv1a(&mut hash, &(node.end_byte() as u64).to_le_bytes());
+        fnv1a(
+            &mut hash,
+            cursor.field_name().unwrap_or_default().as_bytes(),
+        );
+        fnv1a(&mut hash, &[0]);
+        Walk::Continue
+    });
+    hash
+}
+
+/// What to add to the S-expression output of `parse`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SexpFlags {
+    /// Annotate extra nodes, such as comments, with `extra` after their kind.
+    pub mark_extras: bool,
+    /// Include anonymous nodes, with their kinds quoted.
+    pub anonymous_nodes: bool,
+    /// Print the numeric ID of each field after its name.
+    pub field_ids: bool,
+    /// Leave out the position of each node.
+    pub hide_positions: bool,
+}
+
+/// A node in pre-order, as written by [`SexpWriter`]. This is read either from a
+/// tree cursor or from a saved tree.
+pub struct SexpNode<'a> {
+    /// The number of nodes above this one, counting from the first node written.
+    pub depth: usize,
+    pub kind: &'a str,
+    pub field_name: Option<&'a str>,
+    pub field_id: Option<u16>,
+    pub is_named: bool,
+    pub is_extra: bool,
+    pub start_position: Point,
+    pub end_position: Point,
+}
+
+/// Write nodes as an S-expression, putting every named node on its own line along
+/// with its position. Nodes must be given in pre-order, and each node is closed
+/// once a node that isn't below it is written, or when the writer is finished.
+pub struct SexpWriter<'a, W: Write> {
+    out: &'a mut W,
+    flags: SexpFlags,
+    open_depths: Vec<usize>,
+    needs_newline: bool,
+}
+
+impl<'a, W: Write> SexpWriter<'a, W> {
+    pub fn new(out: &'a mut W, flags: SexpFlags) -> Self {
+        Self {
+            out,
+            flags,
+            open_depths: Vec::new(),
+            needs_newline: false,
+        }
+    }
+
+    pub fn write_node(&mut self, node: &SexpNode) -> io::Result<()> {
+        while self
+            .open_depths
+            .last()
+            .is_some_and(|depth| *depth >= node.depth)
+        {
+            self.open_depths.pop();
+            self.out.write_all(b")")?;
+        }
+        if !node.is_named && !self.flags.anonymous_nodes {
+            return Ok(());
+        }
+
+        if self.needs_newline {
+            self.out.write_all(b"\n")?;
+        }
+        for _ in 0..node.depth {
+            self.out.write_all(b"  ")?;
+        }
+        if let Some(field_name) = node.field_name {
+            match node.field_id.filter(|_| self.flags.field_ids) {
+                Some(field_id) => write!(self.out, "{field_name}#{field_id}: ")?,
+                None => write!(self.out, "{field_name}: ")?,
+            }
+        }
+        let kind = if node.is_named {
+            node.kind.to_string()
+        } else {
+            format!("{:?}", node.kind)
+        };
+        write!(
+            self.out,
+            "({kind}{}",
+            if self.flags.mark_extras && node.is_extra {
+                " extra"
+            } else {
+                ""
+            }
+        )?;
+        if !self.flags.hide_positions {
+            let (start, end) = (node.start_position, node.end_position);
+            write!(
+                self.out,
+                " [{}, {}] - [{}, {}]",
+                start.row, start.column, end.row, end.column
+            )?;
+        }
+        self.open_depths.push(node.depth);
+        self.needs_newline = true;
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        for _ in self.open_depths {
+            self.out.write_all(b")")?;
+        }
+        writeln!(self.out)
+    }
+}
+
+/// Write the given node and the nodes beneath it as an S-expression.
+pub fn write_sexp(out: &mut impl Write, node: Node<'_>, flags: SexpFlags) -> io::Result<()> {
+    let mut writer = SexpWriter::new(out, flags);
+    let mut result = Ok(());
+    walk_nodes(node, |cursor| {
+        let node = cursor.node();
+        result = writer.write_node(&SexpNode {
+            depth: cursor.depth() as usize,
+            kind: node.kind(),
+            field_name: cursor.field_name(),
+            field_id: cursor.field_id().map(u16::from),
+            is_named: node.is_named(),
+            is_extra: node.is_extra(),
+            start_position: node.start_position(),
+            end_position: node.end_position(),
+        });
+        if result.is_ok() {
+            Walk::Continue
+        } else {
+            Walk::Stop
+        }
+    });
+    result?;
+    writer.finish()
+}
+
+/// Write the given node and the nodes beneath it as the S-expression of
+/// `--sexp-canonical`, on a single line and without positions, so that the output
+/// only changes when the structure of the tree does.
+pub fn write_canonical_sexp(out: &mut impl Write, node: Node<'_>) -> io::Result<()> {
+    writeln!(out, "{}", node.to_sexp())
+}
+
+fn write_unified_diff(
+    out: &mut impl Write,
+    old: (&str, &str),
+    new: (&str, &str),
+) -> io::Result<bool> {
+    const CONTEXT: usize = 3;
+
+    let changeset = Changeset::new(
+        old.1.trim_end_matches('\n'),
+        new.1.trim_end_matches('\n'),
+        "\n",
+    );
+    let mut lines = Vec::new();
+    for diff in &changeset.diffs {
+        let (tag, part) = match diff {
+            Difference::Same(part) => (' ', part),
+            Difference::Rem(part) => ('-', part),
+            Difference::Add(part) => ('+', part),
+        };
+        lines.extend(part.split('\n').map(|line| (tag, line)));
+    }
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (tag, _))| *tag != ' ')
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return Ok(false);
+    }
+
+    writeln!(out, "--- {}", old.0)?;
+    writeln!(out, "+++ {}", new.0)?;
+    let mut changes = changes.into_iter().peekable();
+    while let Some(first_change) = changes.next() {
+        // Changes that are close enough for their context to touch share a hunk.
+        let start = first_change.saturating_sub(CONTEXT);
+        let mut end = first_change + 1;
+        while let Some(change) = changes.next_if(|change| *change <= end + 2 * CONTEXT) {
+            end = change + 1;
+        }
+        let end = (end + CONTEXT).min(lines.len());
+
+        let line_count = |range: std::ops::Range<usize>, skipped: char| {
+            lines[range]
+                .iter()
+                .filter(|(tag, _)| *tag != skipped)
+                .count()
+        };
+        let (old_start, new_start) = (line_count(0..start, '+'), line_count(0..start, '-'));
+        let (old_length, new_length) = (line_count(start..end, '+'), line_count(start..end, '-'));
+        writeln!(
+            out,
+            "@@ -{},{old_length} +{},{new_length} @@",
+            old_start + 1,
+            new_start + 1
+        )?;
+        for (tag, line) in &lines[start..end] {
+            writeln!(out, "{tag}{line}")?;
+        }
+    }
+    Ok(true)
+}
+
+/// Every Latin-1 byte is the Unicode code point with the same value.
+fn decode_latin1(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .map(|&b| char::from(b))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Decode the text of a node that starts at `start_byte`. If the text is invalid,
+/// the error gives the offset of the bad data within the file.
+fn decode_node_text(bytes: &[u8], start_byte: usize, is_utf16: bool) -> Result<String> {
+    if !is_utf16 {
+        return String::from_utf8(bytes.to_vec()).map_err(|e| {
+            anyhow!(
+                "Invalid UTF-8 in the node at byte {start_byte}, at byte {}",
+                start_byte + e.utf8_error().valid_up_to()
+            )
+        });
+    }
+
+    let code_units = bytes
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+    let mut result = String::with_capacity(bytes.len() / 2);
+    let mut offset = start_byte;
+    for ch in char::decode_utf16(code_units) {
+        match ch {
+            Ok(ch) => {
+                result.push(ch);
+                offset += 2 * ch.len_utf16();
+            }
+            Err(e) => {
+                return Err(anyhow!(
+                    "Invalid UTF-16 in the node at byte {start_byte}: unpaired surrogate {:#06x} at byte {offset}",
+                    e.unpaired_surrogate()
+                ));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The nodes that contain the given position, from the root down to the smallest
+/// one, along with their field names.
+#[must_use]
+pub fn ancestors_for_point(tree: &Tree, point: Point) -> Vec<(Node<'_>, Option<&'static str>)> {
+    let mut cursor = tree.walk();
+    let mut result = vec![(cursor.node(), None)];
+    while cursor.goto_first_child_for_point(point).is_some()
+        && cursor.node().start_position() <= point
+    {
+        result.push((cursor.node(), cursor.field_name()));
+    }
+    result
+}
+
+/// The first node of the given kind in a pre-order traversal of the subtree, which
+/// may be the given node itself.
+#[must_use]
+pub fn first_node_of_kind<'tree>(node: Node<'tree>, kind: &str) -> Option<Node<'tree>> {
+    let mut result = None;
+    walk_nodes(node, |cursor| {
+        if cursor.node().kind() == kind {
+            result = Some(cursor.node());
+            Walk::Stop
+        } else {
+            Walk::Continue
+        }
+    });
+    result
+}
+
+/// Collect, for each kind of named node in the given subtrees, the kinds of nodes
+/// that appear under each of its fields. Named children without a field are
+/// listed under `children`, like in `node-types.json`.
+#[must_use]
+pub fn field_schema(roots: &[Node]) -> BTreeMap<String, BTreeMap<&'static str, BTreeSet<String>>> {
+    let mut schema = BTreeMap::<String, BTreeMap<_, BTreeSet<_>>>::new();
+    let mut stack = roots.to_vec();
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        if !cursor.goto_first_child() {
+            continue;
+        }
+        loop {
+            let child = cursor.node();
+            let field_name = cursor
+                .field_name()
+                .or_else(|| child.is_named().then_some("children"));
+            if let (true, Some(field_name)) = (node.is_named(), field_name) {
+                schema
+                    .entry(node_type_name(node.kind(), true))
+                    .or_default()
+                    .entry(field_name)
+                    .or_default()
+                    .insert(node_type_name(child.kind(), child.is_named()));
+            }
+            stack.push(child);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+    schema
+}
+
+/// Parse a `row:column` position, as given to `--extract` and `--ancestors`.
+pub fn parse_point_flag(flag: &str) -> Result<Point> {
+    flag.split_once(':')
+        .and_then(|(row, column)| Some(Point::new(row.parse().ok()?, column.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid position {flag:?}, expected ROW:COLUMN"))
+}
+
+/// Parse a `--range` flag, which is two positions in the form of `ROW:COLUMN`
+/// separated by a `-`.
+pub fn parse_range_flag(flag: &str) -> Result<std::ops::Range<Point>> {
+    let (start, end) = flag
+        .split_once('-')
+        .and_then(|(start, end)| Some((parse_point_flag(start).ok()?, parse_point_flag(end).ok()?)))
+        .ok_or_else(|| {
+            anyhow!("Invalid range {flag:?}, expected START_ROW:START_COLUMN-END_ROW:END_COLUMN")
+        })?;
+    if start >= end {
+        return Err(anyhow!(
+            "Invalid range {flag:?}, the start must be before the end"
+        ));
+    }
+    Ok(start..end)
+}
+
+/// Parse a `--byte-range` flag, which is two byte offsets separated by a `:`.
+pub fn parse_byte_range_flag(flag: &str) -> Result<std::ops::Range<usize>> {
+    let (start, end) = flag
+        .split_once(':')
+        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid byte range {flag:?}, expected START:END"))?;
+    if start >= end {
+        return Err(anyhow!(
+            "Invalid byte range {flag:?}, the start must be before the end"
+        ));
+    }
+    Ok(start..end)
+}
+
+/// The ranges of the source code to parse for `--byte-range` or `--range`, in
+/// the coordinates of the whole file.
+pub fn included_ranges(
+    source_code: &[u8],
+    path: &Path,
+    byte_range: Option<&std::ops::Range<usize>>,
+    ranges: &[std::ops::Range<Point>],
+) -> Result<Vec<Range>> {
+    let mut included_ranges = Vec::new();
+    if let Some(range) = byte_range {
+        if range.end > source_code.len() {
+            return Err(anyhow!(
+                "The byte range {}:{} is past the end of {}, which is {} bytes long",
+                range.start,
+                range.end,
+                path.display(),
+                source_code.len()
+            ));
+        }
+        included_ranges.push(Range {
+            start_byte: range.start,
+            end_byte: range.end,
+            start_point: position_for_offset(source_code, range.start)?,
+            end_point: position_for_offset(source_code, range.end)?,
+        });
+    }
+    for range in ranges {
+        let offset = |point: Point| {
+            offset_for_position(source_code, point).with_context(|| {
+                format!(
+                    "The range {}-{} is outside of {}",
+                    point_flag(range.start),
+                    point_flag(range.end),
+                    path.display()
+                )
+            })
+        };
+        included_ranges.push(Range {
+            start_byte: offset(range.start)?,
+            end_byte: offset(range.end)?,
+            start_point: range.start,
+            end_point: range.end,
+        });
+    }
+    Ok(included_ranges)
+}
+
+fn point_flag(point: Point) -> String {
+    format!("{}:{}", point.row, point.column)
+}
+
 pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> Result<InputEdit> {
     let start_byte = edit.position;
     let old_end_byte = edit.position + edit.deleted_length;
@@ -396,17 +1528,50 @@ pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> Result
     Ok(edit)
 }
 
+/// Apply the given edits to a copy of `tree` and to `source_code`, then reparse
+/// incrementally, returning the new tree and the ranges whose syntax changed.
+pub fn reparse_with_edits(
+    parser: &mut Parser,
+    tree: &Tree,
+    source_code: &mut Vec<u8>,
+    edits: &[Edit],
+) -> Result<(Tree, Vec<Range>)> {
+    let mut old_tree = tree.clone();
+    for edit in edits {
+        perform_edit(&mut old_tree, source_code, edit)?;
+    }
+    let new_tree = parser
+        .parse(&source_code, Some(&old_tree))
+        .ok_or_else(|| anyhow!("Failed to reparse after applying edits"))?;
+    let changed_ranges = old_tree.changed_ranges(&new_tree).collect();
+    Ok((new_tree, changed_ranges))
+}
+
+fn changed_ranges_json(ranges: &[Range]) -> Value {
+    ranges
+        .iter()
+        .map(|range| {
+            json!({
+                "start_byte": range.start_byte,
+                "end_byte": range.end_byte,
+                "start": {"row": range.start_point.row, "column": range.start_point.column},
+                "end": {"row": range.end_point.row, "column": range.end_point.column},
+            })
+        })
+        .collect()
+}
+
 fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
     let error = || {
         anyhow!(concat!(
             "Invalid edit string '{}'. ",
-            "Edit strings must match the pattern '<START_BYTE_OR_POSITION> <REMOVED_LENGTH> <NEW_TEXT>'"
+            "Edit strings must match the pattern '<START_BYTE_OR_POSITION> <REMOVED_LENGTH_OR_END_POSITION> <NEW_TEXT>'"
         ), flag)
     };
 
     // Three whitespace-separated parts:
     // * edit position
-    // * deleted length
+    // * deleted length or end position
     // * inserted text
     let mut parts = flag.split(' ');
     let position = parts.next().ok_or_else(error)?;
@@ -414,21 +1579,32 @@ fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
     let inserted_text = parts.collect::<Vec<_>>().join(" ").into_bytes();
 
     // Position can either be a byte_offset or row,column pair, separated by a comma
-    let position = if position == "$" {
-        source_code.len()
-    } else if position.contains(',') {
-        let mut parts = position.split(',');
-        let row = parts.next().ok_or_else(error)?;
-        let row = row.parse::<usize>().map_err(|_| error())?;
-        let column = parts.next().ok_or_else(error)?;
-        let column = column.parse::<usize>().map_err(|_| error())?;
-        offset_for_position(source_code, Point { row, column })?
-    } else {
-        position.parse::<usize>().map_err(|_| error())?
+    let parse_position = |position: &str| -> Result<usize> {
+        if position == "$" {
+            Ok(source_code.len())
+        } else if position.contains(',') {
+            let mut parts = position.split(',');
+            let row = parts.next().ok_or_else(error)?;
+            let row = row.parse::<usize>().map_err(|_| error())?;
+            let column = parts.next().ok_or_else(error)?;
+            let column = column.parse::<usize>().map_err(|_| error())?;
+            offset_for_position(source_code, Point { row, column })
+        } else {
+            position.parse::<usize>().map_err(|_| error())
+        }
     };
+    let position = parse_position(position)?;
 
-    // Deleted length must be a byte count.
-    let deleted_length = deleted_length.parse::<usize>().map_err(|_| error())?;
+    // The deleted region can either be a byte count, or an end position given as
+    // a row,column pair (or `$` for the end of the input).
+    let deleted_length = if deleted_length == "$" || deleted_length.contains(',') {
+        let end = parse_position(deleted_length)?;
+        end.checked_sub(position).ok_or_else(|| {
+            anyhow!("Invalid edit string '{flag}'. The end position precedes the start position")
+        })?
+    } else {
+        deleted_length.parse::<usize>().map_err(|_| error())?
+    };
 
     Ok(Edit {
         position,
@@ -438,31 +1614,21 @@ fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
 }
 
 pub fn offset_for_position(input: &[u8], position: Point) -> Result<usize> {
-    let mut row = 0;
-    let mut offset = 0;
-    let mut iter = memchr::memchr_iter(b'\n', input);
-    loop {
-        if let Some(pos) = iter.next() {
-            if row < position.row {
-                row += 1;
-                offset = pos;
-                continue;
-            }
-        }
-        offset += 1;
-        break;
-    }
-    if position.row - row > 0 {
-        return Err(anyhow!("Failed to address a row: {}", position.row));
+    // Rows are delimited by `\n` alone, the same way tree-sitter counts them, so on
+    // CRLF input the `\r` is the last column of its row.
+    let mut lines = memchr::memchr_iter(b'\n', input);
+    let mut line_start = 0;
+    for _ in 0..position.row {
+        let newline = lines
+            .next()
+            .ok_or_else(|| anyhow!("Failed to address a row: {}", position.row))?;
+        line_start = newline + 1;
     }
-    if let Some(pos) = iter.next() {
-        if (pos - offset < position.column) || (input[offset] == b'\n' && position.column > 0) {
-            return Err(anyhow!("Failed to address a column: {}", position.column));
-        };
-    } else if input.len() - offset < position.column {
-        return Err(anyhow!("Failed to address a column over the end"));
+    let line_end = lines.next().unwrap_or(input.len());
+    if line_end - line_start < position.column {
+        return Err(anyhow!("Failed to address a column: {}", position.column));
     }
-    Ok(offset + position.column)
+    Ok(line_start + position.column)
 }
 
 pub fn position_for_offset(input: &[u8], offset: usize) -> Result<Point> {
@@ -482,3 +1648,254 @@ pub fn position_for_offset(input: &[u8], offset: usize) -> Result<Point> {
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_kind_histogram() {
+        let mut stats = Stats {
+            total_parses: 2,
+            successful_parses: 2,
+            ..Default::default()
+        };
+        for (kind, count) in [
+            ("identifier", 12),
+            ("call", 3),
+            ("string", 3),
+            ("number", 1),
+        ] {
+            stats.kind_counts.insert(kind.to_string(), count);
+        }
+        assert!(!stats.to_string().contains("node kinds"));
+
+        stats.top_kind_count = Some(3);
+        let histogram = stats.to_string();
+        assert_eq!(
+            histogram.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "Most frequent node kinds:",
+                "  12 identifier",
+                "   3 call",
+                "   3 string",
+                "  ... and 1 more kinds",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_deepest_path() {
+        let mut stats = Stats {
+            total_parses: 1,
+            successful_parses: 1,
+            ..Default::default()
+        };
+        assert!(!stats.to_string().contains("Maximum depth"));
+
+        let kinds = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+        stats.deepest_path = Some((
+            "a.js".to_string(),
+            kinds(&["program", "call", "arguments", "\"(\""]),
+        ));
+        assert!(stats
+            .to_string()
+            .ends_with("Maximum depth: 4 in a.js\n  program > call > arguments > \"(\"\n"));
+
+        let mut names = vec!["program"];
+        names.extend(["parenthesized"; 24]);
+        names.push("number");
+        stats.deepest_path = Some(("b.js".to_string(), kinds(&names)));
+        let summary = stats.to_string();
+        let summary = summary.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(summary[0], "Maximum depth: 26 in b.js");
+        assert!(summary[1].starts_with("  program > parenthesized > "));
+        assert!(summary[1].contains(" > ... 6 more ... > "));
+        assert!(summary[1].ends_with(" > parenthesized > number"));
+    }
+
+    #[test]
+    fn test_write_unified_diff() {
+        let diff = |old: &str, new: &str| {
+            let mut out = Vec::new();
+            let differs = write_unified_diff(&mut out, ("old", old), ("new", new)).unwrap();
+            (differs, String::from_utf8(out).unwrap())
+        };
+        assert_eq!(diff("a\nb\n", "a\nb\n"), (false, String::new()));
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n17\n";
+        assert_eq!(
+            diff(old, new),
+            (
+                true,
+                [
+                    "--- old",
+                    "+++ new",
+                    "@@ -1,6 +1,6 @@",
+                    " 1",
+                    " 2",
+                    "-3",
+                    "+three",
+                    " 4",
+                    " 5",
+                    " 6",
+                    "@@ -14,3 +14,4 @@",
+                    " 14",
+                    " 15",
+                    " 16",
+                    "+17",
+                    "",
+                ]
+                .join("\n")
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_point_flag() {
+        assert_eq!(parse_point_flag("10:4").unwrap(), Point::new(10, 4));
+        assert_eq!(parse_point_flag("0:0").unwrap(), Point::new(0, 0));
+        assert!(parse_point_flag("10").is_err());
+        assert!(parse_point_flag("10,4").is_err());
+        assert!(parse_point_flag("a:4").is_err());
+    }
+
+    #[test]
+    fn test_parse_range_flag() {
+        assert_eq!(
+            parse_range_flag("1:4-3:0").unwrap(),
+            Point::new(1, 4)..Point::new(3, 0)
+        );
+        assert!(parse_range_flag("1:4").is_err());
+        assert!(parse_range_flag("1:4-3").is_err());
+        assert_eq!(
+            parse_range_flag("3:0-1:4").unwrap_err().to_string(),
+            "Invalid range \"3:0-1:4\", the start must be before the end"
+        );
+    }
+
+    #[test]
+    fn test_parse_byte_range_flag() {
+        assert_eq!(parse_byte_range_flag("3:10").unwrap(), 3..10);
+        assert!(parse_byte_range_flag("3").is_err());
+        assert!(parse_byte_range_flag("3:x").is_err());
+        assert_eq!(
+            parse_byte_range_flag("10:10").unwrap_err().to_string(),
+            "Invalid byte range \"10:10\", the start must be before the end"
+        );
+        assert!(parse_byte_range_flag("10:3").is_err());
+    }
+
+    #[test]
+    fn test_decode_latin1() {
+        assert_eq!(decode_latin1(b"abc"), b"abc");
+        assert_eq!(decode_latin1(b"caf\xe9 \xa9"), "café ©".as_bytes());
+        assert_eq!(
+            decode_latin1(&[0x00, 0x7f, 0x80, 0xff]),
+            "\0\x7f\u{80}ÿ".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"abc"), (Encoding::Utf8, 0));
+        assert_eq!(detect_encoding(b""), (Encoding::Utf8, 0));
+        assert_eq!(detect_encoding(b"\xef\xbb\xbfabc"), (Encoding::Utf8, 3));
+        assert_eq!(detect_encoding(b"\xff\xfea\0"), (Encoding::Utf16Le, 2));
+        assert_eq!(detect_encoding(b"\xfe\xff\0a"), (Encoding::Utf16Be, 2));
+    }
+
+    #[test]
+    fn test_decode_node_text() {
+        assert_eq!(decode_node_text(b"abc", 0, false).unwrap(), "abc");
+        assert_eq!(
+            decode_node_text(b"ab\xffc", 10, false)
+                .unwrap_err()
+                .to_string(),
+            "Invalid UTF-8 in the node at byte 10, at byte 12"
+        );
+
+        let utf16 = |units: &[u16]| {
+            units
+                .iter()
+                .flat_map(|u| u.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            decode_node_text(&utf16(&[0x61, 0xd83d, 0xde00]), 0, true).unwrap(),
+            "a😀"
+        );
+        assert_eq!(
+            decode_node_text(&utf16(&[0x61, 0xd83d, 0xde00, 0xdc00, 0x62]), 4, true)
+                .unwrap_err()
+                .to_string(),
+            "Invalid UTF-16 in the node at byte 4: unpaired surrogate 0xdc00 at byte 10"
+        );
+    }
+
+    #[test]
+    fn test_parse_edit_flag() {
+        let source = b"abc\ndef\nghi";
+
+        let edit = parse_edit_flag(source, "4 3 xyz").unwrap();
+        assert_eq!(edit.position, 4);
+        assert_eq!(edit.deleted_length, 3);
+        assert_eq!(edit.inserted_text, b"xyz");
+
+        let edit = parse_edit_flag(source, "1,0 3 x y").unwrap();
+        assert_eq!(edit.position, 4);
+        assert_eq!(edit.deleted_length, 3);
+        assert_eq!(edit.inserted_text, b"x y");
+
+        let edit = parse_edit_flag(source, "1,1 2,2 ").unwrap();
+        assert_eq!(edit.position, 5);
+        assert_eq!(edit.deleted_length, 5);
+        assert_eq!(edit.inserted_text, b"");
+
+        let edit = parse_edit_flag(source, "2,1 $ !").unwrap();
+        assert_eq!(edit.position, 9);
+        assert_eq!(edit.deleted_length, 2);
+        assert_eq!(edit.inserted_text, b"!");
+
+        assert!(parse_edit_flag(source, "1,2 0,1 x").is_err());
+        assert!(parse_edit_flag(source, "1,2 three x").is_err());
+    }
+
+    #[test]
+    fn test_offset_for_position() {
+        let source = b"abc\n\ndef";
+        assert_eq!(offset_for_position(source, Point::new(0, 0)).unwrap(), 0);
+        assert_eq!(offset_for_position(source, Point::new(0, 2)).unwrap(), 2);
+        assert_eq!(offset_for_position(source, Point::new(0, 3)).unwrap(), 3);
+        assert_eq!(offset_for_position(source, Point::new(1, 0)).unwrap(), 4);
+        assert_eq!(offset_for_position(source, Point::new(2, 3)).unwrap(), 8);
+        assert!(offset_for_position(source, Point::new(0, 4)).is_err());
+        assert!(offset_for_position(source, Point::new(1, 1)).is_err());
+        assert!(offset_for_position(source, Point::new(2, 4)).is_err());
+        assert!(offset_for_position(source, Point::new(3, 0)).is_err());
+    }
+
+    #[test]
+    fn test_edit_positions_with_crlf_line_endings() {
+        let source = b"ab\r\ncd\r\n\r\nef";
+        assert_eq!(offset_for_position(source, Point::new(0, 2)).unwrap(), 2);
+        assert_eq!(offset_for_position(source, Point::new(1, 0)).unwrap(), 4);
+        assert_eq!(offset_for_position(source, Point::new(1, 1)).unwrap(), 5);
+        assert_eq!(offset_for_position(source, Point::new(2, 0)).unwrap(), 8);
+        assert_eq!(offset_for_position(source, Point::new(3, 2)).unwrap(), 12);
+        assert!(offset_for_position(source, Point::new(1, 4)).is_err());
+
+        for offset in 0..=source.len() {
+            let position = position_for_offset(source, offset).unwrap();
+            assert_eq!(offset_for_position(source, position).unwrap(), offset);
+        }
+
+        let edit = parse_edit_flag(source, "1,0 2 xy").unwrap();
+        assert_eq!(edit.position, 4);
+        assert_eq!(
+            &source[edit.position..edit.position + edit.deleted_length],
+            b"cd"
+        );
+    }
+}
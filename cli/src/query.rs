@@ -1,87 +1,432 @@
-use crate::query_testing;
-use anyhow::{Context, Result};
+use crate::{query_testing, util};
+use anyhow::{anyhow, Context, Error, Result};
+use regex::Regex;
 use std::{
-    fs,
+    collections::{BTreeMap, HashMap},
     io::{self, Write},
     ops::Range,
     path::Path,
     time::Instant,
 };
-use tree_sitter::{Language, Parser, Point, Query, QueryCursor};
+use tree_sitter::{Language, Node, Parser, Point, Query, QueryCapture, QueryCursor};
+use tree_sitter_loader::Loader;
 
-#[allow(clippy::too_many_arguments)]
+/// The order in which `query --sort` prints the captures of each file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureOrder {
+    Position,
+    Name,
+    Pattern,
+}
+
+impl CaptureOrder {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "position" => Ok(Self::Position),
+            "name" => Ok(Self::Name),
+            "pattern" => Ok(Self::Pattern),
+            _ => Err(anyhow!(
+                "Invalid sort order {name:?}. Expected one of: position, name, pattern"
+            )),
+        }
+    }
+
+    /// Sort captures, given as their pattern index, capture index, capture name and
+    /// node. Ties keep the order the captures were found in, and captures at the same
+    /// position are sorted from the outermost node in.
+    pub fn sort(self, captures: &mut [(usize, u32, &str, Node)]) {
+        match self {
+            Self::Position => captures
+                .sort_by_key(|(.., node)| (node.start_byte(), std::cmp::Reverse(node.end_byte()))),
+            Self::Name => captures.sort_by_key(|(_, _, name, _)| *name),
+            Self::Pattern => captures.sort_by_key(|(pattern_index, ..)| *pattern_index),
+        }
+    }
+}
+
+/// How `query` prints the captures of each file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The captures grouped by match, or listed in order with `--captures`.
+    #[default]
+    Text,
+    /// One row per capture, in aligned columns, for pasting elsewhere.
+    Table,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "text" => Ok(Self::Text),
+            "table" => Ok(Self::Table),
+            _ => Err(anyhow!(
+                "Invalid output format {name:?}. Expected one of: text, table"
+            )),
+        }
+    }
+}
+
+/// The options for [`query_files_at_paths`], which mostly correspond to the
+/// flags of `query`.
+pub struct QueryFileOptions<'a> {
+    pub current_dir: &'a Path,
+    pub scope: Option<&'a str>,
+    pub query_path: &'a Path,
+    pub ordered_captures: bool,
+    pub sort: Option<CaptureOrder>,
+    pub format: OutputFormat,
+    pub byte_ranges: &'a [Range<usize>],
+    pub point_range: Option<Range<Point>>,
+    pub match_limit: Option<u32>,
+    pub max_start_depth: Option<u32>,
+    pub should_test: bool,
+    pub text_filter: Option<&'a Regex>,
+    pub patterns: &'a [usize],
+    pub disabled_captures: &'a [String],
+    pub limit: Option<usize>,
+    pub context: Option<usize>,
+    pub capture_kinds: bool,
+    pub quiet: bool,
+    pub print_time: bool,
+    pub debug: bool,
+}
+
+/// Run the query on each of the files, in whichever language each file is written.
+/// The query is compiled once per language, and files in languages for which it
+/// doesn't compile are skipped.
 pub fn query_files_at_paths(
-    language: &Language,
+    loader: &mut Loader,
     paths: Vec<String>,
-    query_path: &Path,
-    ordered_captures: bool,
-    byte_range: Option<Range<usize>>,
-    point_range: Option<Range<Point>>,
-    should_test: bool,
-    quiet: bool,
-    print_time: bool,
+    opts: &QueryFileOptions,
 ) -> Result<()> {
+    let QueryFileOptions {
+        current_dir,
+        scope,
+        query_path,
+        ordered_captures,
+        sort,
+        format,
+        byte_ranges,
+        ref point_range,
+        match_limit,
+        max_start_depth,
+        should_test,
+        text_filter,
+        patterns,
+        disabled_captures,
+        limit,
+        context,
+        capture_kinds,
+        quiet,
+        print_time,
+        debug,
+    } = *opts;
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
-    let query_source = fs::read_to_string(query_path)
-        .with_context(|| format!("Error reading query file {query_path:?}"))?;
-    let query = Query::new(language, &query_source).with_context(|| "Query compilation failed")?;
+    let query_name = if query_path == Path::new(util::STDIN_PATH) {
+        "stdin".to_string()
+    } else {
+        format!("{query_path:?}")
+    };
+    let query_source = String::from_utf8(util::read_source(query_path)?)
+        .with_context(|| format!("Error reading the query from {query_name}"))?;
+
+    // Select the language of every file, and compile the query for each of those
+    // languages, before any file is queried.
+    let mut files = Vec::new();
+    for path in paths {
+        let language_path = util::language_detection_path(Path::new(&path));
+        let language_contents = util::language_detection_contents(Path::new(&path))?;
+        match loader.select_language_with_contents(
+            &language_path,
+            current_dir,
+            scope,
+            language_contents.as_deref(),
+        ) {
+            Ok(language) => files.push((path, language)),
+            Err(e) if scope.is_none() => eprintln!("Warning: skipping {path:?}, {e}"),
+            Err(e) => return Err(e),
+        }
+    }
+    if files.is_empty() {
+        return Err(anyhow!(
+            "There are no files to query, the language of each of them is unknown"
+        ));
+    }
+    let mut queries = HashMap::<Language, Query>::new();
+    let mut compile_errors = Vec::<(&String, &Language, Error)>::new();
+    for (path, language) in &files {
+        if queries.contains_key(language) || compile_errors.iter().any(|(_, l, _)| l == &language) {
+            continue;
+        }
+        match Query::new(language, &query_source) {
+            Ok(mut query) => {
+                // The patterns and captures of the query are the same in every
+                // language, so bad values of these flags are reported only once.
+                let enabled_patterns = enable_only_patterns(&mut query, &query_source, patterns)?;
+                disable_captures(&mut query, disabled_captures)?;
+                if queries.is_empty() {
+                    if let Some(enabled_patterns) = enabled_patterns {
+                        eprintln!("{enabled_patterns}");
+                    }
+                }
+                queries.insert(language.clone(), query);
+            }
+            Err(e) => {
+                let e =
+                    Error::from(e).context(format!("Query compilation failed for {query_name}"));
+                compile_errors.push((path, language, e));
+            }
+        }
+    }
+    // A query that doesn't compile for one of the languages is an error with
+    // `--test`, or when it compiles for none of them. Otherwise the files in that
+    // language are skipped.
+    if should_test || queries.is_empty() {
+        let mut compile_errors = compile_errors.into_iter().map(|(_, _, e)| e);
+        if let Some(error) = compile_errors.next() {
+            return Err(compile_errors.fold(error, |error, other| anyhow!("{error:#}\n{other:#}")));
+        }
+    } else {
+        for (path, _, e) in &compile_errors {
+            eprintln!("Warning: skipping the files in the language of {path:?}, {e:#}");
+        }
+    }
 
     let mut query_cursor = QueryCursor::new();
-    if let Some(range) = byte_range {
-        query_cursor.set_byte_range(range);
+    // The cursor can only be limited to one range, so with several ranges, it runs
+    // over all of them, and matches without a capture in any of them are skipped.
+    if let (Some(start), Some(end)) = (
+        byte_ranges.iter().map(|range| range.start).min(),
+        byte_ranges.iter().map(|range| range.end).max(),
+    ) {
+        query_cursor.set_byte_range(start..end);
     }
+    let is_in_byte_ranges = |captures: &[QueryCapture]| {
+        byte_ranges.len() <= 1
+            || captures.iter().any(|capture| {
+                let (start, end) = (capture.node.start_byte(), capture.node.end_byte());
+                byte_ranges.iter().any(|range| {
+                    (start < range.end && range.start < end)
+                        || (start == end && range.contains(&start))
+                })
+            })
+    };
     if let Some(range) = point_range {
-        query_cursor.set_point_range(range);
+        query_cursor.set_point_range(range.clone());
+    }
+    if let Some(limit) = match_limit {
+        query_cursor.set_match_limit(limit);
+    }
+    query_cursor.set_max_start_depth(max_start_depth);
+    if debug {
+        let describe = |limit: Option<u32>| {
+            limit
+                .filter(|limit| *limit != u32::MAX)
+                .map_or_else(|| "none".to_string(), |limit| limit.to_string())
+        };
+        eprintln!(
+            "Match limit: {}, max start depth: {}",
+            describe(Some(query_cursor.match_limit())),
+            describe(max_start_depth)
+        );
     }
 
+    // With `--capture-kinds`, the number of nodes of each kind that each capture
+    // matched in all of the files, which are printed at the end.
+    let mut kinds_by_capture = capture_kinds.then(BTreeMap::<String, BTreeMap<String, usize>>::new);
+    let mut count_kind = |capture_name: &str, node: Node| {
+        if let Some(kinds_by_capture) = &mut kinds_by_capture {
+            let kind = if node.is_named() {
+                node.kind().to_string()
+            } else {
+                format!("{:?}", node.kind())
+            };
+            *kinds_by_capture
+                .entry(capture_name.to_string())
+                .or_default()
+                .entry(kind)
+                .or_default() += 1;
+        }
+    };
+
     let mut parser = Parser::new();
-    parser.set_language(language)?;
 
-    for path in paths {
+    for (path, language) in &files {
+        let Some(query) = queries.get(language) else {
+            continue;
+        };
+        parser.set_language(language)?;
+
         let mut results = Vec::new();
 
         writeln!(&mut stdout, "{path}")?;
 
-        let source_code =
-            fs::read(&path).with_context(|| format!("Error reading source file {path:?}"))?;
+        let source_code = util::read_source(Path::new(path))?;
         let tree = parser.parse(&source_code, None).unwrap();
 
+        // Captures whose text doesn't match `--grep` are still tested, but not printed.
+        let mut filtered_count = 0;
+        let mut is_shown = |text: &str| {
+            let shown = text_filter.map_or(true, |regex| regex.is_match(text));
+            if !shown {
+                filtered_count += 1;
+            }
+            shown
+        };
+
+        // Past `--limit`, captures are still tested and counted, but not printed.
+        let mut printed_count = 0;
+        let mut truncated_count = 0;
+        let mut is_printed = |shown: bool| {
+            if !shown || quiet {
+                false
+            } else if limit.map_or(true, |limit| printed_count < limit) {
+                printed_count += 1;
+                true
+            } else {
+                truncated_count += 1;
+                false
+            }
+        };
+
+        // With `--context`, the source lines around each printed capture follow it.
+        let mut source_context =
+            context.map(|context| util::SourceContext::new(&source_code, context));
+
+        // In the table format, the printed captures are collected as rows, and
+        // printed once the widths of the columns are known.
+        let mut table = (format == OutputFormat::Table).then(Vec::new);
+
         let start = Instant::now();
         if ordered_captures {
             for (mat, capture_index) in
-                query_cursor.captures(&query, tree.root_node(), source_code.as_slice())
+                query_cursor.captures(query, tree.root_node(), source_code.as_slice())
             {
+                if !is_in_byte_ranges(mat.captures) {
+                    continue;
+                }
                 let capture = mat.captures[capture_index];
                 let capture_name = &query.capture_names()[capture.index as usize];
-                if !quiet {
-                    writeln!(
-                        &mut stdout,
-                        "    pattern: {:>2}, capture: {} - {capture_name}, start: {}, end: {}, text: `{}`",
-                        mat.pattern_index,
-                        capture.index,
-                        capture.node.start_position(),
-                        capture.node.end_position(),
-                        capture.node.utf8_text(&source_code).unwrap_or("")
-                    )?;
+                let text = capture.node.utf8_text(&source_code).unwrap_or("");
+                if is_printed(is_shown(text)) {
+                    if let Some(table) = &mut table {
+                        table.push(table_row(
+                            mat.pattern_index,
+                            capture.index,
+                            capture_name,
+                            capture.node,
+                            text,
+                        ));
+                    } else {
+                        writeln!(
+                            &mut stdout,
+                            "    pattern: {:>2}, capture: {} - {capture_name}, start: {}, end: {}, text: `{}`",
+                            mat.pattern_index,
+                            capture.index,
+                            capture.node.start_position(),
+                            capture.node.end_position(),
+                            text
+                        )?;
+                        if let Some(source_context) = &mut source_context {
+                            source_context.write(
+                                &mut stdout,
+                                "      ",
+                                capture.node.start_position().row,
+                                capture.node.end_position().row,
+                            )?;
+                        }
+                    }
                 }
+                count_kind(capture_name, capture.node);
                 results.push(query_testing::CaptureInfo {
                     name: (*capture_name).to_string(),
                     start: capture.node.start_position(),
                     end: capture.node.end_position(),
                 });
             }
+        } else if let Some(sort) = sort {
+            // Collect the captures of all of the matches, so that they can be sorted
+            // before they are printed.
+            let mut captures = Vec::new();
+            for m in query_cursor.matches(query, tree.root_node(), source_code.as_slice()) {
+                if !is_in_byte_ranges(m.captures) {
+                    continue;
+                }
+                for capture in m.captures {
+                    let capture_name = query.capture_names()[capture.index as usize];
+                    let text = capture.node.utf8_text(&source_code).unwrap_or("");
+                    if !quiet && is_shown(text) {
+                        captures.push((m.pattern_index, capture.index, capture_name, capture.node));
+                    }
+                    count_kind(capture_name, capture.node);
+                    results.push(query_testing::CaptureInfo {
+                        name: capture_name.to_string(),
+                        start: capture.node.start_position(),
+                        end: capture.node.end_position(),
+                    });
+                }
+            }
+            sort.sort(&mut captures);
+            for (pattern_index, capture_index, capture_name, node) in captures {
+                if is_printed(true) {
+                    let text = node.utf8_text(&source_code).unwrap_or("");
+                    if let Some(table) = &mut table {
+                        table.push(table_row(
+                            pattern_index,
+                            capture_index,
+                            capture_name,
+                            node,
+                            text,
+                        ));
+                    } else {
+                        writeln!(
+                            &mut stdout,
+                            "    pattern: {pattern_index:>2}, capture: {capture_index} - {capture_name}, start: {}, end: {}, text: `{text}`",
+                            node.start_position(),
+                            node.end_position(),
+                        )?;
+                        if let Some(source_context) = &mut source_context {
+                            source_context.write(
+                                &mut stdout,
+                                "      ",
+                                node.start_position().row,
+                                node.end_position().row,
+                            )?;
+                        }
+                    }
+                }
+            }
         } else {
-            for m in query_cursor.matches(&query, tree.root_node(), source_code.as_slice()) {
-                if !quiet {
+            for m in query_cursor.matches(query, tree.root_node(), source_code.as_slice()) {
+                if !is_in_byte_ranges(m.captures) {
+                    continue;
+                }
+                let printed_captures = m
+                    .captures
+                    .iter()
+                    .map(|capture| {
+                        is_printed(is_shown(capture.node.utf8_text(&source_code).unwrap_or("")))
+                    })
+                    .collect::<Vec<_>>();
+                if table.is_none() && printed_captures.contains(&true) {
                     writeln!(&mut stdout, "  pattern: {}", m.pattern_index)?;
                 }
-                for capture in m.captures {
+                for (capture, printed) in m.captures.iter().zip(printed_captures) {
                     let start = capture.node.start_position();
                     let end = capture.node.end_position();
                     let capture_name = &query.capture_names()[capture.index as usize];
-                    if !quiet {
+                    if let (true, Some(table)) = (printed, &mut table) {
+                        let text = capture.node.utf8_text(&source_code).unwrap_or("");
+                        table.push(table_row(
+                            m.pattern_index,
+                            capture.index,
+                            capture_name,
+                            capture.node,
+                            text,
+                        ));
+                    } else if printed {
                         if end.row == start.row {
                             writeln!(
                                 &mut stdout,
@@ -95,7 +440,11 @@ pub fn query_files_at_paths(
                                 "    capture: {capture_name}, start: {start}, end: {end}",
                             )?;
                         }
+                        if let Some(source_context) = &mut source_context {
+                            source_context.write(&mut stdout, "      ", start.row, end.row)?;
+                        }
                     }
+                    count_kind(capture_name, capture.node);
                     results.push(query_testing::CaptureInfo {
                         name: (*capture_name).to_string(),
                         start: capture.node.start_position(),
@@ -104,6 +453,18 @@ pub fn query_files_at_paths(
                 }
             }
         }
+        if let Some(table) = table {
+            write_table(&mut stdout, &table)?;
+        }
+        if !quiet && filtered_count > 0 {
+            writeln!(
+                &mut stdout,
+                "  {filtered_count} capture(s) filtered out by --grep"
+            )?;
+        }
+        if truncated_count > 0 {
+            writeln!(&mut stdout, "  ... (truncated, {truncated_count} more)")?;
+        }
         if query_cursor.did_exceed_match_limit() {
             writeln!(
                 &mut stdout,
@@ -111,12 +472,185 @@ pub fn query_files_at_paths(
             )?;
         }
         if should_test {
-            query_testing::assert_expected_captures(&results, path, &mut parser, language)?;
+            query_testing::assert_expected_captures(&results, path.clone(), &mut parser, language)?;
         }
         if print_time {
             writeln!(&mut stdout, "{:?}", start.elapsed())?;
         }
     }
 
+    if let Some(kinds_by_capture) = kinds_by_capture {
+        writeln!(&mut stdout, "Node kinds by capture:")?;
+        for (capture_name, kinds) in kinds_by_capture {
+            let kinds = kinds
+                .into_iter()
+                .map(|(kind, count)| format!("{kind} ({count})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(&mut stdout, "  @{capture_name}: {kinds}")?;
+        }
+    }
+
     Ok(())
 }
+
+/// Disable every pattern of the query except for the given ones, and describe which
+/// patterns remain enabled. An empty list leaves all of the patterns enabled.
+fn enable_only_patterns(
+    query: &mut Query,
+    query_source: &str,
+    patterns: &[usize],
+) -> Result<Option<String>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let pattern_count = query.pattern_count();
+    if let Some(pattern) = patterns.iter().find(|pattern| **pattern >= pattern_count) {
+        return Err(anyhow!(
+            "There is no pattern {pattern}, the query has {pattern_count} patterns"
+        ));
+    }
+    for pattern in 0..pattern_count {
+        if !patterns.contains(&pattern) {
+            query.disable_pattern(pattern);
+        }
+    }
+    let mut enabled = patterns.to_vec();
+    enabled.sort_unstable();
+    enabled.dedup();
+    let enabled = enabled
+        .into_iter()
+        .map(|pattern| {
+            let start_byte = query.start_byte_for_pattern(pattern);
+            let row = query_source[..start_byte].matches('\n').count();
+            format!("{pattern} (line {})", row + 1)
+        })
+        .collect::<Vec<_>>();
+    Ok(Some(format!(
+        "Running {} of {pattern_count} patterns: {}",
+        enabled.len(),
+        enabled.join(", ")
+    )))
+}
+
+/// Remove the given captures from all of the query's patterns.
+fn disable_captures(query: &mut Query, names: &[String]) -> Result<()> {
+    for name in names {
+        if !query.capture_names().contains(&name.as_str()) {
+            return Err(anyhow!(
+                "There is no capture named `@{name}`. Expected one of: {}",
+                query
+                    .capture_names()
+                    .iter()
+                    .map(|name| format!("@{name}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        query.disable_capture(name);
+    }
+    Ok(())
+}
+
+const TABLE_HEADER: [&str; 6] = ["start", "end", "pattern", "capture", "name", "text"];
+
+fn table_row(
+    pattern_index: usize,
+    capture_index: u32,
+    capture_name: &str,
+    node: Node,
+    text: &str,
+) -> [String; 6] {
+    [
+        node.start_position().to_string(),
+        node.end_position().to_string(),
+        pattern_index.to_string(),
+        capture_index.to_string(),
+        capture_name.to_string(),
+        // Keep every capture on one row.
+        text.escape_debug().to_string(),
+    ]
+}
+
+/// Print the rows in columns separated by ` | `, each padded to its widest cell.
+/// The last column isn't padded, so that lines have no trailing whitespace.
+fn write_table(out: &mut impl Write, rows: &[[String; 6]]) -> io::Result<()> {
+    let mut widths = TABLE_HEADER.map(|header| header.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = TABLE_HEADER.map(ToString::to_string);
+    for row in std::iter::once(&header).chain(rows) {
+        let mut line = String::new();
+        for (i, (cell, width)) in row.iter().zip(widths).enumerate() {
+            if i + 1 < row.len() {
+                line += &format!("{cell:width$} | ");
+            } else {
+                line += cell;
+            }
+        }
+        writeln!(out, "  {}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_order_from_name() {
+        assert_eq!(
+            CaptureOrder::from_name("position").unwrap(),
+            CaptureOrder::Position
+        );
+        assert_eq!(CaptureOrder::from_name("name").unwrap(), CaptureOrder::Name);
+        assert_eq!(
+            CaptureOrder::from_name("pattern").unwrap(),
+            CaptureOrder::Pattern
+        );
+        assert!(CaptureOrder::from_name("capture").is_err());
+    }
+
+    #[test]
+    fn test_output_format_from_name() {
+        assert_eq!(OutputFormat::from_name("text").unwrap(), OutputFormat::Text);
+        assert_eq!(
+            OutputFormat::from_name("table").unwrap(),
+            OutputFormat::Table
+        );
+        assert_eq!(
+            OutputFormat::from_name("json").unwrap_err().to_string(),
+            "Invalid output format \"json\". Expected one of: text, table"
+        );
+    }
+
+    #[test]
+    fn test_write_table() {
+        let row = |cells: [&str; 6]| cells.map(ToString::to_string);
+        let rows = [
+            row(["(0, 0)", "(0, 1)", "0", "1", "id", "a"]),
+            row(["(10, 4)", "(12, 0)", "12", "0", "function", "f() {\\n}"]),
+        ];
+        let mut out = Vec::new();
+        write_table(&mut out, &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "  start   | end     | pattern | capture | name     | text\n",
+                "  (0, 0)  | (0, 1)  | 0       | 1       | id       | a\n",
+                "  (10, 4) | (12, 0) | 12      | 0       | function | f() {\\n}\n",
+            )
+        );
+
+        // Without any rows, the columns are as wide as their headers.
+        let mut out = Vec::new();
+        write_table(&mut out, &[]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "  start | end | pattern | capture | name | text\n"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use ansi_term::Colour;
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -150,27 +151,115 @@ pub fn parse_position_comments(
     Ok(result)
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct CaptureMismatch {
+    pub position: Point,
+    pub expected: String,
+    pub actual: String,
+}
+
 pub fn assert_expected_captures(
     infos: &[CaptureInfo],
     path: String,
     parser: &mut Parser,
     language: &Language,
 ) -> Result<()> {
-    let contents = fs::read_to_string(path)?;
+    let contents = fs::read_to_string(&path)?;
     let pairs = parse_position_comments(parser, language, contents.as_bytes())?;
-    for info in infos {
-        if let Some(found) = pairs.iter().find(|p| {
-            p.position.row == info.start.row && p.position >= info.start && p.position < info.end
-        }) {
-            if found.expected_capture_name != info.name && info.name != "name" {
-                Err(anyhow!(
-                    "Assertion failed: at {}, found {}, expected {}",
-                    info.start,
-                    found.expected_capture_name,
-                    info.name
-                ))?;
-            }
-        }
+    let mismatches = infos
+        .iter()
+        .filter_map(|info| {
+            let found = pairs.iter().find(|p| {
+                p.position.row == info.start.row
+                    && p.position >= info.start
+                    && p.position < info.end
+            })?;
+            (found.expected_capture_name != info.name && info.name != "name").then(|| {
+                CaptureMismatch {
+                    position: info.start,
+                    expected: found.expected_capture_name.clone(),
+                    actual: info.name.clone(),
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Assertion failed in {path}:\n{}",
+            format_capture_mismatches(&mismatches)
+        ))
+    }
+}
+
+/// Lay out the expected and actual captures side by side, one row per mismatch.
+#[must_use]
+pub fn format_capture_mismatches(mismatches: &[CaptureMismatch]) -> String {
+    let positions = mismatches
+        .iter()
+        .map(|m| m.position.to_string())
+        .collect::<Vec<_>>();
+    let position_width = positions.iter().map(String::len).max().unwrap_or(0);
+    let expected_width = mismatches
+        .iter()
+        .map(|m| m.expected.len())
+        .chain(Some("expected".len()))
+        .max()
+        .unwrap_or(0);
+
+    // Pad before painting, since the color codes would count towards the width.
+    let mut result = format!(
+        "  {:position_width$}  {}  {}\n",
+        "",
+        Colour::Green.paint(format!("{:expected_width$}", "expected")),
+        Colour::Red.paint("actual"),
+    );
+    for (mismatch, position) in mismatches.iter().zip(positions) {
+        result += &format!(
+            "  {position:position_width$}  {}  {}\n",
+            Colour::Green.paint(format!("{:expected_width$}", mismatch.expected)),
+            Colour::Red.paint(&mismatch.actual),
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_capture_mismatches() {
+        let mismatches = [
+            CaptureMismatch {
+                position: Point::new(1, 4),
+                expected: "function".to_string(),
+                actual: "variable".to_string(),
+            },
+            CaptureMismatch {
+                position: Point::new(12, 10),
+                expected: "type".to_string(),
+                actual: "constructor".to_string(),
+            },
+        ];
+        let green = |s: &str| Colour::Green.paint(s).to_string();
+        let red = |s: &str| Colour::Red.paint(s).to_string();
+        assert_eq!(
+            format_capture_mismatches(&mismatches),
+            format!(
+                concat!(
+                    "            {}  {}\n",
+                    "  (1, 4)    {}  {}\n",
+                    "  (12, 10)  {}  {}\n",
+                ),
+                green("expected"),
+                red("actual"),
+                green("function"),
+                red("variable"),
+                green("type    "),
+                red("constructor"),
+            )
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,55 @@
+use super::parse::{reparse_with_edits, write_sexp, Edit, SexpFlags};
+use anyhow::{anyhow, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+use tree_sitter::{Language, Parser, Tree};
+
+/// Read snippets from stdin, one per line, and print the syntax tree of each. In
+/// incremental mode, every line is appended to the text read so far, and that text
+/// is reparsed by editing the previous tree.
+pub fn run(language: &Language, incremental: bool) -> Result<()> {
+    let mut parser = Parser::new();
+    parser.set_language(language)?;
+
+    let stdin = io::stdin();
+    let show_prompt = stdin.is_terminal();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    let mut source_code = Vec::new();
+    let mut tree = None::<Tree>;
+    let mut lines = stdin.lock().lines();
+    loop {
+        if show_prompt {
+            write!(&mut stdout, "> ")?;
+            stdout.flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let snippet = format!("{}\n", line?).into_bytes();
+
+        let new_tree = match tree.take().filter(|_| incremental) {
+            Some(old_tree) => {
+                let edit = Edit {
+                    position: source_code.len(),
+                    deleted_length: 0,
+                    inserted_text: snippet,
+                };
+                reparse_with_edits(&mut parser, &old_tree, &mut source_code, &[edit])?.0
+            }
+            None => {
+                source_code = snippet;
+                parser
+                    .parse(&source_code, None)
+                    .ok_or_else(|| anyhow!("Failed to parse"))?
+            }
+        };
+        write_sexp(&mut stdout, new_tree.root_node(), SexpFlags::default())?;
+        tree = Some(new_tree);
+    }
+
+    if show_prompt {
+        writeln!(&mut stdout)?;
+    }
+    Ok(())
+}
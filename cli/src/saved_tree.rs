@@ -0,0 +1,252 @@
+use super::parse::{walk_nodes, SexpFlags, SexpNode, SexpWriter, Walk};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tree_sitter::{Point, Tree, TreeCursor};
+
+const MAGIC: &[u8; 8] = b"TSTREE\x00\x02";
+const NO_FIELD: u32 = u32::MAX;
+
+const FLAG_NAMED: u8 = 1 << 0;
+const FLAG_EXTRA: u8 = 1 << 1;
+const FLAG_MISSING: u8 = 1 << 2;
+
+/// A read-only copy of a syntax tree, which can be saved to a file and rendered
+/// again later without the language or the source code. The nodes are stored in
+/// pre-order along with their depths, so that no operation on the tree needs to
+/// recurse.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SavedTree {
+    pub nodes: Vec<SavedNode>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SavedNode {
+    pub depth: usize,
+    pub kind: String,
+    pub field_name: Option<String>,
+    pub field_id: Option<u16>,
+    pub is_named: bool,
+    pub is_extra: bool,
+    pub is_missing: bool,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_position: Point,
+    pub end_position: Point,
+}
+
+impl SavedTree {
+    #[must_use]
+    pub fn from_tree(tree: &Tree) -> Self {
+        let mut nodes = Vec::new();
+        walk_nodes(tree.root_node(), |cursor| {
+            nodes.push(SavedNode::from_cursor(cursor));
+            Walk::Continue
+        });
+        Self { nodes }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        fs::write(path, bytes).with_context(|| format!("Failed to write the tree to {path:?}"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("Failed to read a tree from {path:?}"))?;
+        Self::read(&mut bytes.as_slice()).with_context(|| format!("Invalid tree file {path:?}"))
+    }
+
+    /// Write the tree as a table of the distinct kinds and field names, followed
+    /// by the nodes in pre-order, each referring to those tables by index.
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut strings = StringTable::default();
+        let mut nodes = Vec::new();
+        write_u32(&mut nodes, self.nodes.len())?;
+        for node in &self.nodes {
+            node.write(&mut nodes, &mut strings)?;
+        }
+
+        out.write_all(MAGIC)?;
+        write_u32(out, strings.values.len())?;
+        for value in &strings.values {
+            write_u32(out, value.len())?;
+            out.write_all(value.as_bytes())?;
+        }
+        out.write_all(&nodes)
+    }
+
+    pub fn read(input: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(anyhow!("Not a saved tree"));
+        }
+        let mut strings = Vec::new();
+        for _ in 0..read_u32(input)? {
+            // Read through `take` rather than allocating the length up front, since
+            // it comes from the file and may be far larger than the file itself.
+            let len = read_u32(input)?;
+            let mut value = Vec::new();
+            input.by_ref().take(len.into()).read_to_end(&mut value)?;
+            if value.len() != len as usize {
+                return Err(anyhow!("Unexpected end of file"));
+            }
+            strings.push(String::from_utf8(value)?);
+        }
+        let mut nodes = Vec::<SavedNode>::new();
+        for _ in 0..read_u32(input)? {
+            let node = SavedNode::read(input, &strings)?;
+            let is_valid_depth = match nodes.last() {
+                Some(previous) => (1..=previous.depth + 1).contains(&node.depth),
+                None => node.depth == 0,
+            };
+            if !is_valid_depth {
+                return Err(anyhow!("Invalid node depth {}", node.depth));
+            }
+            nodes.push(node);
+        }
+        if nodes.is_empty() {
+            return Err(anyhow!("The tree has no nodes"));
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Write the tree in the same format as the S-expression output of `parse`.
+    pub fn write_sexp(&self, out: &mut impl Write, flags: SexpFlags) -> io::Result<()> {
+        let mut writer = SexpWriter::new(out, flags);
+        for node in &self.nodes {
+            writer.write_node(&SexpNode {
+                depth: node.depth,
+                kind: &node.kind,
+                field_name: node.field_name.as_deref(),
+                field_id: node.field_id,
+                is_named: node.is_named,
+                is_extra: node.is_extra,
+                start_position: node.start_position,
+                end_position: node.end_position,
+            })?;
+        }
+        writer.finish()
+    }
+}
+
+impl SavedNode {
+    fn from_cursor(cursor: &TreeCursor) -> Self {
+        let node = cursor.node();
+        Self {
+            depth: cursor.depth() as usize,
+            kind: node.kind().to_string(),
+            field_name: cursor.field_name().map(ToString::to_string),
+            field_id: cursor.field_id().map(u16::from),
+            is_named: node.is_named(),
+            is_extra: node.is_extra(),
+            is_missing: node.is_missing(),
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            start_position: node.start_position(),
+            end_position: node.end_position(),
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>, strings: &mut StringTable) -> io::Result<()> {
+        let mut flags = 0;
+        if self.is_named {
+            flags |= FLAG_NAMED;
+        }
+        if self.is_extra {
+            flags |= FLAG_EXTRA;
+        }
+        if self.is_missing {
+            flags |= FLAG_MISSING;
+        }
+        out.push(flags);
+        write_u32(out, strings.index(&self.kind))?;
+        match (&self.field_name, self.field_id) {
+            (Some(field_name), Some(field_id)) => {
+                write_u32(out, strings.index(field_name))?;
+                write_u32(out, field_id.into())?;
+            }
+            _ => out.write_all(&NO_FIELD.to_le_bytes())?,
+        }
+        for value in [
+            self.start_byte,
+            self.end_byte,
+            self.start_position.row,
+            self.start_position.column,
+            self.end_position.row,
+            self.end_position.column,
+            self.depth,
+        ] {
+            write_u32(out, value)?;
+        }
+        Ok(())
+    }
+
+    fn read(input: &mut impl Read, strings: &[String]) -> Result<Self> {
+        let string = |index: u32| {
+            strings
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| anyhow!("Invalid string index {index}"))
+        };
+
+        let mut flags = [0];
+        input.read_exact(&mut flags)?;
+        let kind = string(read_u32(input)?)?;
+        let (field_name, field_id) = match read_u32(input)? {
+            NO_FIELD => (None, None),
+            index => (Some(string(index)?), Some(u16::try_from(read_u32(input)?)?)),
+        };
+        let mut values = [0; 7];
+        for value in &mut values {
+            *value = read_u32(input)? as usize;
+        }
+        let [start_byte, end_byte, start_row, start_column, end_row, end_column, depth] = values;
+        Ok(Self {
+            depth,
+            kind,
+            field_name,
+            field_id,
+            is_named: flags[0] & FLAG_NAMED != 0,
+            is_extra: flags[0] & FLAG_EXTRA != 0,
+            is_missing: flags[0] & FLAG_MISSING != 0,
+            start_byte,
+            end_byte,
+            start_position: Point::new(start_row, start_column),
+            end_position: Point::new(end_row, end_column),
+        })
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    values: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl StringTable {
+    fn index(&mut self, value: &str) -> usize {
+        if let Some(index) = self.indices.get(value) {
+            return *index;
+        }
+        self.values.push(value.to_string());
+        self.indices
+            .insert(value.to_string(), self.values.len() - 1);
+        self.values.len() - 1
+    }
+}
+
+fn write_u32(out: &mut impl Write, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
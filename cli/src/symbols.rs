@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tree_sitter::Language;
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub c_symbol: String,
+}
+
+/// Read the `ts_symbol_identifiers` enum of a generated `parser.c`, mapping each
+/// symbol id to the name of its C constant.
+#[must_use]
+pub fn parse_symbol_identifiers(parser_c: &str) -> BTreeMap<u16, String> {
+    parser_c
+        .lines()
+        .skip_while(|line| !line.starts_with("enum ts_symbol_identifiers {"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("};"))
+        .filter_map(|line| {
+            let (c_symbol, id) = line.trim().trim_end_matches(',').split_once(" = ")?;
+            Some((id.parse().ok()?, c_symbol.to_string()))
+        })
+        .collect()
+}
+
+/// Pair every symbol of the language with its C constant in `parser.c`. The
+/// `end` symbol has no constant of its own, so it is left out.
+pub fn symbol_map(language: &Language, parser_c: &str) -> Result<BTreeMap<u16, SymbolInfo>> {
+    let mut c_symbols = parse_symbol_identifiers(parser_c);
+    let mut result = BTreeMap::new();
+    for id in 1..language.node_kind_count() as u16 {
+        let name = language
+            .node_kind_for_id(id)
+            .unwrap_or_default()
+            .to_string();
+        let c_symbol = c_symbols.remove(&id).ok_or_else(|| {
+            anyhow!("Symbol {id} ({name:?}) is missing from parser.c, it may be out of date")
+        })?;
+        result.insert(id, SymbolInfo { name, c_symbol });
+    }
+    if let Some((id, c_symbol)) = c_symbols.into_iter().next() {
+        return Err(anyhow!(
+            "{c_symbol} = {id} in parser.c is not a symbol of the language, it may be out of date"
+        ));
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ExtraInfo {
+    pub kind: String,
+    pub named: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ExtrasInfo {
+    pub word_token: Option<String>,
+    pub extras: Vec<ExtraInfo>,
+}
+
+/// List the extra tokens and the word token of a language. The compiled language
+/// doesn't record these, so they are read from the `grammar.json` that it was
+/// generated from, and checked against the language's symbols. Extras that are
+/// only patterns, such as whitespace, never produce nodes and are left out, and so
+/// are strings that the grammar's rules don't use anywhere else.
+pub fn extras_info(language: &Language, grammar_json: &str) -> Result<ExtrasInfo> {
+    let grammar = serde_json::from_str::<Value>(grammar_json)?;
+    let check_symbol = |kind: &str, named: bool| {
+        if language.id_for_node_kind(kind, named) == 0 {
+            Err(anyhow!(
+                "{kind:?} is not a symbol of the language, grammar.json may be out of date"
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    let word_token = grammar["word"].as_str().map(ToString::to_string);
+    if let Some(word_token) = &word_token {
+        check_symbol(word_token, true)?;
+    }
+
+    let mut extras = Vec::new();
+    for rule in grammar["extras"].as_array().into_iter().flatten() {
+        let (kind, named) = match rule["type"].as_str() {
+            Some("SYMBOL") => (&rule["name"], true),
+            Some("STRING") => (&rule["value"], false),
+            _ => continue,
+        };
+        let kind = kind
+            .as_str()
+            .ok_or_else(|| anyhow!("Invalid extra rule {rule}"))?;
+        if !named && language.id_for_node_kind(kind, false) == 0 {
+            continue;
+        }
+        check_symbol(kind, named)?;
+        extras.push(ExtraInfo {
+            kind: kind.to_string(),
+            named,
+        });
+    }
+
+    Ok(ExtrasInfo { word_token, extras })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_symbol_identifiers() {
+        let parser_c = concat!(
+            "#define SYMBOL_COUNT 4\n",
+            "\n",
+            "enum ts_symbol_identifiers {\n",
+            "  sym_identifier = 1,\n",
+            "  anon_sym_LPAREN = 2,\n",
+            "  sym__expression = 3,\n",
+            "};\n",
+            "\n",
+            "enum ts_field_identifiers {\n",
+            "  field_name = 1,\n",
+            "};\n",
+        );
+        assert_eq!(
+            parse_symbol_identifiers(parser_c),
+            BTreeMap::from([
+                (1, "sym_identifier".to_string()),
+                (2, "anon_sym_LPAREN".to_string()),
+                (3, "sym__expression".to_string()),
+            ])
+        );
+        assert!(parse_symbol_identifiers("").is_empty());
+    }
+}
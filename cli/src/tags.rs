@@ -1,12 +1,33 @@
 use super::util;
 use anyhow::{anyhow, Result};
+use std::collections::HashSet;
 use std::io::{self, Write};
 use std::path::Path;
+use std::str;
 use std::time::Instant;
-use std::{fs, str};
 use tree_sitter_loader::{Config, Loader};
-use tree_sitter_tags::TagsContext;
+use tree_sitter_tags::{Tag, TagsContext};
 
+/// The order in which `tags --sort` prints the tags of each file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagOrder {
+    Name,
+    Position,
+}
+
+impl TagOrder {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "name" => Ok(Self::Name),
+            "position" => Ok(Self::Position),
+            _ => Err(anyhow!(
+                "Invalid sort order {name:?}. Expected one of: name, position"
+            )),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn generate_tags(
     loader: &Loader,
     loader_config: &Config,
@@ -14,6 +35,8 @@ pub fn generate_tags(
     paths: &[String],
     quiet: bool,
     time: bool,
+    sort: Option<TagOrder>,
+    dedup: bool,
 ) -> Result<()> {
     let mut lang = None;
     if let Some(scope) = scope {
@@ -33,7 +56,15 @@ pub fn generate_tags(
         let (language, language_config) = match lang.clone() {
             Some(v) => v,
             None => {
-                if let Some(v) = loader.language_configuration_for_file_name(path)? {
+                if path == Path::new(util::STDIN_PATH) {
+                    return Err(util::stdin_requires_scope());
+                }
+                let language_path = util::language_detection_path(path);
+                let language_contents = util::language_detection_contents(path)?;
+                if let Some(v) = loader.language_configuration_for_file_name_with_contents(
+                    &language_path,
+                    language_contents.as_deref(),
+                )? {
                     v
                 } else {
                     eprintln!("{}", util::lang_not_found_for_path(path, loader_config));
@@ -52,13 +83,14 @@ pub fn generate_tags(
                 ""
             };
 
-            let source = fs::read(path)?;
+            let source = util::read_source(path)?;
             let t0 = Instant::now();
-            for tag in context
+            let mut tags = context
                 .generate_tags(tags_config, &source, Some(&cancellation_flag))?
                 .0
-            {
-                let tag = tag?;
+                .collect::<Result<Vec<_>, _>>()?;
+            sort_tags(&mut tags, &source, sort, dedup);
+            for tag in tags {
                 if !quiet {
                     write!(
                         &mut stdout,
@@ -91,3 +123,133 @@ pub fn generate_tags(
 
     Ok(())
 }
+
+/// Sort the tags of a file by `order`, and drop the ones with the same name, kind and
+/// range as an earlier tag if `dedup` is set. Ties keep the order the tags were found in.
+fn sort_tags(tags: &mut Vec<Tag>, source: &[u8], order: Option<TagOrder>, dedup: bool) {
+    let position = |tag: &Tag| (tag.range.start, tag.range.end);
+    match order {
+        Some(TagOrder::Name) => tags.sort_by(|a, b| {
+            source[a.name_range.clone()]
+                .cmp(&source[b.name_range.clone()])
+                .then_with(|| position(a).cmp(&position(b)))
+        }),
+        Some(TagOrder::Position) => tags.sort_by_key(position),
+        None => {}
+    }
+    if dedup {
+        let mut seen = HashSet::new();
+        tags.retain(|tag| {
+            seen.insert((
+                &source[tag.name_range.clone()],
+                tag.syntax_type_id,
+                tag.is_definition,
+                position(tag),
+            ))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Point;
+
+    const SOURCE: &[u8] = b"def b(): pass\ndef a(): b()\nclass b: pass\n";
+
+    // A tag whose name is the word at `start`, spanning the rest of its line.
+    fn tag(start: usize, syntax_type_id: u32, is_definition: bool) -> Tag {
+        let name_end = start
+            + SOURCE[start..]
+                .iter()
+                .position(|c| !c.is_ascii_alphabetic())
+                .unwrap();
+        let line_end = start + SOURCE[start..].iter().position(|c| *c == b'\n').unwrap();
+        Tag {
+            range: start..line_end,
+            name_range: start..name_end,
+            line_range: 0..0,
+            span: Point::default()..Point::default(),
+            utf16_column_range: 0..0,
+            docs: None,
+            is_definition,
+            syntax_type_id,
+        }
+    }
+
+    fn sorted(tags: &[Tag], order: Option<TagOrder>, dedup: bool) -> Vec<(usize, u32, bool)> {
+        let mut tags = tags.to_vec();
+        sort_tags(&mut tags, SOURCE, order, dedup);
+        tags.iter()
+            .map(|tag| (tag.range.start, tag.syntax_type_id, tag.is_definition))
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_tags() {
+        // `b` is defined as a function and a class, and `a` calls the function.
+        let tags = [
+            tag(33, 1, true),
+            tag(4, 0, true),
+            tag(23, 0, false),
+            tag(18, 0, true),
+        ];
+
+        assert_eq!(
+            sorted(&tags, None, false),
+            [(33, 1, true), (4, 0, true), (23, 0, false), (18, 0, true)]
+        );
+        assert_eq!(
+            sorted(&tags, Some(TagOrder::Name), false),
+            [(18, 0, true), (4, 0, true), (23, 0, false), (33, 1, true)]
+        );
+        assert_eq!(
+            sorted(&tags, Some(TagOrder::Position), false),
+            [(4, 0, true), (18, 0, true), (23, 0, false), (33, 1, true)]
+        );
+
+        // Tags at the same position keep the order they were found in.
+        let tags = [tag(4, 1, true), tag(4, 0, true), tag(4, 0, false)];
+        for order in [TagOrder::Name, TagOrder::Position] {
+            assert_eq!(
+                sorted(&tags, Some(order), false),
+                [(4, 1, true), (4, 0, true), (4, 0, false)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sort_tags_dedup() {
+        let tags = [
+            tag(4, 0, true),
+            tag(23, 0, false),
+            tag(4, 0, true),
+            // The same name and range, but a different kind.
+            tag(4, 1, true),
+            tag(4, 0, false),
+            // The same name and kind, but a different range.
+            tag(33, 0, true),
+            tag(23, 0, false),
+        ];
+        assert_eq!(
+            sorted(&tags, None, true),
+            [
+                (4, 0, true),
+                (23, 0, false),
+                (4, 1, true),
+                (4, 0, false),
+                (33, 0, true)
+            ]
+        );
+        assert_eq!(
+            sorted(&tags, Some(TagOrder::Position), true),
+            [
+                (4, 0, true),
+                (4, 1, true),
+                (4, 0, false),
+                (23, 0, false),
+                (33, 0, true)
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use super::util;
+use super::{parse, util};
 use ansi_term::Colour;
 use anyhow::{anyhow, Context, Result};
 use difference::{Changeset, Difference};
@@ -12,7 +12,7 @@ use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::str;
-use tree_sitter::{format_sexp, Language, LogType, Parser, Query};
+use tree_sitter::{format_sexp, Language, LogType, Parser, Query, Tree};
 use walkdir::WalkDir;
 
 lazy_static! {
@@ -88,6 +88,37 @@ impl Default for TestAttributes {
     }
 }
 
+/// How the actual tree of each corpus test is rendered before it is compared with
+/// the expected output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompareOutput {
+    /// The S-expression of the tree's root node.
+    #[default]
+    Tree,
+    /// The output of `parse`, without the positions of the nodes.
+    SExpression,
+}
+
+impl CompareOutput {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "tree" => Ok(Self::Tree),
+            "s-expression" => Ok(Self::SExpression),
+            _ => Err(anyhow!(
+                "Invalid output format {name:?}. Expected one of: tree, s-expression"
+            )),
+        }
+    }
+
+    /// Render the actual tree of a corpus test in this format.
+    pub fn render(self, tree: &Tree) -> Result<String> {
+        match self {
+            Self::Tree => Ok(tree.root_node().to_sexp()),
+            Self::SExpression => render_parse_output(tree),
+        }
+    }
+}
+
 pub struct TestOptions<'a> {
     pub path: PathBuf,
     pub debug: bool,
@@ -98,10 +129,12 @@ pub struct TestOptions<'a> {
     pub update: bool,
     pub open_log: bool,
     pub languages: BTreeMap<&'a str, &'a Language>,
+    pub extensions: &'a [String],
+    pub compare_output: CompareOutput,
 }
 
 pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<()> {
-    let test_entry = parse_tests(&opts.path)?;
+    let test_entry = parse_tests(&opts.path, opts.extensions)?;
     let mut _log_session = None;
 
     if opts.debug_graph {
@@ -276,7 +309,7 @@ fn run_tests(
                         return Ok(false);
                     }
                 } else {
-                    let mut actual = tree.root_node().to_sexp();
+                    let mut actual = opts.compare_output.render(&tree)?;
                     if !has_fields {
                         actual = strip_sexp_fields(&actual);
                     }
@@ -436,7 +469,10 @@ fn write_tests_to_buffer(
     Ok(())
 }
 
-pub fn parse_tests(path: &Path) -> io::Result<TestEntry> {
+/// Parse the corpus file at the given path, or all of the corpus files in the given
+/// directory. If `extensions` isn't empty, only the files in the directory with one
+/// of those extensions are treated as corpus files.
+pub fn parse_tests(path: &Path, extensions: &[String]) -> io::Result<TestEntry> {
     let name = path
         .file_stem()
         .and_then(|s| s.to_str())
@@ -447,7 +483,8 @@ pub fn parse_tests(path: &Path) -> io::Result<TestEntry> {
         for entry in fs::read_dir(path)? {
             let entry = entry?;
             let hidden = entry.file_name().to_str().unwrap_or("").starts_with('.');
-            if !hidden {
+            if !hidden && (entry.file_type()?.is_dir() || has_extension(&entry.path(), extensions))
+            {
                 children.push(entry.path());
             }
         }
@@ -458,7 +495,7 @@ pub fn parse_tests(path: &Path) -> io::Result<TestEntry> {
         });
         let children = children
             .iter()
-            .map(|path| parse_tests(path))
+            .map(|path| parse_tests(path, extensions))
             .collect::<io::Result<Vec<TestEntry>>>()?;
         Ok(TestEntry::Group {
             name,
@@ -471,6 +508,30 @@ pub fn parse_tests(path: &Path) -> io::Result<TestEntry> {
     }
 }
 
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    extensions.is_empty()
+        || path.extension().is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|e| extension == e.strip_prefix('.').unwrap_or(e))
+        })
+}
+
+/// Render the tree as `parse` prints it, without the positions of the nodes and
+/// with its whitespace normalized in the same way as the expected output.
+fn render_parse_output(tree: &Tree) -> Result<String> {
+    let mut output = Vec::new();
+    let flags = parse::SexpFlags {
+        hide_positions: true,
+        ..Default::default()
+    };
+    parse::write_sexp(&mut output, tree.root_node(), flags)?;
+    let output = String::from_utf8(output)?;
+    Ok(WHITESPACE_REGEX
+        .replace_all(output.trim(), " ")
+        .replace(" )", ")"))
+}
+
 #[must_use]
 pub fn strip_sexp_fields(sexp: &str) -> String {
     SEXP_FIELD_REGEX.replace_all(sexp, " (").to_string()
@@ -669,6 +730,22 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compare_output_from_name() {
+        assert_eq!(
+            CompareOutput::from_name("tree").unwrap(),
+            CompareOutput::Tree
+        );
+        assert_eq!(
+            CompareOutput::from_name("s-expression").unwrap(),
+            CompareOutput::SExpression
+        );
+        assert_eq!(
+            CompareOutput::from_name("sexp").unwrap_err().to_string(),
+            "Invalid output format \"sexp\". Expected one of: tree, s-expression"
+        );
+    }
+
     #[test]
     fn test_parse_test_content_simple() {
         let entry = parse_test_content(
@@ -1211,4 +1288,38 @@ a
             }
         );
     }
+
+    #[test]
+    fn test_parse_tests_with_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let test = "===\na\n===\na\n---\n(a)\n";
+        fs::write(dir.path().join("one.txt"), test).unwrap();
+        fs::write(dir.path().join("two.corpus"), test).unwrap();
+        fs::write(dir.path().join("README.md"), "not a test").unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("nested").join("three.corpus"), test).unwrap();
+
+        let file_names = |entry: &TestEntry| {
+            let TestEntry::Group { children, .. } = entry else {
+                panic!("expected a group");
+            };
+            children
+                .iter()
+                .map(|child| match child {
+                    TestEntry::Group { name, .. } | TestEntry::Example { name, .. } => name.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let entry = parse_tests(dir.path(), &[]).unwrap();
+        assert_eq!(file_names(&entry), ["README", "nested", "one", "two"]);
+
+        let extensions = ["corpus".to_string(), ".txt".to_string()];
+        let entry = parse_tests(dir.path(), &extensions).unwrap();
+        assert_eq!(file_names(&entry), ["nested", "one", "two"]);
+
+        let extensions = ["corpus".to_string()];
+        let entry = parse_tests(dir.path(), &extensions).unwrap();
+        assert_eq!(file_names(&entry), ["nested", "two"]);
+    }
 }
@@ -126,9 +126,9 @@ fn test_language_corpus(
 
     let error_corpus_file = error_corpus_dir.join(format!("{language_name}_errors.txt"));
     let template_corpus_file = template_corpus_dir.join(format!("{language_name}_templates.txt"));
-    let main_tests = parse_tests(&corpus_dir).unwrap();
-    let error_tests = parse_tests(&error_corpus_file).unwrap_or_default();
-    let template_tests = parse_tests(&template_corpus_file).unwrap_or_default();
+    let main_tests = parse_tests(&corpus_dir, &[]).unwrap();
+    let error_tests = parse_tests(&error_corpus_file, &[]).unwrap_or_default();
+    let template_tests = parse_tests(&template_corpus_file, &[]).unwrap_or_default();
     let mut tests = flatten_tests(main_tests);
     tests.extend(flatten_tests(error_tests));
     tests.extend(flatten_tests(template_tests).into_iter().map(|mut t| {
@@ -368,7 +368,7 @@ fn test_feature_corpus_files() {
             let corpus_path = test_path.join("corpus.txt");
             let c_code = generate_result.unwrap().1;
             let language = get_test_language(language_name, &c_code, Some(&test_path));
-            let test = parse_tests(&corpus_path).unwrap();
+            let test = parse_tests(&corpus_path, &[]).unwrap();
             let tests = flatten_tests(test);
 
             if !tests.is_empty() {
@@ -82,6 +82,131 @@ fn detect_language_by_first_line_regex() {
     );
 }
 
+#[test]
+fn detect_language_by_content_regex() {
+    let make_dir = tree_sitter_dir(
+        r#"{
+  "name": "tree-sitter-make",
+  "version": "0.0.1",
+  "tree-sitter": [
+    {
+      "scope": "source.make",
+      "file-types": [
+        "mk"
+      ],
+      "content-regex": "^[a-z]+:\\s*$"
+    }
+  ]
+}
+"#,
+        "make",
+    );
+
+    let mut loader = Loader::with_parser_lib_path(scratch_dir().to_path_buf());
+    loader
+        .find_language_configurations_at_path(make_dir.path(), false)
+        .unwrap();
+
+    let content_scope = |file_name: &Path| {
+        loader
+            .language_configuration_for_content_regex(file_name)
+            .unwrap()
+            .and_then(|(_, config)| config.scope.clone())
+    };
+
+    // a file without a known extension is recognized by its content
+    let file_name = make_dir.path().join("Rules");
+    std::fs::write(&file_name, "# build rules\nall:\n\tcc main.c\n").unwrap();
+    assert!(get_lang_scope(&loader, &file_name).is_none());
+    assert_eq!(content_scope(&file_name), Some("source.make".into()));
+
+    let file_name = make_dir.path().join("Notes");
+    std::fs::write(&file_name, "all: of these words\n").unwrap();
+    assert!(content_scope(&file_name).is_none());
+}
+
+#[test]
+fn detect_language_by_given_contents() {
+    let strace_dir = tree_sitter_dir(
+        r#"{
+  "name": "tree-sitter-strace",
+  "version": "0.0.1",
+  "tree-sitter": [
+    {
+      "scope": "source.strace",
+      "file-types": [
+        "strace"
+      ],
+      "first-line-regex":  "[0-9:.]* *execve"
+    }
+  ]
+}
+"#,
+        "strace",
+    );
+
+    let mut loader = Loader::with_parser_lib_path(scratch_dir().to_path_buf());
+    loader
+        .find_language_configurations_at_path(strace_dir.path(), false)
+        .unwrap();
+    let strace = loader
+        .language_configuration_for_scope("source.strace")
+        .unwrap()
+        .unwrap()
+        .0;
+
+    // the contents are used in place of the file, which doesn't exist, as for
+    // a gzipped file that is detected by its name without the `.gz` suffix
+    let empty_dir = tempfile::tempdir().unwrap();
+    let file_name = empty_dir.path().join("trace");
+    let select = |loader: &mut Loader, contents: &[u8]| {
+        loader
+            .select_language_with_contents(&file_name, empty_dir.path(), None, Some(contents))
+            .ok()
+    };
+    assert_eq!(select(&mut loader, b"447845 execve\nworld"), Some(strace));
+    assert_eq!(select(&mut loader, b"hello\nexecve"), None);
+}
+
+#[test]
+fn load_only_compiled_languages() {
+    let dir = tree_sitter_dir(
+        r#"{
+  "name": "tree-sitter-uncompiled",
+  "version": "0.0.1",
+  "tree-sitter": [
+    {
+      "scope": "source.uncompiled",
+      "file-types": [
+        "uncompiled"
+      ]
+    }
+  ]
+}
+"#,
+        "uncompiled",
+    );
+
+    let lib_dir = tempfile::tempdir().unwrap();
+    let mut loader = Loader::with_parser_lib_path(lib_dir.path().to_path_buf());
+    loader
+        .find_language_configurations_at_path(dir.path(), false)
+        .unwrap();
+    let (configuration, _) = loader.get_all_language_configurations()[0];
+
+    // the grammar isn't compiled until its language is requested
+    assert!(loader
+        .compiled_language_for_configuration(configuration)
+        .unwrap()
+        .is_none());
+    assert!(!loader.library_path("uncompiled").exists());
+    loader.language_for_configuration(configuration).unwrap();
+    assert!(loader
+        .compiled_language_for_configuration(configuration)
+        .unwrap()
+        .is_some());
+}
+
 fn tree_sitter_dir(package_json: &str, name: &str) -> tempfile::TempDir {
     let temp_dir = tempfile::tempdir().unwrap();
     std::fs::write(temp_dir.path().join("package.json"), package_json).unwrap();
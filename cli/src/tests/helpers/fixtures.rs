@@ -7,7 +7,7 @@ use tree_sitter_highlight::HighlightConfiguration;
 use tree_sitter_loader::{CompileConfig, Loader};
 use tree_sitter_tags::TagsConfiguration;
 
-use crate::generate::ALLOC_HEADER;
+use crate::generate::{generate_parser_for_grammar, ALLOC_HEADER};
 
 include!("./dirs.rs");
 
@@ -132,3 +132,63 @@ pub fn get_test_language(name: &str, parser_code: &str, path: Option<&Path>) ->
 
     TEST_LOADER.load_language_at_path_with_name(config).unwrap()
 }
+
+/// A small grammar of assignments like `a = (1);`, with `name` and `value` fields
+/// and `//` comments, for the tests that need a tree without loading one of the
+/// fixture grammars.
+pub fn get_assignment_language() -> Language {
+    let (grammar_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_grammar_for_assignment_statements",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"},
+                {"type": "SYMBOL", "name": "comment"}
+            ],
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "name",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "="},
+                        {
+                            "type": "FIELD",
+                            "name": "value",
+                            "content": {"type": "SYMBOL", "name": "_expression"}
+                        },
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "_expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "SYMBOL", "name": "parenthesized"}
+                    ]
+                },
+                "parenthesized": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "("},
+                        {"type": "SYMBOL", "name": "_expression"},
+                        {"type": "STRING", "value": ")"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"},
+                "comment": {"type": "PATTERN", "value": "//.*"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    get_test_language(&grammar_name, &parser_code, None)
+}
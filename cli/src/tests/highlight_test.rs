@@ -1,4 +1,9 @@
-use super::helpers::fixtures::{get_highlight_config, get_language, get_language_queries_path};
+use super::helpers::fixtures::{
+    get_assignment_language, get_highlight_config, get_language, get_language_queries_path,
+};
+use crate::highlight::{
+    ansi_lines, coverage, names_missing_from_theme, truncate_line, Style, Theme,
+};
 use lazy_static::lazy_static;
 use std::ffi::CString;
 use std::os::raw::c_char;
@@ -7,6 +12,7 @@ use std::{fs, ptr, slice, str};
 use tree_sitter_highlight::{
     c, Error, Highlight, HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer,
 };
+use tree_sitter_loader::Loader;
 
 lazy_static! {
     static ref JS_HIGHLIGHT: HighlightConfiguration =
@@ -678,6 +684,90 @@ fn test_decode_utf8_lossy() {
     assert_eq!(parts, vec!["hello", "\u{fffd}", "\u{fffd}"]);
 }
 
+#[test]
+fn test_highlight_coverage() {
+    let language = get_assignment_language();
+    let theme = Theme {
+        styles: vec![Style::default(), Style::default()],
+        highlight_names: vec!["number".to_string(), "operator".to_string()],
+    };
+    let mut config = HighlightConfiguration::new(
+        language,
+        "test",
+        "(identifier) @variable (number) @number.integer \"=\" @operator (program) @_program",
+        "",
+        "",
+    )
+    .unwrap();
+    config.configure(&theme.highlight_names);
+
+    assert_eq!(names_missing_from_theme(&config, &theme), ["variable"]);
+
+    let loader = Loader::with_parser_lib_path(std::env::temp_dir());
+    let source = b"ab = 1;\ncd = 23;";
+    let coverage = coverage(&loader, source, &config, None).unwrap();
+    let unhighlighted = coverage
+        .unhighlighted_ranges
+        .iter()
+        .map(|range| str::from_utf8(&source[range.clone()]).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(unhighlighted, ["ab", ";\ncd", ";"]);
+    assert_eq!(coverage.unhighlighted_bytes, 6);
+    assert_eq!(coverage.total_bytes, 11);
+}
+
+#[test]
+fn test_ansi_lines() {
+    let theme = Theme {
+        styles: vec![
+            Style {
+                ansi: ansi_term::Color::Blue.normal(),
+                css: None,
+            },
+            Style {
+                ansi: ansi_term::Style::new().bold(),
+                css: None,
+            },
+        ],
+        highlight_names: vec!["number".to_string(), "operator".to_string()],
+    };
+    let mut config = HighlightConfiguration::new(
+        get_assignment_language(),
+        "test",
+        "(number) @number \"=\" @operator",
+        "",
+        "",
+    )
+    .unwrap();
+    config.configure(&theme.highlight_names);
+
+    let source = b"ab = 1;\r\nabcdef = 123456;\n";
+    let lines = |max_width| ansi_lines(&theme, source, &config, None, max_width).unwrap();
+    let blue = |text: &str| ansi_term::Color::Blue.paint(text).to_string();
+    let bold = |text: &str| ansi_term::Style::new().bold().paint(text).to_string();
+
+    assert_eq!(
+        lines(None),
+        [
+            format!("ab {} {};", bold("="), blue("1")),
+            format!("abcdef {} {};", bold("="), blue("123456")),
+            String::new(),
+        ]
+    );
+
+    // Long lines are cut like `truncate_line` cuts them, keeping their styles.
+    assert_eq!(truncate_line("abcdef = 123456;", 12), "abcdef = 12…");
+    assert_eq!(
+        lines(Some(12)),
+        [
+            format!("ab {} {};", bold("="), blue("1")),
+            format!("abcdef {} {}…", bold("="), blue("12")),
+            String::new(),
+        ]
+    );
+    assert_eq!(lines(Some(0)), ["", "", ""]);
+}
+
 fn c_string(s: &str) -> CString {
     CString::new(s.as_bytes().to_vec()).unwrap()
 }
@@ -1,4 +1,6 @@
-use super::helpers::fixtures::get_language;
+use super::helpers::fixtures::{get_language, get_test_language};
+use crate::generate::generate_parser_for_grammar;
+use crate::symbols::{extras_info, ExtraInfo};
 use tree_sitter::Parser;
 
 #[test]
@@ -62,3 +64,70 @@ fn test_lookahead_iterator_modifiable_only_by_mut() {
     let mut names = lookahead.iter_names();
     let _ = names.next();
 }
+
+#[test]
+fn test_extras_info() {
+    let grammar_json = r#"
+    {
+        "name": "test_grammar_for_extras_info",
+        "word": "identifier",
+        "extras": [
+            {"type": "PATTERN", "value": "\\s+"},
+            {"type": "SYMBOL", "name": "comment"},
+            {"type": "STRING", "value": ";"},
+            {"type": "STRING", "value": "\\"}
+        ],
+        "rules": {
+            "program": {
+                "type": "REPEAT",
+                "content": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": ";"}
+                    ]
+                }
+            },
+            "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+            "comment": {"type": "PATTERN", "value": "//.*"}
+        }
+    }
+    "#;
+    let (grammar_name, parser_code) = generate_parser_for_grammar(grammar_json).unwrap();
+    let language = get_test_language(&grammar_name, &parser_code, None);
+
+    // The whitespace pattern and the backslash, which no rule uses, don't produce
+    // nodes, so they aren't listed.
+    let info = extras_info(&language, grammar_json).unwrap();
+    assert_eq!(info.word_token.as_deref(), Some("identifier"));
+    assert_eq!(
+        info.extras,
+        [
+            ExtraInfo {
+                kind: "comment".to_string(),
+                named: true,
+            },
+            ExtraInfo {
+                kind: ";".to_string(),
+                named: false,
+            },
+        ]
+    );
+
+    let info = extras_info(&language, r#"{"extras": []}"#).unwrap();
+    assert_eq!(info.word_token, None);
+    assert!(info.extras.is_empty());
+
+    // A grammar.json that doesn't match the language is an error.
+    let stale_grammar_json = grammar_json.replace(r#""name": "comment""#, r#""name": "note""#);
+    assert_eq!(
+        extras_info(&language, &stale_grammar_json)
+            .unwrap_err()
+            .to_string(),
+        "\"note\" is not a symbol of the language, grammar.json may be out of date"
+    );
+    let stale_grammar_json =
+        grammar_json.replace(r#""word": "identifier""#, r#""word": "program2""#);
+    assert!(extras_info(&language, &stale_grammar_json).is_err());
+    assert!(extras_info(&language, "{").is_err());
+}
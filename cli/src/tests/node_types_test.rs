@@ -0,0 +1,87 @@
+use super::helpers::fixtures::get_test_language;
+use crate::generate::generate_parser_for_grammar;
+use crate::node_types::{NodeType, NodeTypes};
+
+#[test]
+fn test_compare_node_types_with_language() {
+    let (grammar_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_grammar_for_node_types",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"}
+            ],
+            "supertypes": ["_expression"],
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "_expression"}
+                },
+                "_expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "SYMBOL", "name": "sum"}
+                    ]
+                },
+                "sum": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "STRING", "value": "+"},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]
+                },
+                "number": {"type": "PATTERN", "value": "\\d+"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&grammar_name, &parser_code, None);
+
+    let node_types = NodeTypes::parse(
+        r#"[
+            {
+                "type": "_expression",
+                "named": true,
+                "subtypes": [
+                    {"type": "number", "named": true},
+                    {"type": "sum", "named": true}
+                ]
+            },
+            {"type": "program", "named": true},
+            {"type": "sum", "named": true},
+            {"type": "+", "named": false},
+            {"type": "number", "named": true}
+        ]"#,
+    )
+    .unwrap();
+    assert!(node_types.compare_with_language(&language).is_empty());
+
+    // a stale file, missing a token and still listing a removed rule
+    let node_types = NodeTypes::parse(
+        r#"[
+            {"type": "program", "named": true},
+            {"type": "product", "named": true},
+            {"type": "sum", "named": true},
+            {"type": "number", "named": true}
+        ]"#,
+    )
+    .unwrap();
+    let mismatch = node_types.compare_with_language(&language);
+    assert_eq!(
+        mismatch.missing_from_node_types,
+        vec![NodeType {
+            kind: "+".to_string(),
+            named: false
+        }]
+    );
+    assert_eq!(
+        mismatch.missing_from_language,
+        vec![NodeType {
+            kind: "product".to_string(),
+            named: true
+        }]
+    );
+}
@@ -1,11 +1,12 @@
 use super::helpers::{
     allocations,
-    fixtures::{get_language, get_test_language},
+    fixtures::{get_assignment_language, get_language, get_test_language},
     query_helpers::{assert_query_matches, Match, Pattern},
     ITERATION_COUNT,
 };
 use crate::{
     generate::generate_parser_for_grammar,
+    query::CaptureOrder,
     tests::helpers::query_helpers::{collect_captures, collect_matches},
 };
 use indoc::indoc;
@@ -5104,3 +5105,62 @@ fn test_query_compiler_oob_access() {
     // UBSAN should not report any OOB access
     assert!(Query::new(&language, "(package_declaration _ (_) @name _)").is_ok());
 }
+
+#[test]
+fn test_capture_order() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let source_code = "b = 2;\na = 1;";
+    let tree = parser.parse(source_code, None).unwrap();
+    let root = tree.root_node();
+    let (first, second) = (root.child(0).unwrap(), root.child(1).unwrap());
+
+    // The captures, as `(pattern_index, capture_index, capture_name, node)`, in the
+    // order that the matches of `(number) @value (identifier) @name (assignment) @def`
+    // would be found in.
+    let captures = [
+        (2, 2, "def", first),
+        (1, 1, "name", first.child(0).unwrap()),
+        (0, 0, "value", first.child(2).unwrap()),
+        (2, 2, "def", second),
+        (1, 1, "name", second.child(0).unwrap()),
+        (0, 0, "value", second.child(2).unwrap()),
+    ];
+    let sorted = |order: CaptureOrder| {
+        let mut captures = captures;
+        order.sort(&mut captures);
+        captures
+            .iter()
+            .map(|(_, _, name, node)| format!("{name} {}", &source_code[node.byte_range()]))
+            .collect::<Vec<_>>()
+    };
+
+    // Captures at the same position start with the outermost node.
+    let mut reversed = captures;
+    reversed.reverse();
+    CaptureOrder::Position.sort(&mut reversed);
+    assert_eq!(reversed, captures);
+
+    assert_eq!(
+        sorted(CaptureOrder::Name),
+        [
+            "def b = 2;",
+            "def a = 1;",
+            "name b",
+            "name a",
+            "value 2",
+            "value 1"
+        ]
+    );
+    assert_eq!(
+        sorted(CaptureOrder::Pattern),
+        [
+            "value 2",
+            "value 1",
+            "name b",
+            "name a",
+            "def b = 2;",
+            "def a = 1;"
+        ]
+    );
+}
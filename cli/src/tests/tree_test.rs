@@ -1,8 +1,17 @@
 use super::helpers::edits::invert_edit;
-use super::helpers::fixtures::get_language;
-use crate::parse::{perform_edit, Edit};
+use super::helpers::fixtures::{get_assignment_language, get_language, get_test_language};
+use crate::generate::generate_parser_for_grammar;
+use crate::parse::{
+    ancestors_for_point, deepest_path, dot_graph, error_nodes, field_schema, first_divergence,
+    first_node_of_kind, included_ranges, node_reuse, parse_byte_range_flag, parse_range_flag,
+    reparse_with_edits, tree_checksum, tree_memory, validate_incremental_parse,
+    write_canonical_sexp, write_sexp, Edit, SexpFlags,
+};
+use crate::saved_tree::SavedTree;
+use crate::test::CompareOutput;
+use std::path::Path;
 use std::str;
-use tree_sitter::{InputEdit, Parser, Point, Range, Tree};
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Range, Tree};
 
 #[test]
 fn test_tree_edit() {
@@ -678,6 +687,519 @@ fn test_get_changed_ranges() {
     }
 }
 
+#[test]
+fn test_reparse_with_edits() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+
+    let mut source_code = b"abc 123 def".to_vec();
+    let tree = parser.parse(&source_code, None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(list (identifier) (number) (identifier))"
+    );
+
+    // Replace `123` with `xyz` - that token has changed syntax
+    let edit = Edit {
+        position: index_of(&source_code, "123"),
+        deleted_length: 3,
+        inserted_text: b"xyz".to_vec(),
+    };
+    let (tree, ranges) = reparse_with_edits(&mut parser, &tree, &mut source_code, &[edit]).unwrap();
+    assert_eq!(source_code, b"abc xyz def");
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(list (identifier) (identifier) (identifier))"
+    );
+    assert_eq!(ranges, vec![range_of(&source_code, "xyz")]);
+
+    // Several edits are applied in order before a single reparse
+    let edits = [
+        Edit {
+            position: 0,
+            deleted_length: 3,
+            inserted_text: b"1".to_vec(),
+        },
+        Edit {
+            position: index_of(&source_code, "def") - 2,
+            deleted_length: 3,
+            inserted_text: b"2".to_vec(),
+        },
+    ];
+    let (tree, ranges) = reparse_with_edits(&mut parser, &tree, &mut source_code, &edits).unwrap();
+    assert_eq!(source_code, b"1 xyz 2");
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(list (number) (identifier) (number))"
+    );
+    assert_eq!(
+        ranges,
+        vec![range_of(&source_code, "1"), range_of(&source_code, "2")]
+    );
+}
+
+#[test]
+fn test_canonical_sexp_is_stable() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+    let mut canonical_sexp = |source_code: &str| {
+        let tree = parser.parse(source_code, None).unwrap();
+        let mut output = Vec::new();
+        write_canonical_sexp(&mut output, tree.root_node()).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = canonical_sexp("abc   123\n\n\tdef\r\n456 ");
+    assert_eq!(
+        output,
+        "(list (identifier) (number) (identifier) (number))\n"
+    );
+
+    // Reformatting the source doesn't change the output, unlike the positions in the
+    // default output.
+    assert_eq!(canonical_sexp("abc 123 def 456"), output);
+    assert_eq!(canonical_sexp("\n\n  abc\n123\ndef\n456"), output);
+    assert_ne!(canonical_sexp("abc 123 def"), output);
+}
+
+#[test]
+fn test_write_sexp_without_positions() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+    let tree = parser.parse("abc 123\ndef", None).unwrap();
+
+    let mut output = Vec::new();
+    let flags = SexpFlags {
+        hide_positions: true,
+        ..Default::default()
+    };
+    write_sexp(&mut output, tree.root_node(), flags).unwrap();
+    assert_eq!(
+        str::from_utf8(&output).unwrap(),
+        "(list\n  (identifier)\n  (number)\n  (identifier))\n"
+    );
+}
+
+#[test]
+fn test_write_sexp_with_anonymous_nodes_and_field_ids() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let tree = parser.parse("a = (1);", None).unwrap();
+
+    let mut output = Vec::new();
+    let flags = SexpFlags {
+        anonymous_nodes: true,
+        field_ids: true,
+        hide_positions: true,
+        ..Default::default()
+    };
+    write_sexp(&mut output, tree.root_node(), flags).unwrap();
+    assert_eq!(
+        str::from_utf8(&output).unwrap(),
+        [
+            "(program",
+            "  (assignment",
+            "    name#1: (identifier)",
+            r#"    ("=")"#,
+            "    value#2: (parenthesized",
+            r#"      ("(")"#,
+            "      (number)",
+            r#"      (")"))"#,
+            r#"    (";")))"#,
+            "",
+        ]
+        .join("\n")
+    );
+
+    // Without `field_ids`, fields are printed by name only.
+    let mut output = Vec::new();
+    let flags = SexpFlags {
+        hide_positions: true,
+        ..Default::default()
+    };
+    write_sexp(&mut output, tree.root_node(), flags).unwrap();
+    assert_eq!(
+        str::from_utf8(&output).unwrap(),
+        "(program\n  (assignment\n    name: (identifier)\n    value: (parenthesized\n      (number))))\n"
+    );
+}
+
+#[test]
+fn test_parse_byte_range() {
+    let source_code = b"abc 12\nde 34 fg\n56";
+    let path = Path::new("list.txt");
+    let byte_range = parse_byte_range_flag("10:18").unwrap();
+    let ranges = included_ranges(source_code, path, Some(&byte_range), &[]).unwrap();
+    assert_eq!(
+        ranges,
+        [Range {
+            start_byte: 10,
+            end_byte: 18,
+            start_point: Point::new(1, 3),
+            end_point: Point::new(2, 2),
+        }]
+    );
+
+    // Only the text in the range is parsed, but the nodes keep their rows and
+    // byte offsets in the whole file.
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+    parser.set_included_ranges(&ranges).unwrap();
+    let tree = parser.parse(source_code, None).unwrap();
+    let mut output = Vec::new();
+    write_sexp(&mut output, tree.root_node(), SexpFlags::default()).unwrap();
+    assert_eq!(
+        str::from_utf8(&output).unwrap(),
+        [
+            "(list [1, 3] - [2, 2]",
+            "  (number [1, 3] - [1, 5])",
+            "  (identifier [1, 6] - [1, 8])",
+            "  (number [2, 0] - [2, 2]))",
+            "",
+        ]
+        .join("\n")
+    );
+    assert_eq!(tree.root_node().named_child(2).unwrap().start_byte(), 16);
+
+    assert_eq!(
+        included_ranges(source_code, path, Some(&(10..19)), &[])
+            .unwrap_err()
+            .to_string(),
+        "The byte range 10:19 is past the end of list.txt, which is 18 bytes long"
+    );
+    assert_eq!(
+        parse_byte_range_flag("18:10").unwrap_err().to_string(),
+        "Invalid byte range \"18:10\", the start must be before the end"
+    );
+}
+
+#[test]
+fn test_parse_ranges() {
+    let source_code = b"abc 12\nde 34 fg\n56 hi";
+    let ranges = ["0:0-0:4", "1:6-2:2"]
+        .map(|flag| parse_range_flag(flag).unwrap())
+        .to_vec();
+    let ranges = included_ranges(source_code, Path::new("list.txt"), None, &ranges).unwrap();
+    assert_eq!(
+        ranges
+            .iter()
+            .map(|range| range.start_byte..range.end_byte)
+            .collect::<Vec<_>>(),
+        [0..4, 13..18]
+    );
+
+    // The text between and after the ranges produces no nodes, and the nodes in
+    // the ranges have their positions in the whole file.
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+    parser.set_included_ranges(&ranges).unwrap();
+    let tree = parser.parse(source_code, None).unwrap();
+    let mut output = Vec::new();
+    write_sexp(&mut output, tree.root_node(), SexpFlags::default()).unwrap();
+    assert_eq!(
+        str::from_utf8(&output).unwrap(),
+        [
+            "(list [0, 0] - [2, 2]",
+            "  (identifier [0, 0] - [0, 3])",
+            "  (identifier [1, 6] - [1, 8])",
+            "  (number [2, 0] - [2, 2]))",
+            "",
+        ]
+        .join("\n")
+    );
+
+    let ranges = [parse_range_flag("2:0-3:0").unwrap()];
+    assert!(included_ranges(source_code, Path::new("list.txt"), None, &ranges).is_err());
+}
+
+#[test]
+fn test_error_nodes() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let describe = |nodes: Vec<Node>| {
+        nodes
+            .iter()
+            .map(|node| format!("{} {}", node.kind(), node.start_position()))
+            .collect::<Vec<_>>()
+    };
+
+    let tree = parser.parse("a = (b = (1;", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (ERROR (identifier) (ERROR (identifier)) (number)))"
+    );
+    assert_eq!(
+        describe(error_nodes(&tree, usize::MAX, false)),
+        ["ERROR (0, 0)"]
+    );
+    assert_eq!(
+        describe(error_nodes(&tree, usize::MAX, true)),
+        ["ERROR (0, 0)", "ERROR (0, 5)"]
+    );
+    assert_eq!(describe(error_nodes(&tree, 1, true)), ["ERROR (0, 0)"]);
+
+    let tree = parser.parse("a = 1 2 3; b = (= 4 )", None).unwrap();
+    assert_eq!(
+        describe(error_nodes(&tree, usize::MAX, false)),
+        ["ERROR (0, 6)", "ERROR (0, 16)", "; (0, 21)"]
+    );
+    assert_eq!(
+        describe(error_nodes(&tree, 2, false)),
+        ["ERROR (0, 6)", "ERROR (0, 16)"]
+    );
+}
+
+#[test]
+fn test_tree_checksum() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+
+    let mut checksum = |source_code: &str| tree_checksum(&parser.parse(source_code, None).unwrap());
+    let original = checksum("abc 123 def");
+    assert_eq!(original, 0x7a90_366e_b099_4a21);
+    assert_eq!(original, checksum("abc 123 def"));
+
+    // a change in structure, in a node kind, or in a position changes the checksum
+    assert_ne!(original, checksum("abc 123"));
+    assert_ne!(original, checksum("abc xyz def"));
+    assert_ne!(original, checksum("abc  123 def"));
+}
+
+#[test]
+fn test_ancestors_for_point() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+    let tree = parser.parse("abc  123 def", None).unwrap();
+
+    let kinds = |point| {
+        ancestors_for_point(&tree, point)
+            .into_iter()
+            .map(|(node, _)| node.kind())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(kinds(Point::new(0, 6)), ["list", "number"]);
+    assert_eq!(kinds(Point::new(0, 10)), ["list", "identifier"]);
+
+    // a position at the end of a node is still inside it
+    assert_eq!(kinds(Point::new(0, 3)), ["list", "identifier"]);
+
+    // positions between nodes and after the end are only inside the root
+    assert_eq!(kinds(Point::new(0, 4)), ["list"]);
+    assert_eq!(kinds(Point::new(0, 20)), ["list"]);
+}
+
+#[test]
+fn test_first_node_of_kind() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let tree = parser.parse("a = 1;\nb = 2;", None).unwrap();
+    let root = tree.root_node();
+
+    assert_eq!(first_node_of_kind(root, "program"), Some(root));
+    let assignment = first_node_of_kind(root, "assignment").unwrap();
+    assert_eq!(
+        assignment.to_sexp(),
+        "(assignment name: (identifier) value: (number))"
+    );
+    assert_eq!(assignment.start_position(), Point::new(0, 0));
+    let number = first_node_of_kind(root, "number").unwrap();
+    assert_eq!(number.start_position(), Point::new(0, 4));
+    assert_eq!(
+        first_node_of_kind(root.named_child(1).unwrap(), "=")
+            .unwrap()
+            .start_position(),
+        Point::new(1, 2)
+    );
+    assert_eq!(first_node_of_kind(root, "string"), None);
+}
+
+#[test]
+fn test_tree_memory() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_list_language()).unwrap();
+
+    let tree = parser.parse("abc 123 def", None).unwrap();
+    let memory = tree_memory(&tree);
+    assert_eq!(memory.node_count, 4);
+    assert_eq!(memory.estimated_bytes, 80 + 3 * 8);
+
+    let empty_tree = parser.parse("", None).unwrap();
+    assert_eq!(tree_memory(&empty_tree).estimated_bytes, 0);
+}
+
+#[test]
+fn test_node_reuse() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+
+    let mut source_code = b"a = 1; b = 2; c = 3; d = 4; e = 5; f = 6;".to_vec();
+    let tree = parser.parse(&source_code, None).unwrap();
+    let reuse = node_reuse(&tree, &tree);
+    assert_eq!(reuse.reused_count, 31);
+    assert_eq!(reuse.node_count, 31);
+
+    // Only the nodes in the assignments that the edit doesn't touch can be reused.
+    let edit = Edit {
+        position: index_of(&source_code, "6"),
+        deleted_length: 1,
+        inserted_text: b"7".to_vec(),
+    };
+    let (new_tree, _) = reparse_with_edits(&mut parser, &tree, &mut source_code, &[edit]).unwrap();
+    let reuse = node_reuse(&tree, &new_tree);
+    assert_eq!(reuse.node_count, 31);
+    assert!(reuse.reused_count > 0);
+    assert!(reuse.reused_count < reuse.node_count);
+
+    let fresh_tree = parser.parse(&source_code, None).unwrap();
+    assert_eq!(node_reuse(&tree, &fresh_tree).reused_count, 0);
+}
+
+#[test]
+fn test_validate_incremental_parse() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+
+    let mut source_code = b"a = 1; b = 2; c = 3;".to_vec();
+    let mut tree = parser.parse(&source_code, None).unwrap();
+    for (position, deleted_length, inserted_text) in [(4, 1, "42"), (8, 0, "bb"), (0, 7, "")] {
+        let edit = Edit {
+            position,
+            deleted_length,
+            inserted_text: inserted_text.as_bytes().to_vec(),
+        };
+        let (new_tree, _) =
+            reparse_with_edits(&mut parser, &tree, &mut source_code, &[edit]).unwrap();
+        assert_eq!(
+            validate_incremental_parse(&mut parser, &new_tree, &source_code).unwrap(),
+            None
+        );
+        tree = new_tree;
+    }
+
+    // Trees with different text but the same structure don't diverge.
+    let tree = parser.parse(b"a = 1; b = 2;", None).unwrap();
+    let other_tree = parser.parse(b"x = 3; y = 4;", None).unwrap();
+    assert_eq!(first_divergence(&tree, &other_tree), None);
+
+    let other_tree = parser.parse(b"a = 1; bb = 2;", None).unwrap();
+    let divergence = first_divergence(&tree, &other_tree).unwrap();
+    assert_eq!(divergence.incremental, "name: (identifier [0, 7] - [0, 8])");
+    assert_eq!(divergence.full, "name: (identifier [0, 7] - [0, 9])");
+
+    let other_tree = parser.parse(b"a = 1; b = 2; c = 3;", None).unwrap();
+    let divergence = first_divergence(&tree, &other_tree).unwrap();
+    assert_eq!(divergence.incremental, "(program [0, 0] - [0, 13])");
+    assert_eq!(divergence.full, "(program [0, 0] - [0, 20])");
+
+    let tree = parser.parse(b"a = 1; b = 2; c = 3;", None).unwrap();
+    let other_tree = parser.parse(b"a = 1; b = 2; c = 3", None).unwrap();
+    let divergence = first_divergence(&tree, &other_tree).unwrap();
+    assert_eq!(divergence.incremental, r#"(";" [0, 19] - [0, 20])"#);
+    assert_eq!(divergence.full, r#"(MISSING ";" [0, 19] - [0, 19])"#);
+}
+
+#[test]
+fn test_saved_tree_round_trip() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let tree = parser
+        .parse("a = 1;\n// hi\nb = 2\nc = 3;\n", None)
+        .unwrap();
+
+    let saved_tree = SavedTree::from_tree(&tree);
+    let mut bytes = Vec::new();
+    saved_tree.write(&mut bytes).unwrap();
+    let loaded_tree = SavedTree::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded_tree, saved_tree);
+    assert!(SavedTree::read(&mut &bytes[1..]).is_err());
+
+    // A string length past the end of the file is an error, not an allocation.
+    let mut truncated = bytes[..8].to_vec();
+    truncated.extend(1_u32.to_le_bytes());
+    truncated.extend(u32::MAX.to_le_bytes());
+    truncated.extend(b"abc");
+    assert!(SavedTree::read(&mut truncated.as_slice()).is_err());
+
+    for flags in [
+        SexpFlags {
+            mark_extras: true,
+            ..Default::default()
+        },
+        SexpFlags {
+            mark_extras: true,
+            anonymous_nodes: true,
+            field_ids: true,
+            hide_positions: true,
+        },
+    ] {
+        let mut expected = Vec::new();
+        write_sexp(&mut expected, tree.root_node(), flags).unwrap();
+        let mut actual = Vec::new();
+        loaded_tree.write_sexp(&mut actual, flags).unwrap();
+        assert_eq!(str::from_utf8(&actual), str::from_utf8(&expected));
+    }
+}
+
+#[test]
+fn test_field_schema() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let tree = parser.parse("a = 1; b = (2);", None).unwrap();
+
+    let schema = field_schema(&[tree.root_node()]);
+    let describe = |kind: &str| {
+        schema[kind]
+            .iter()
+            .map(|(field_name, kinds)| {
+                let kinds = kinds.iter().map(String::as_str).collect::<Vec<_>>();
+                format!("{field_name}: {}", kinds.join(" | "))
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(schema.len(), 3);
+    assert_eq!(describe("(program)"), ["children: (assignment)"]);
+    assert_eq!(
+        describe("(assignment)"),
+        ["name: (identifier)", "value: (number) | (parenthesized)"]
+    );
+    assert_eq!(describe("(parenthesized)"), ["children: (number)"]);
+
+    let first_assignment = tree.root_node().named_child(0).unwrap();
+    let schema = field_schema(&[first_assignment]);
+    assert_eq!(schema.len(), 1);
+    assert_eq!(schema["(assignment)"]["value"].len(), 1);
+}
+
+fn get_list_language() -> Language {
+    let (grammar_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_grammar_for_reparse",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"}
+            ],
+            "rules": {
+                "list": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "_item"}
+                },
+                "_item": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    get_test_language(&grammar_name, &parser_code, None)
+}
+
 fn index_of(text: &[u8], substring: &str) -> usize {
     str::from_utf8(text).unwrap().find(substring).unwrap()
 }
@@ -699,9 +1221,128 @@ fn get_changed_ranges(
     source_code: &mut Vec<u8>,
     edit: &Edit,
 ) -> Vec<Range> {
-    perform_edit(tree, source_code, edit).unwrap();
-    let new_tree = parser.parse(&source_code, Some(tree)).unwrap();
-    let result = tree.changed_ranges(&new_tree).collect();
+    let (new_tree, result) =
+        reparse_with_edits(parser, tree, source_code, std::slice::from_ref(edit)).unwrap();
     *tree = new_tree;
     result
 }
+
+#[test]
+fn test_compare_output() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+    let tree = parser.parse("a = (1);\n// c\nb = 2", None).unwrap();
+
+    assert_eq!(
+        CompareOutput::Tree.render(&tree).unwrap(),
+        concat!(
+            "(program ",
+            "(assignment name: (identifier) value: (parenthesized (number))) ",
+            "(comment) ",
+            "(assignment name: (identifier) value: (number) (MISSING \";\")))",
+        )
+    );
+    // Like the output of `parse`, this leaves out anonymous nodes, even missing ones.
+    assert_eq!(
+        CompareOutput::SExpression.render(&tree).unwrap(),
+        concat!(
+            "(program ",
+            "(assignment name: (identifier) value: (parenthesized (number))) ",
+            "(comment) ",
+            "(assignment name: (identifier) value: (number)))",
+        )
+    );
+}
+
+#[test]
+fn test_deepest_path() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+
+    let tree = parser.parse("a = 1;", None).unwrap();
+    assert_eq!(deepest_path(&tree), ["program", "assignment", "identifier"]);
+
+    // The path leads to the first of the deepest nodes, which can be anonymous.
+    let tree = parser
+        .parse("a = (1);\nb = ((2));\nc = ((3));", None)
+        .unwrap();
+    assert_eq!(
+        deepest_path(&tree),
+        [
+            "program",
+            "assignment",
+            "parenthesized",
+            "parenthesized",
+            "\"(\""
+        ]
+    );
+
+    let tree = parser.parse("a = (((((1)))));", None).unwrap();
+    let path = deepest_path(&tree);
+    assert_eq!(path.len(), 8);
+    assert_eq!(path[2..7], ["parenthesized"; 5]);
+    assert_eq!(path[7], "\"(\"");
+
+    let tree = parser.parse("", None).unwrap();
+    assert_eq!(deepest_path(&tree), ["program"]);
+}
+
+#[test]
+fn test_dot_graph() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_assignment_language()).unwrap();
+
+    let tree = parser.parse("a = (1);", None).unwrap();
+    assert_eq!(
+        dot_graph(&tree, false),
+        r#"digraph tree {
+  node [shape=box];
+  n0 [label="program 0..8"];
+  n1 [label="assignment 0..8"];
+  n0 -> n1;
+  n2 [label="identifier 0..1"];
+  n1 -> n2 [label="name"];
+  n3 [label="\"=\" 2..3"];
+  n1 -> n3;
+  n4 [label="parenthesized 4..7"];
+  n1 -> n4 [label="value"];
+  n5 [label="\"(\" 4..5"];
+  n4 -> n5;
+  n6 [label="number 5..6"];
+  n4 -> n6;
+  n7 [label="\")\" 6..7"];
+  n4 -> n7;
+  n8 [label="\";\" 7..8"];
+  n1 -> n8;
+}
+"#
+    );
+
+    // Error and missing nodes are red.
+    let tree = parser.parse("a = = 1;", None).unwrap();
+    assert_eq!(
+        dot_graph(&tree, true),
+        r#"digraph tree {
+  node [shape=box];
+  n0 [label="program"];
+  n1 [label="assignment"];
+  n0 -> n1;
+  n2 [label="identifier"];
+  n1 -> n2 [label="name"];
+  n3 [label="\"=\""];
+  n1 -> n3;
+  n4 [label="ERROR", color=red];
+  n1 -> n4;
+  n5 [label="\"=\""];
+  n4 -> n5;
+  n6 [label="number"];
+  n1 -> n6 [label="value"];
+  n7 [label="\";\""];
+  n1 -> n7;
+}
+"#
+    );
+    let tree = parser.parse("a = (1;", None).unwrap();
+    let graph = dot_graph(&tree, false);
+    assert!(graph.contains("  n7 [label=\"MISSING ) 6..6\", color=red];\n  n4 -> n7;\n"));
+}
@@ -1,4 +1,6 @@
 use std::{
+    fs,
+    io::{self, Read, Write},
     path::{Path, PathBuf},
     process::{Child, ChildStdin, Command, Stdio},
     sync::{
@@ -50,6 +52,221 @@ pub fn lang_not_found_for_path(path: &Path, loader_config: &LoaderConfig) -> Str
     )
 }
 
+/// The path that stands for the standard input, for the commands that can read from it.
+pub const STDIN_PATH: &str = "-";
+
+/// Read a source file, decompressing it first if it is gzipped, or the standard
+/// input if the path is [`STDIN_PATH`].
+pub fn read_source(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new(STDIN_PATH) {
+        let mut source = Vec::new();
+        io::stdin()
+            .read_to_end(&mut source)
+            .context("Failed to read from stdin")?;
+        return Ok(source);
+    }
+
+    let contents = fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+    if !is_gzipped(path) {
+        return Ok(contents);
+    }
+    #[cfg(feature = "gzip")]
+    {
+        let mut source = Vec::new();
+        flate2::read::GzDecoder::new(contents.as_slice())
+            .read_to_end(&mut source)
+            .with_context(|| format!("Failed to decompress {path:?}"))?;
+        Ok(source)
+    }
+    #[cfg(not(feature = "gzip"))]
+    Err(anyhow!(
+        "{path:?} is gzipped, which requires the CLI to be built with the `gzip` feature"
+    ))
+}
+
+/// The error for reading from the standard input without selecting a language.
+#[must_use]
+pub fn stdin_requires_scope() -> anyhow::Error {
+    anyhow!("Reading from stdin (`{STDIN_PATH}`) requires --scope to select the language")
+}
+
+/// The path by which the language of a source file is selected, which leaves out
+/// the `.gz` suffix of a gzipped file.
+#[must_use]
+pub fn language_detection_path(path: &Path) -> PathBuf {
+    if is_gzipped(path) {
+        path.with_extension("")
+    } else {
+        path.to_owned()
+    }
+}
+
+/// The contents by which the language of a source file is selected, which are
+/// only read up front for a gzipped file, as its detection path doesn't name the
+/// decompressed file.
+pub fn language_detection_contents(path: &Path) -> Result<Option<Vec<u8>>> {
+    if is_gzipped(path) {
+        read_source(path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// Read the paths listed in a `--paths` file, one per line. Blank lines and lines
+/// starting with `#` are ignored, and a line starting with `!` removes a path that
+/// an earlier line listed.
+#[must_use]
+pub fn paths_from_paths_file(contents: &str) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(path) = line.strip_prefix('!') {
+            let path = path.trim_start();
+            if let Some(index) = result.iter().position(|p| p == path) {
+                result.remove(index);
+            }
+        } else {
+            result.push(line.to_string());
+        }
+    }
+    result
+}
+
+/// The paths that match a glob pattern. Unless `follow_symlinks` is set, the paths
+/// that pass through a symbolic link after the start of the pattern are skipped.
+pub fn glob_paths(pattern: &str, follow_symlinks: bool) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).with_context(|| format!("Invalid glob pattern {pattern:?}"))?;
+    let root = glob_root(pattern);
+    let mut result = Vec::new();
+    for entry in paths {
+        let entry = entry?;
+        if follow_symlinks || !passes_through_symlink(&entry, &root) {
+            result.push(entry);
+        }
+    }
+    Ok(result)
+}
+
+/// The directory that a glob pattern starts matching from, i.e. the components of
+/// the pattern before the first one containing a wildcard.
+fn glob_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+/// Check if the path is a symbolic link, or is in a directory beneath `root` that
+/// is one. Links in `root` itself were given explicitly, and are always followed.
+fn passes_through_symlink(path: &Path, root: &Path) -> bool {
+    path.ancestors()
+        .take_while(|ancestor| *ancestor != root && !ancestor.as_os_str().is_empty())
+        .any(|ancestor| {
+            fs::symlink_metadata(ancestor).is_ok_and(|metadata| metadata.file_type().is_symlink())
+        })
+}
+
+/// Remove the paths that match any of the `--exclude` globs. A glob without a `/`
+/// matches a path's file name, and any other glob matches the whole path.
+pub fn exclude_paths(paths: Vec<String>, excludes: &[String]) -> Result<Vec<String>> {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let patterns = excludes
+        .iter()
+        .map(|exclude| {
+            glob::Pattern::new(exclude.trim_start_matches("./"))
+                .with_context(|| format!("Invalid exclude pattern {exclude:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(paths
+        .into_iter()
+        .filter(|path| {
+            let path = Path::new(path.trim_start_matches("./"));
+            !patterns.iter().any(|pattern| {
+                if pattern.as_str().contains('/') {
+                    pattern.matches_path_with(path, options)
+                } else {
+                    path.file_name()
+                        .is_some_and(|name| pattern.matches_path_with(Path::new(name), options))
+                }
+            })
+        })
+        .collect())
+}
+
+/// Prints the lines of a source file around the ranges of rows reported in it, for
+/// `--context`. Each line is printed at most once, even when the windows around
+/// the reported rows overlap, and gaps between the printed lines are marked with
+/// `--`.
+pub struct SourceContext {
+    lines: Vec<String>,
+    context: usize,
+    printed: Vec<bool>,
+    last_printed_row: Option<usize>,
+}
+
+impl SourceContext {
+    #[must_use]
+    pub fn new(source: &[u8], context: usize) -> Self {
+        let mut lines = String::from_utf8_lossy(source)
+            .split('\n')
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect::<Vec<_>>();
+        if lines.len() > 1 && lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        Self {
+            printed: vec![false; lines.len()],
+            lines,
+            context,
+            last_printed_row: None,
+        }
+    }
+
+    /// Print the lines from `context` lines before `start_row` to `context` lines
+    /// after `end_row` that haven't been printed yet, prefixed with their row.
+    pub fn write(
+        &mut self,
+        out: &mut impl Write,
+        indent: &str,
+        start_row: usize,
+        end_row: usize,
+    ) -> io::Result<()> {
+        let Some(last_row) = self.lines.len().checked_sub(1) else {
+            return Ok(());
+        };
+        let row_width = last_row.to_string().len();
+        let first = start_row.saturating_sub(self.context);
+        let last = end_row.saturating_add(self.context).min(last_row);
+        for row in first..=last {
+            if self.printed[row] {
+                continue;
+            }
+            if self.last_printed_row.is_some_and(|last| last + 1 != row) {
+                writeln!(out, "{indent}--")?;
+            }
+            let line = format!("{indent}{row:>row_width$} | {}", self.lines[row]);
+            writeln!(out, "{}", line.trim_end())?;
+            self.printed[row] = true;
+            self.last_printed_row = Some(row);
+        }
+        Ok(())
+    }
+}
+
 #[must_use]
 pub fn cancel_on_signal() -> Arc<AtomicUsize> {
     let result = Arc::new(AtomicUsize::new(0));
@@ -128,3 +345,149 @@ impl Drop for LogSession {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_from_paths_file() {
+        let contents = indoc! {"
+            # The files with known parse errors
+            examples/a.js
+
+              examples/b.js  \t
+            # examples/c.js
+            examples/d.js
+            !examples/b.js
+            ! examples/d.js
+            !examples/never-listed.js
+        "};
+        assert_eq!(paths_from_paths_file(contents), ["examples/a.js"]);
+        assert!(paths_from_paths_file("\n# nothing\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_language_detection_path() {
+        assert_eq!(
+            language_detection_path(Path::new("src/a.rs.gz")),
+            Path::new("src/a.rs")
+        );
+        assert_eq!(
+            language_detection_path(Path::new("src/a.rs")),
+            Path::new("src/a.rs")
+        );
+        assert_eq!(language_detection_path(Path::new("a.gz")), Path::new("a"));
+    }
+
+    #[test]
+    fn test_glob_root() {
+        assert_eq!(glob_root("src/**/*.js"), Path::new("src"));
+        assert_eq!(glob_root("src/a/b?.js"), Path::new("src/a"));
+        assert_eq!(glob_root("*.js"), Path::new(""));
+        assert_eq!(glob_root("src/a.js"), Path::new("src/a.js"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_glob_paths_with_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("real")).unwrap();
+        fs::write(root.join("real/a.txt"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+        let glob = |pattern: &str, follow_symlinks| {
+            glob_paths(&root.join(pattern).to_string_lossy(), follow_symlinks)
+                .unwrap()
+                .into_iter()
+                .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        // By default, the link beneath the start of the pattern is skipped.
+        assert_eq!(glob("*/*.txt", false), [Path::new("real/a.txt")]);
+        assert_eq!(
+            glob("*/*.txt", true),
+            [Path::new("link/a.txt"), Path::new("real/a.txt")]
+        );
+
+        // A link before the first wildcard was given explicitly, so it's followed.
+        assert!(passes_through_symlink(
+            &root.join("link/a.txt"),
+            &glob_root(&root.join("*/*.txt").to_string_lossy())
+        ));
+        assert!(!passes_through_symlink(
+            &root.join("link/a.txt"),
+            &root.join("link")
+        ));
+        assert_eq!(glob("link/*.txt", false), [Path::new("link/a.txt")]);
+    }
+
+    #[test]
+    fn test_exclude_paths() {
+        let paths = [
+            "src/a.js",
+            "src/b.min.js",
+            "./src/vendor/c.js",
+            "test/d.js",
+            "test/vendor/e.js",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(exclude_paths(paths.clone(), &[]).unwrap(), paths);
+        assert_eq!(
+            exclude_paths(paths.clone(), &["*.min.js".to_string()]).unwrap(),
+            [
+                "src/a.js",
+                "./src/vendor/c.js",
+                "test/d.js",
+                "test/vendor/e.js"
+            ]
+        );
+        assert_eq!(
+            exclude_paths(
+                paths.clone(),
+                &["src/vendor/*".to_string(), "test/**/*.js".to_string()]
+            )
+            .unwrap(),
+            ["src/a.js", "src/b.min.js"]
+        );
+        assert_eq!(
+            exclude_paths(paths.clone(), &["**/vendor/**".to_string()]).unwrap(),
+            ["src/a.js", "src/b.min.js", "test/d.js"]
+        );
+        assert!(exclude_paths(paths, &["[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_source_context() {
+        let source = b"zero\none\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+        let mut context = SourceContext::new(source, 1);
+        let mut out = Vec::new();
+        context.write(&mut out, "  ", 2, 2).unwrap();
+        context.write(&mut out, "  ", 3, 4).unwrap();
+        context.write(&mut out, "  ", 9, 10).unwrap();
+        context.write(&mut out, "  ", 0, 0).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            indoc! {"
+                 1 | one
+                 2 | two
+                 3 | three
+                 4 | four
+                 5 | five
+                --
+                 8 | eight
+                 9 | nine
+                10 | ten
+                --
+                 0 | zero
+            "}
+            .lines()
+            .map(|line| format!("  {line}\n"))
+            .collect::<String>()
+        );
+    }
+}
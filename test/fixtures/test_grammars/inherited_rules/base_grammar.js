@@ -0,0 +1,17 @@
+module.exports = grammar({
+  name: "base_rules",
+
+  extras: $ => [/\s/, $.comment],
+
+  word: $ => $.identifier,
+
+  rules: {
+    program: $ => repeat($.statement),
+    statement: $ => seq($.expression, ";"),
+    expression: $ => choice($.identifier, $.number, $.call),
+    call: $ => seq($.identifier, "(", ")"),
+    identifier: $ => /[a-z]+/,
+    number: $ => /\d+/,
+    comment: $ => token(seq("#", /.*/)),
+  }
+})
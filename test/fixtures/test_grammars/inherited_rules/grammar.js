@@ -0,0 +1,10 @@
+// A grammar that extends another one, replacing one of its rules. The base
+// grammar's `call` rule is no longer used, so it must be removed.
+module.exports = grammar(require("./base_grammar"), {
+  name: "inherited_rules",
+
+  rules: {
+    expression: $ => choice($.identifier, $.number, $.string),
+    string: $ => /"[^"]*"/,
+  }
+})